@@ -16,14 +16,6 @@ impl AudioSystem {
         Ok(AudioSystem { manager })
     }
     
-    pub fn play_file(&mut self, file_path: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let sound_data = StaticSoundData::from_file(file_path)?;
-        let sound = sound_data.with_settings(StaticSoundSettings::new().volume(Volume::Amplitude(0.5)));
-        self.manager.play(sound)?;
-        println!("🎵 Playing audio file: {}", file_path);
-        Ok(())
-    }
-    
     pub fn play_file_looped(&mut self, file_path: &str, volume: f32) -> Result<(), Box<dyn std::error::Error>> {
         let sound_data = StaticSoundData::from_file(file_path)?;
         let sound = sound_data.with_settings(
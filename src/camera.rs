@@ -0,0 +1,241 @@
+// Orbit camera: looks at a target point from a distance, turned about it by yaw (around world
+// Y) and pitch. Input moves the view it's headed for and the drawn view eases after it, so
+// drags and key presses glide rather than jump
+use crate::model::BoundingSphere;
+use glam::{Mat4, Vec3};
+
+// Field of view limits for zooming, in degrees
+pub const MIN_FOV: f32 = 5.0;
+pub const MAX_FOV: f32 = 120.0;
+// Closest the camera gets to its target
+const MIN_DISTANCE: f32 = 1.0;
+// Just short of straight up or down, where the up vector would flip
+const MAX_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+// Room left around a framed model, as a multiple of its radius
+const FRAME_MARGIN: f32 = 1.1;
+
+// Perspective parameters, settable from the command line
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Projection {
+    // Vertical field of view in degrees
+    pub fov: f32,
+    pub near: f32,
+    pub far: f32,
+}
+
+impl Default for Projection {
+    fn default() -> Self {
+        Self { fov: 45.0, near: 0.1, far: 2000.0 }
+    }
+}
+
+impl Projection {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.fov >= MIN_FOV && self.fov <= MAX_FOV) {
+            return Err(format!("field of view must be between {} and {} degrees, got {}", MIN_FOV, MAX_FOV, self.fov));
+        }
+        if !(self.near > 0.0 && self.far > self.near) {
+            return Err(format!("clip planes need 0 < near < far, got near {} and far {}", self.near, self.far));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct View {
+    target: Vec3,
+    // Radians; yaw 0 and pitch 0 look down -Z
+    yaw: f32,
+    pitch: f32,
+    distance: f32,
+    // Degrees
+    fov: f32,
+}
+
+impl View {
+    fn lerp(&self, to: &View, t: f32) -> View {
+        let mix = |from: f32, to: f32| from + (to - from) * t;
+        View {
+            target: self.target.lerp(to.target, t),
+            yaw: mix(self.yaw, to.yaw),
+            pitch: mix(self.pitch, to.pitch),
+            distance: mix(self.distance, to.distance),
+            fov: mix(self.fov, to.fov),
+        }
+    }
+
+    // From the target out to the eye
+    fn offset(&self) -> Vec3 {
+        let (sin_yaw, cos_yaw) = self.yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = self.pitch.sin_cos();
+        Vec3::new(cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw) * self.distance
+    }
+}
+
+pub struct Camera {
+    pub near: f32,
+    pub far: f32,
+    // How quickly the drawn view closes on where it's headed, per second; 0 jumps straight there
+    pub smoothing: f32,
+    // Where reset returns to: 800 units back along +Z, looking at the origin
+    home: View,
+    goal: View,
+    current: View,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new(Projection::default())
+    }
+}
+
+impl Camera {
+    pub fn new(projection: Projection) -> Self {
+        let home = View { target: Vec3::ZERO, yaw: 0.0, pitch: 0.0, distance: 800.0, fov: projection.fov };
+        Self { near: projection.near, far: projection.far, smoothing: 12.0, home, goal: home, current: home }
+    }
+
+    // Circle the target by `yaw` and `pitch` radians
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        self.goal.yaw += yaw;
+        self.goal.pitch = (self.goal.pitch + pitch).clamp(-MAX_PITCH, MAX_PITCH);
+    }
+
+    // Slide the camera and its target across the view, in fractions of the distance between them
+    pub fn pan(&mut self, right: f32, up: f32) {
+        let (right_axis, up_axis) = self.axes(&self.goal);
+        self.goal.target += (right_axis * right + up_axis * up) * self.goal.distance;
+    }
+
+    // Move toward (factor < 1) or away from the target, keeping the field of view
+    pub fn dolly(&mut self, factor: f32) {
+        self.goal.distance = (self.goal.distance * factor).clamp(MIN_DISTANCE, self.far * 0.5);
+    }
+
+    // Narrow (factor < 1) or widen the field of view, staying put
+    pub fn zoom(&mut self, factor: f32) {
+        self.goal.fov = (self.goal.fov * factor).clamp(MIN_FOV, MAX_FOV);
+    }
+
+    // Look at the middle of `sphere` from just far enough back to fit it in view
+    pub fn frame(&mut self, sphere: BoundingSphere) {
+        let half_fov = (self.goal.fov.to_radians() * 0.5).sin();
+        self.goal.target = sphere.center;
+        self.goal.distance = (sphere.radius * FRAME_MARGIN / half_fov).clamp(MIN_DISTANCE, self.far * 0.5);
+    }
+
+    // Head back to the starting view
+    pub fn reset(&mut self) {
+        self.goal = self.home;
+    }
+
+    // Ease the drawn view toward the goal over `dt` real seconds
+    pub fn update(&mut self, dt: f32) {
+        let t = if self.smoothing > 0.0 { 1.0 - (-self.smoothing * dt).exp() } else { 1.0 };
+        self.current = self.current.lerp(&self.goal, t);
+    }
+
+    pub fn eye(&self) -> Vec3 {
+        self.current.target + self.current.offset()
+    }
+
+    pub fn view(&self) -> Mat4 {
+        Mat4::look_at_rh(self.eye(), self.current.target, Vec3::Y)
+    }
+
+    pub fn projection(&self, aspect: f32) -> Mat4 {
+        Mat4::perspective_rh(self.current.fov.to_radians(), aspect, self.near, self.far)
+    }
+
+    // The view's right and up directions in the world
+    fn axes(&self, view: &View) -> (Vec3, Vec3) {
+        let forward = -view.offset().normalize();
+        let right = forward.cross(Vec3::Y).normalize();
+        (right, right.cross(forward))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settle(camera: &mut Camera) {
+        for _ in 0..200 {
+            camera.update(1.0 / 60.0);
+        }
+    }
+
+    #[test]
+    fn starts_where_the_fixed_camera_was() {
+        let camera = Camera::default();
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 800.0), Vec3::ZERO, Vec3::Y);
+        assert!(camera.view().abs_diff_eq(view, 1e-4));
+        let projection = Mat4::perspective_rh(45.0_f32.to_radians(), 1.5, 0.1, 2000.0);
+        assert!(camera.projection(1.5).abs_diff_eq(projection, 1e-6));
+    }
+
+    #[test]
+    fn view_eases_after_input_unless_smoothing_is_off() {
+        let mut camera = Camera::default();
+        camera.orbit(std::f32::consts::FRAC_PI_2, 0.0);
+        camera.update(1.0 / 60.0);
+        // Part of the way round toward +X
+        let eye = camera.eye();
+        assert!(eye.x > 0.0 && eye.z > 0.0, "eye at {}", eye);
+        settle(&mut camera);
+        assert!(camera.eye().abs_diff_eq(Vec3::new(800.0, 0.0, 0.0), 1e-2), "eye at {}", camera.eye());
+
+        camera.smoothing = 0.0;
+        camera.dolly(0.5);
+        camera.update(1.0 / 60.0);
+        assert!(camera.eye().abs_diff_eq(Vec3::new(400.0, 0.0, 0.0), 1e-2));
+    }
+
+    #[test]
+    fn pitch_distance_and_fov_stay_in_range() {
+        let mut camera = Camera { smoothing: 0.0, ..Default::default() };
+        camera.orbit(0.0, 10.0);
+        camera.dolly(0.0);
+        camera.zoom(100.0);
+        camera.update(0.0);
+        assert_eq!(camera.current.pitch, MAX_PITCH);
+        assert_eq!(camera.current.distance, MIN_DISTANCE);
+        assert_eq!(camera.current.fov, MAX_FOV);
+        camera.dolly(1e6);
+        camera.update(0.0);
+        assert_eq!(camera.current.distance, camera.far * 0.5);
+    }
+
+    #[test]
+    fn pans_across_the_view_and_resets_home() {
+        let mut camera = Camera { smoothing: 0.0, ..Default::default() };
+        camera.pan(0.1, -0.1);
+        camera.update(0.0);
+        // Looking down -Z, right is +X and up is +Y
+        assert!(camera.eye().abs_diff_eq(Vec3::new(80.0, -80.0, 800.0), 1e-3), "eye at {}", camera.eye());
+        camera.reset();
+        camera.update(0.0);
+        assert!(camera.view().abs_diff_eq(Camera::default().view(), 1e-4));
+    }
+
+    #[test]
+    fn frames_a_sphere_so_it_fits_in_view() {
+        let mut camera = Camera { smoothing: 0.0, ..Default::default() };
+        camera.orbit(1.0, 0.5);
+        camera.frame(BoundingSphere { center: Vec3::new(10.0, 20.0, 30.0), radius: 100.0 });
+        camera.update(0.0);
+        assert_eq!(camera.current.target, Vec3::new(10.0, 20.0, 30.0));
+        // The sphere's edge is inside the half field of view, from whichever way it's seen
+        let distance = camera.eye().distance(camera.current.target);
+        assert!((100.0 / distance).asin() < 22.5_f32.to_radians());
+        assert!(distance < 400.0);
+    }
+
+    #[test]
+    fn rejects_bad_projections() {
+        assert!(Projection::default().validate().is_ok());
+        assert!(Projection { fov: 0.0, ..Default::default() }.validate().is_err());
+        assert!(Projection { near: 0.0, ..Default::default() }.validate().is_err());
+        assert!(Projection { near: 10.0, far: 5.0, ..Default::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,143 @@
+// Command-line options:
+//   little-rusty [MODEL] [--up-axis y|z] [--units N] [--normals flat|smooth] [--crease-angle DEG] [--particles FILE] [--seed N] [--simulation auto|cpu|gpu]
+//                [--fov DEG] [--near N] [--far N] [--headless] [--frames N] [--size WxH] [--fps N] [--out DIR]
+use crate::camera::Projection;
+use crate::emitter::{ParticleConfig, SimulationBackend, DEFAULT_PARTICLES};
+use crate::geometry::NormalMode;
+use crate::headless::HeadlessOptions;
+use crate::model::{ModelFit, UpAxis};
+use crate::model_loader::LoadOptions;
+use crate::renderer::DEFAULT_MODEL_FIT;
+use std::path::PathBuf;
+
+pub const DEFAULT_MODEL: &str = "assets/9-5_mailbox/9-5_mailbox.gltf";
+
+pub struct CliArgs {
+    pub model_path: String,
+    // How the model is turned upright and sized in the scene
+    pub model_fit: ModelFit,
+    // How normals are rebuilt for primitives that don't have any
+    pub load_options: LoadOptions,
+    // Emitter definitions (TOML)
+    pub particles_path: String,
+    // Particle RNG seed; falls back to the particle file's, then to a random one (printed)
+    pub seed: Option<u64>,
+    // Where to step the particles, overriding the particle file
+    pub simulation: Option<SimulationBackend>,
+    // The camera's field of view and clip planes
+    pub projection: Projection,
+    // Render offscreen to PNGs instead of opening a window
+    pub headless: bool,
+    pub headless_options: HeadlessOptions,
+}
+
+impl CliArgs {
+    pub fn parse() -> Result<Self, String> {
+        Self::parse_from(std::env::args().skip(1))
+    }
+
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = CliArgs {
+            model_path: DEFAULT_MODEL.to_string(),
+            model_fit: DEFAULT_MODEL_FIT,
+            load_options: LoadOptions::default(),
+            particles_path: DEFAULT_PARTICLES.to_string(),
+            seed: None,
+            simulation: None,
+            projection: Projection::default(),
+            headless: false,
+            headless_options: HeadlessOptions::default(),
+        };
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--up-axis" => {
+                    parsed.model_fit.up_axis = match value("--up-axis")?.as_str() {
+                        "y" => UpAxis::Y,
+                        "z" => UpAxis::Z,
+                        other => return Err(format!("--up-axis expects y or z, got '{}'", other)),
+                    }
+                }
+                // Keep the model's own size, in scene units per asset unit, rather than fitting it to the view
+                "--units" => {
+                    parsed.model_fit.unit_scale = parse_number("--units", &value("--units")?)?;
+                    if !(parsed.model_fit.unit_scale.is_finite() && parsed.model_fit.unit_scale > 0.0) {
+                        return Err("--units must be positive".to_string());
+                    }
+                    parsed.model_fit.fit_radius = None;
+                }
+                "--normals" => {
+                    parsed.load_options.normals = match value("--normals")?.as_str() {
+                        "flat" => NormalMode::Flat,
+                        "smooth" => NormalMode::default(),
+                        other => return Err(format!("--normals expects flat or smooth, got '{}'", other)),
+                    }
+                }
+                // Smooth normals, keeping edges sharper than this many degrees faceted
+                "--crease-angle" => {
+                    let degrees: f32 = parse_number("--crease-angle", &value("--crease-angle")?)?;
+                    if !(0.0..=180.0).contains(&degrees) {
+                        return Err(format!("--crease-angle must be between 0 and 180 degrees, got {}", degrees));
+                    }
+                    parsed.load_options.normals = NormalMode::Smooth { crease_angle: degrees.to_radians() };
+                }
+                "--particles" => parsed.particles_path = value("--particles")?,
+                "--seed" => parsed.seed = Some(parse_number("--seed", &value("--seed")?)?),
+                "--simulation" => {
+                    parsed.simulation = Some(match value("--simulation")?.as_str() {
+                        "auto" => SimulationBackend::Auto,
+                        "cpu" => SimulationBackend::Cpu,
+                        "gpu" => SimulationBackend::Gpu,
+                        other => return Err(format!("--simulation expects auto, cpu or gpu, got '{}'", other)),
+                    })
+                }
+                "--fov" => parsed.projection.fov = parse_number("--fov", &value("--fov")?)?,
+                "--near" => parsed.projection.near = parse_number("--near", &value("--near")?)?,
+                "--far" => parsed.projection.far = parse_number("--far", &value("--far")?)?,
+                "--headless" => parsed.headless = true,
+                "--frames" => parsed.headless_options.frames = parse_number("--frames", &value("--frames")?)?,
+                "--fps" => {
+                    parsed.headless_options.fps = parse_number("--fps", &value("--fps")?)?;
+                    if parsed.headless_options.fps <= 0.0 {
+                        return Err("--fps must be positive".to_string());
+                    }
+                }
+                "--out" => parsed.headless_options.output_dir = PathBuf::from(value("--out")?),
+                "--size" => {
+                    let size = value("--size")?;
+                    let (width, height) = size.split_once('x').ok_or_else(|| format!("--size expects WxH, got '{}'", size))?;
+                    parsed.headless_options.width = parse_number("--size", width)?;
+                    parsed.headless_options.height = parse_number("--size", height)?;
+                    if parsed.headless_options.width == 0 || parsed.headless_options.height == 0 {
+                        return Err(format!("--size must not be zero, got '{}'", size));
+                    }
+                }
+                flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+                path => parsed.model_path = path.to_string(),
+            }
+        }
+        parsed.projection.validate()?;
+        Ok(parsed)
+    }
+
+    // Applies --simulation to `particles` and picks the seed to run with. A seed only replays
+    // on the backend it ran on, so a fixed seed runs on the CPU unless a backend is asked for
+    pub fn particle_seed(&self, particles: &mut ParticleConfig) -> u64 {
+        if let Some(simulation) = self.simulation {
+            particles.simulation = simulation;
+        }
+        match self.seed.or(particles.seed) {
+            Some(seed) => {
+                if particles.simulation == SimulationBackend::Auto {
+                    particles.simulation = SimulationBackend::Cpu;
+                }
+                seed
+            }
+            None => rand::random(),
+        }
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("{} expects a number, got '{}'", name, value))
+}
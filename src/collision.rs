@@ -0,0 +1,333 @@
+// Particle collisions: finding the first collider a particle crosses during a step, and how it
+// bounces off (or dies on) it. The model is tested through a BVH over its triangles, kept in
+// the model's own space so it can follow the model as it turns
+use crate::emitter::{ColliderConfig, ColliderShape};
+use glam::{Mat4, Vec3};
+
+// How far off the surface a particle is put back, so its next step starts clear of it
+const SKIN: f32 = 0.01;
+// Triangles per BVH leaf
+const LEAF_SIZE: usize = 4;
+
+// Where a particle's step first crosses a collider
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Hit {
+    // Just off the surface, on the side the particle came from
+    pub position: Vec3,
+    // Facing the side the particle came from
+    pub normal: Vec3,
+    pub collider: usize,
+}
+
+// The scene's colliders with the model's BVH, if any collider needs it
+pub struct Colliders {
+    pub configs: Vec<ColliderConfig>,
+    pub bvh: Option<Bvh>,
+    // Places the BVH's triangles in the scene, and back
+    model: Mat4,
+    model_inverse: Mat4,
+}
+
+impl Colliders {
+    // `triangles` are the model's, in the pose it's loaded in
+    pub fn new(configs: &[ColliderConfig], triangles: &[[Vec3; 3]]) -> Self {
+        let bvh = configs.iter().any(|collider| collider.shape == ColliderShape::Mesh).then(|| Bvh::new(triangles));
+        Self { configs: configs.to_vec(), bvh, model: Mat4::IDENTITY, model_inverse: Mat4::IDENTITY }
+    }
+
+    // How the model has moved from the pose its mesh was captured in
+    pub fn set_model_transform(&mut self, transform: Mat4) {
+        self.model = transform;
+        self.model_inverse = transform.inverse();
+    }
+
+    // The first collider the step from `from` to `to` runs into
+    pub fn hit(&self, from: Vec3, to: Vec3) -> Option<Hit> {
+        let mut nearest: Option<(f32, Vec3, usize)> = None;
+        for (i, collider) in self.configs.iter().enumerate() {
+            let hit = match collider.shape {
+                ColliderShape::Plane { point, normal } => hit_plane(from, to, Vec3::from(point), Vec3::from(normal).normalize()),
+                ColliderShape::Sphere { center, radius } => hit_sphere(from, to, Vec3::from(center), radius),
+                ColliderShape::Box { center, half_extents } => {
+                    let (center, half_extents) = (Vec3::from(center), Vec3::from(half_extents));
+                    hit_box(from, to, center - half_extents, center + half_extents)
+                }
+                ColliderShape::Mesh => self.bvh.as_ref().and_then(|bvh| {
+                    let (t, normal) = bvh.hit(self.model_inverse.transform_point3(from), self.model_inverse.transform_point3(to))?;
+                    Some((t, self.model.transform_vector3(normal).normalize()))
+                }),
+            };
+            if let Some((t, normal)) = hit {
+                if nearest.is_none_or(|(nearest_t, _, _)| t < nearest_t) {
+                    nearest = Some((t, normal, i));
+                }
+            }
+        }
+        let (t, normal, collider) = nearest?;
+        Some(Hit { position: from.lerp(to, t) + normal * SKIN, normal, collider })
+    }
+}
+
+// Velocity after hitting a surface facing `normal`, or None when the collider kills
+pub fn respond(collider: &ColliderConfig, velocity: Vec3, normal: Vec3) -> Option<Vec3> {
+    if collider.kill {
+        return None;
+    }
+    let into = normal * velocity.dot(normal);
+    let along = velocity - into;
+    Some(along * (1.0 - collider.friction) - into * collider.bounce)
+}
+
+// Hits are the fraction of the way from `from` to `to` and the surface normal there
+
+fn hit_plane(from: Vec3, to: Vec3, point: Vec3, normal: Vec3) -> Option<(f32, Vec3)> {
+    let (before, after) = ((from - point).dot(normal), (to - point).dot(normal));
+    (before >= 0.0 && after < 0.0).then(|| (before / (before - after), normal))
+}
+
+fn hit_sphere(from: Vec3, to: Vec3, center: Vec3, radius: f32) -> Option<(f32, Vec3)> {
+    let (offset, step) = (from - center, to - from);
+    let c = offset.length_squared() - radius * radius;
+    let b = offset.dot(step);
+    // Starting inside, or not heading in
+    if c < 0.0 || b >= 0.0 {
+        return None;
+    }
+    let a = step.length_squared();
+    let discriminant = b * b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / a;
+    (t <= 1.0).then(|| (t, (from + step * t - center) / radius))
+}
+
+fn hit_box(from: Vec3, to: Vec3, min: Vec3, max: Vec3) -> Option<(f32, Vec3)> {
+    // Starting inside
+    if from.cmpge(min).all() && from.cmple(max).all() {
+        return None;
+    }
+    let step = to - from;
+    let (enter, _, axis) = slabs(from, step, min, max, 1.0)?;
+    let mut normal = Vec3::ZERO;
+    normal[axis] = -step[axis].signum();
+    Some((enter, normal))
+}
+
+// Where the ray from `from` along `step` enters and leaves the box (as fractions of `step`),
+// and the axis it enters through; None if it misses, or not before `max_t`
+fn slabs(from: Vec3, step: Vec3, min: Vec3, max: Vec3, max_t: f32) -> Option<(f32, f32, usize)> {
+    let (mut enter, mut exit, mut axis) = (0.0f32, max_t, 0);
+    for i in 0..3 {
+        if step[i] == 0.0 {
+            if from[i] < min[i] || from[i] > max[i] {
+                return None;
+            }
+            continue;
+        }
+        let (a, b) = ((min[i] - from[i]) / step[i], (max[i] - from[i]) / step[i]);
+        let (near, far) = (a.min(b), a.max(b));
+        if near > enter {
+            enter = near;
+            axis = i;
+        }
+        exit = exit.min(far);
+        if enter > exit {
+            return None;
+        }
+    }
+    Some((enter, exit, axis))
+}
+
+// Möller-Trumbore, from either side; the normal faces back along the step
+fn hit_triangle(from: Vec3, step: Vec3, [a, b, c]: [Vec3; 3]) -> Option<(f32, Vec3)> {
+    let (ab, ac) = (b - a, c - a);
+    let p = step.cross(ac);
+    let determinant = ab.dot(p);
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+    let inverse = 1.0 / determinant;
+    let offset = from - a;
+    let u = offset.dot(p) * inverse;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = offset.cross(ab);
+    let v = step.dot(q) * inverse;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let t = ac.dot(q) * inverse;
+    // Not the surface the particle is resting on
+    if t <= 1e-4 || t > 1.0 {
+        return None;
+    }
+    let normal = ab.cross(ac).normalize();
+    Some((t, if normal.dot(step) > 0.0 { -normal } else { normal }))
+}
+
+// Bounding volume hierarchy over triangles, flattened depth first: a branch's first child
+// follows it, and it points at its second
+#[derive(Clone, Debug)]
+pub struct Bvh {
+    pub nodes: Vec<BvhNode>,
+    // Reordered so each leaf's triangles are contiguous
+    pub triangles: Vec<[Vec3; 3]>,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct BvhNode {
+    pub min: Vec3,
+    pub max: Vec3,
+    // Leaves: their first triangle; branches: their second child
+    pub index: u32,
+    // Triangles in a leaf, 0 for a branch
+    pub count: u32,
+}
+
+impl Bvh {
+    pub fn new(triangles: &[[Vec3; 3]]) -> Self {
+        let mut bvh = Self { nodes: Vec::new(), triangles: triangles.to_vec() };
+        if !bvh.triangles.is_empty() {
+            bvh.build(0, bvh.triangles.len());
+        }
+        bvh
+    }
+
+    // Adds the node over triangles[start..end] and everything under it
+    fn build(&mut self, start: usize, end: usize) {
+        let triangles = &mut self.triangles[start..end];
+        let (min, max) = triangles.iter().flatten().fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), &corner| (min.min(corner), max.max(corner)));
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode { min, max, index: start as u32, count: triangles.len() as u32 });
+        if triangles.len() <= LEAF_SIZE {
+            return;
+        }
+
+        // Split at the median along the axis the triangle centers spread furthest on
+        let centroid = |triangle: &[Vec3; 3]| (triangle[0] + triangle[1] + triangle[2]) / 3.0;
+        let (low, high) = triangles.iter().map(centroid).fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), c| (min.min(c), max.max(c)));
+        let extent = high - low;
+        let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+        let middle = triangles.len() / 2;
+        triangles.select_nth_unstable_by(middle, |a, b| centroid(a)[axis].total_cmp(&centroid(b)[axis]));
+
+        self.build(start, start + middle);
+        self.nodes[node].index = self.nodes.len() as u32;
+        self.nodes[node].count = 0;
+        self.build(start + middle, end);
+    }
+
+    // The nearest triangle the step from `from` to `to` crosses
+    pub fn hit(&self, from: Vec3, to: Vec3) -> Option<(f32, Vec3)> {
+        let step = to - from;
+        let mut nearest: Option<(f32, Vec3)> = None;
+        // Nodes still to visit; median splits keep the tree far shallower than this
+        let mut stack = [0usize; 64];
+        let mut pending = usize::from(!self.nodes.is_empty());
+        while pending > 0 {
+            pending -= 1;
+            let i = stack[pending];
+            let node = self.nodes[i];
+            let max_t = nearest.map_or(1.0, |(t, _)| t);
+            if slabs(from, step, node.min, node.max, max_t).is_none() {
+                continue;
+            }
+            if node.count == 0 {
+                stack[pending] = node.index as usize;
+                stack[pending + 1] = i + 1;
+                pending += 2;
+                continue;
+            }
+            let first = node.index as usize;
+            for &triangle in &self.triangles[first..first + node.count as usize] {
+                if let Some(hit) = hit_triangle(from, step, triangle) {
+                    if nearest.is_none_or(|(t, _)| hit.0 < t) {
+                        nearest = Some(hit);
+                    }
+                }
+            }
+        }
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collider(shape: ColliderShape) -> ColliderConfig {
+        ColliderConfig { shape, bounce: 0.5, friction: 0.25, kill: false }
+    }
+
+    // A cube of 12 triangles centered on the origin
+    fn cube(half: f32) -> Vec<[Vec3; 3]> {
+        let corner = |i: usize| Vec3::new(
+            if i & 1 == 0 { -half } else { half },
+            if i & 2 == 0 { -half } else { half },
+            if i & 4 == 0 { -half } else { half },
+        );
+        let faces = [[0, 1, 3, 2], [4, 6, 7, 5], [0, 4, 5, 1], [2, 3, 7, 6], [0, 2, 6, 4], [1, 5, 7, 3]];
+        faces.iter().flat_map(|&[a, b, c, d]| [[corner(a), corner(b), corner(c)], [corner(a), corner(c), corner(d)]]).collect()
+    }
+
+    #[test]
+    fn bounces_off_the_ground_losing_speed() {
+        let ground = collider(ColliderShape::Plane { point: [0.0; 3], normal: [0.0, 1.0, 0.0] });
+        let colliders = Colliders::new(std::slice::from_ref(&ground), &[]);
+        let hit = colliders.hit(Vec3::new(0.0, 1.0, 0.0), Vec3::new(2.0, -1.0, 0.0)).unwrap();
+        assert!((hit.position - Vec3::new(1.0, SKIN, 0.0)).length() < 1e-5);
+        assert_eq!(hit.normal, Vec3::Y);
+
+        let velocity = respond(&ground, Vec3::new(4.0, -2.0, 0.0), hit.normal).unwrap();
+        assert_eq!(velocity, Vec3::new(3.0, 1.0, 0.0));
+        assert_eq!(respond(&ColliderConfig { kill: true, ..ground }, velocity, hit.normal), None);
+    }
+
+    #[test]
+    fn solid_shapes_are_hit_from_outside_only() {
+        let sphere = ColliderShape::Sphere { center: [0.0; 3], radius: 1.0 };
+        let cube = ColliderShape::Box { center: [5.0, 0.0, 0.0], half_extents: [1.0; 3] };
+        let colliders = Colliders::new(&[collider(sphere), collider(cube)], &[]);
+
+        let hit = colliders.hit(Vec3::new(-3.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0)).unwrap();
+        assert_eq!((hit.collider, hit.normal), (0, Vec3::NEG_X));
+        assert!((hit.position.x + 1.0 + SKIN).abs() < 1e-5);
+        let hit = colliders.hit(Vec3::new(3.0, 0.0, 0.0), Vec3::new(10.0, 0.0, 0.0)).unwrap();
+        assert_eq!((hit.collider, hit.normal), (1, Vec3::NEG_X));
+        assert!((hit.position.x - 4.0 + SKIN).abs() < 1e-5);
+
+        // Leaving from inside either one
+        assert_eq!(colliders.hit(Vec3::ZERO, Vec3::new(3.0, 0.0, 0.0)), None);
+        assert_eq!(colliders.hit(Vec3::new(5.0, 0.0, 0.0), Vec3::new(5.0, 3.0, 0.0)), None);
+    }
+
+    #[test]
+    fn bvh_finds_the_nearest_triangle() {
+        // Two cubes in a row, so the BVH has to pick between leaves
+        let mut triangles = cube(1.0);
+        triangles.extend(cube(1.0).iter().map(|triangle| triangle.map(|corner| corner + Vec3::new(5.0, 0.0, 0.0))));
+        let bvh = Bvh::new(&triangles);
+        assert!(bvh.nodes.len() > 1);
+
+        let (t, normal) = bvh.hit(Vec3::new(10.0, 0.2, 0.3), Vec3::new(-10.0, 0.2, 0.3)).unwrap();
+        assert!((t - 0.2).abs() < 1e-5);
+        assert_eq!(normal, Vec3::X);
+        // From inside the first cube out through its far side
+        let (t, normal) = bvh.hit(Vec3::ZERO, Vec3::new(0.0, 0.0, -2.0)).unwrap();
+        assert!((t - 0.5).abs() < 1e-5);
+        assert_eq!(normal, Vec3::Z);
+        assert_eq!(bvh.hit(Vec3::new(2.5, 3.0, 0.0), Vec3::new(2.5, -3.0, 0.0)), None);
+    }
+
+    #[test]
+    fn mesh_colliders_follow_the_model() {
+        let mut colliders = Colliders::new(&[collider(ColliderShape::Mesh)], &cube(1.0));
+        colliders.set_model_transform(Mat4::from_translation(Vec3::new(0.0, 10.0, 0.0)));
+        assert_eq!(colliders.hit(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.0, -3.0, 0.0)), None);
+        let hit = colliders.hit(Vec3::new(0.0, 13.0, 0.0), Vec3::new(0.0, 7.0, 0.0)).unwrap();
+        assert!((hit.position.y - (11.0 + SKIN)).abs() < 1e-4);
+        assert_eq!(hit.normal, Vec3::Y);
+    }
+}
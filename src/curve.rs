@@ -0,0 +1,121 @@
+// Keyframed values over a particle's life, for fading, growing and recoloring particles
+// from data. Keys are `[age, value]` pairs with age running from 0 (birth) to 1 (death)
+use glam::Vec3;
+use serde::Deserialize;
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    // Hold each key's value until the next key
+    Step,
+    #[default]
+    Linear,
+    // Catmull-Rom spline through the keys
+    Smooth,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Curve<T> {
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub keys: Vec<(f32, T)>,
+}
+
+// Colors are keyed as [r, g, b]
+pub type Gradient = Curve<Vec3>;
+
+impl<T> Curve<T>
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    pub fn new(interpolation: Interpolation, keys: Vec<(f32, T)>) -> Self {
+        Self { interpolation, keys }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Self::new(Interpolation::Step, vec![(0.0, value)])
+    }
+
+    // Value at `age`; ages outside the keys hold the first or last value
+    pub fn sample(&self, age: f32) -> T {
+        let keys = &self.keys;
+        let next = keys.partition_point(|&(time, _)| time <= age);
+        if next == 0 {
+            return keys[0].1;
+        }
+        if next == keys.len() {
+            return keys[next - 1].1;
+        }
+        let (t0, v0) = keys[next - 1];
+        let (t1, v1) = keys[next];
+        let s = (age - t0) / (t1 - t0);
+        match self.interpolation {
+            Interpolation::Step => v0,
+            Interpolation::Linear => v0 + (v1 - v0) * s,
+            Interpolation::Smooth => {
+                // Tangents from the neighbouring keys (one-sided at the ends), per unit of age
+                let tangent = |i: usize| {
+                    let (before, after) = (keys[i.saturating_sub(1)], keys[(i + 1).min(keys.len() - 1)]);
+                    (after.1 - before.1) * (1.0 / (after.0 - before.0))
+                };
+                let (m0, m1) = (tangent(next - 1) * (t1 - t0), tangent(next) * (t1 - t0));
+                let (s2, s3) = (s * s, s * s * s);
+                v0 * (2.0 * s3 - 3.0 * s2 + 1.0) + m0 * (s3 - 2.0 * s2 + s) + v1 * (3.0 * s2 - 2.0 * s3) + m1 * (s3 - s2)
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.keys.is_empty() {
+            return Err("needs at least one key".to_string());
+        }
+        if self.keys.iter().any(|(time, _)| !time.is_finite()) {
+            return Err("key ages must be finite".to_string());
+        }
+        if self.keys.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err("key ages must increase".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holds_the_ends_and_interpolates_between_keys() {
+        let curve = Curve::new(Interpolation::Linear, vec![(0.2, 1.0), (0.6, 3.0)]);
+        assert_eq!(curve.sample(0.0), 1.0);
+        assert_eq!(curve.sample(0.4), 2.0);
+        assert_eq!(curve.sample(1.0), 3.0);
+
+        let step = Curve::new(Interpolation::Step, curve.keys.clone());
+        assert_eq!(step.sample(0.59), 1.0);
+        assert_eq!(step.sample(0.6), 3.0);
+    }
+
+    #[test]
+    fn smooth_curves_pass_through_their_keys() {
+        let curve = Curve::new(Interpolation::Smooth, vec![(0.0, 0.0), (0.5, 0.8), (1.0, 0.0)]);
+        for (age, value) in [(0.0, 0.0), (0.5, 0.8), (1.0, 0.0)] {
+            assert!((curve.sample(age) - value).abs() < 1e-6);
+        }
+        // Flat on top, where the neighbours are level
+        assert!(curve.sample(0.45) < 0.8 && curve.sample(0.55) < 0.8);
+    }
+
+    #[test]
+    fn gradients_blend_colors() {
+        let gradient = Gradient::new(Interpolation::Linear, vec![(0.0, Vec3::ZERO), (1.0, Vec3::new(1.0, 0.5, 0.0))]);
+        assert_eq!(gradient.sample(0.5), Vec3::new(0.5, 0.25, 0.0));
+    }
+
+    #[test]
+    fn rejects_unordered_keys() {
+        assert!(Curve::new(Interpolation::Linear, vec![(0.5, 1.0), (0.5, 2.0)]).validate().is_err());
+        assert!(Curve::<f32>::new(Interpolation::Linear, Vec::new()).validate().is_err());
+    }
+}
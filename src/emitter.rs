@@ -0,0 +1,994 @@
+// Particle emitters described in data: a TOML file lists the scene's emitters with their
+// shape, spawn rate, bursts, starting motion and the forces acting on their particles, and
+// the force fields and colliders they all share. Anything left out of the file falls back to
+// the built-in fire emitter
+use crate::curve::{Curve, Gradient, Interpolation};
+use crate::model::ModelData;
+use crate::timestep::SIMULATION_STEP;
+use glam::{Mat4, Vec3};
+use rand::Rng;
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+// Shipped description of the default fire, the same emitter `ParticleConfig::default` builds
+pub const DEFAULT_PARTICLES: &str = "assets/particles/fire.toml";
+// Longest trail a particle can leave, in steps
+pub const MAX_TRAIL_POINTS: u32 = 32;
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, source: toml::de::Error },
+    Invalid { path: PathBuf, reason: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Read { path, source } => write!(f, "could not read {}: {}", path.display(), source),
+            ConfigError::Parse { path, source } => write!(f, "{} is not a valid particle file: {}", path.display(), source),
+            ConfigError::Invalid { path, reason } => write!(f, "{}: {}", path.display(), reason),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Read { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+// Everything the particle system needs to know about a scene
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParticleConfig {
+    // Particle RNG seed; --seed wins over it, and a random one is used when neither is given
+    pub seed: Option<u64>,
+    // --simulation wins over it
+    pub simulation: SimulationBackend,
+    pub emitters: Vec<EmitterConfig>,
+    // Forces acting on every emitter's particles, on top of the emitters' own
+    pub fields: Vec<FieldConfig>,
+    // Surfaces every emitter's particles collide with
+    pub colliders: Vec<ColliderConfig>,
+}
+
+impl Default for ParticleConfig {
+    fn default() -> Self {
+        Self { seed: None, simulation: SimulationBackend::Auto, emitters: vec![EmitterConfig::default()], fields: Vec::new(), colliders: Vec::new() }
+    }
+}
+
+// Where particles are stepped. The two backends draw their random numbers differently, so
+// a seed replays the same run only on the same backend; a fixed seed resolves Auto to the CPU
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SimulationBackend {
+    // The GPU when it can run compute shaders, else the CPU
+    #[default]
+    Auto,
+    Cpu,
+    Gpu,
+}
+
+impl ParticleConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|source| ConfigError::Read { path: path.to_path_buf(), source })?;
+        let mut config: ParticleConfig = toml::from_str(&text).map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })?;
+        for emitter in &mut config.emitters {
+            emitter.validate().map_err(|reason| ConfigError::Invalid {
+                path: path.to_path_buf(),
+                reason: format!("emitter '{}': {}", emitter.name, reason),
+            })?;
+            // Textures are found next to the particle file
+            if let Some(texture) = &mut emitter.sprite.texture {
+                *texture = path.parent().unwrap_or(Path::new("")).join(&texture);
+            }
+        }
+        for emitter in &config.emitters {
+            if let Some(sub) = emitter.sub_emitters.iter().find(|sub| config.emitter_index(&sub.emitter).is_none()) {
+                return Err(ConfigError::Invalid {
+                    path: path.to_path_buf(),
+                    reason: format!("emitter '{}': sub-emitter '{}' names no emitter", emitter.name, sub.emitter),
+                });
+            }
+        }
+        for (i, field) in config.fields.iter().enumerate() {
+            field.validate().map_err(|reason| ConfigError::Invalid {
+                path: path.to_path_buf(),
+                reason: format!("field {}: {}", i + 1, reason),
+            })?;
+        }
+        for (i, collider) in config.colliders.iter().enumerate() {
+            collider.validate().map_err(|reason| ConfigError::Invalid {
+                path: path.to_path_buf(),
+                reason: format!("collider {}: {}", i + 1, reason),
+            })?;
+        }
+        Ok(config)
+    }
+
+    // The first emitter called `name`, which sub-emitters spawn from
+    pub fn emitter_index(&self, name: &str) -> Option<usize> {
+        self.emitters.iter().position(|emitter| emitter.name == name)
+    }
+
+    // Whether particles need drawing back to front: anything but additive blending depends on order
+    pub fn sorts(&self) -> bool {
+        self.emitters.iter().any(|emitter| emitter.sprite.blend != BlendMode::Additive)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmitterConfig {
+    pub name: String,
+    pub shape: EmitterShape,
+    // Continuous spawn rate in particles per second
+    pub rate: f32,
+    pub bursts: Vec<Burst>,
+    pub max_particles: usize,
+    // Seconds a particle lives, picked in [min, max); the max is also the life it fades over
+    pub lifetime: [f32; 2],
+    pub size: [f32; 2],
+    // Size gained per second
+    pub growth: f32,
+    // Starting rotation in radians and spin in radians per second, picked per particle; the
+    // appearance's rotation curve turns them further
+    pub rotation: [f32; 2],
+    pub angular_velocity: [f32; 2],
+    pub orientation: Orientation,
+    pub velocity: VelocityConfig,
+    pub forces: ForceConfig,
+    pub appearance: AppearanceConfig,
+    pub sprite: SpriteConfig,
+    pub trail: TrailConfig,
+    // Other emitters' particles spawned where this one's are born, die or collide
+    pub sub_emitters: Vec<SubEmitterConfig>,
+}
+
+impl Default for EmitterConfig {
+    fn default() -> Self {
+        // Fire rising from a disk behind the mailbox
+        Self {
+            name: "fire".to_string(),
+            shape: EmitterShape::Disk { center: [0.0, -50.0, -300.0], radius: 80.0, normal: [0.0, 1.0, 0.0] },
+            rate: 900.0,
+            bursts: Vec::new(),
+            max_particles: 5000,
+            lifetime: [1.2, 2.2],
+            size: [6.0, 12.0],
+            growth: 2.5,
+            rotation: [0.0, 0.0],
+            angular_velocity: [0.0, 0.0],
+            orientation: Orientation::ViewFacing,
+            velocity: VelocityConfig::default(),
+            forces: ForceConfig::default(),
+            appearance: AppearanceConfig::default(),
+            sprite: SpriteConfig::default(),
+            trail: TrailConfig::default(),
+            sub_emitters: Vec::new(),
+        }
+    }
+}
+
+impl EmitterConfig {
+    fn validate(&self) -> Result<(), String> {
+        let ranges = [("lifetime", self.lifetime), ("size", self.size), ("velocity.speed", self.velocity.speed),
+                      ("velocity.radial", self.velocity.radial), ("forces.waver.amplitude", self.forces.waver.amplitude),
+                      ("forces.waver.frequency", self.forces.waver.frequency), ("rotation", self.rotation),
+                      ("angular_velocity", self.angular_velocity)];
+        for (name, [min, max]) in ranges {
+            if min.is_nan() || max.is_nan() || min > max {
+                return Err(format!("{} range [{}, {}] is empty", name, min, max));
+            }
+        }
+        if self.lifetime[1] <= 0.0 {
+            return Err("lifetime must be positive".to_string());
+        }
+        if self.rate.is_nan() || self.rate < 0.0 {
+            return Err(format!("rate must not be negative, got {}", self.rate));
+        }
+        let appearance = &self.appearance;
+        let curves = [("color", appearance.color.validate()), ("alpha", appearance.alpha.validate()),
+                      ("size", appearance.size.validate()), ("rotation", appearance.rotation.validate())];
+        for (name, result) in curves {
+            result.map_err(|reason| format!("appearance.{}: {}", name, reason))?;
+        }
+        if self.sprite.frames.contains(&0) {
+            return Err("sprite.frames needs at least one column and one row".to_string());
+        }
+        if !self.sprite.cycles.is_finite() || self.sprite.cycles < 0.0 {
+            return Err(format!("sprite.cycles must not be negative, got {}", self.sprite.cycles));
+        }
+        if !self.sprite.softness.is_finite() || self.sprite.softness < 0.0 {
+            return Err(format!("sprite.softness must not be negative, got {}", self.sprite.softness));
+        }
+        if self.trail.points > MAX_TRAIL_POINTS {
+            return Err(format!("trail.points can be at most {}, got {}", MAX_TRAIL_POINTS, self.trail.points));
+        }
+        if !self.trail.width.is_finite() || self.trail.width < 0.0 {
+            return Err(format!("trail.width must not be negative, got {}", self.trail.width));
+        }
+        if let Some(sub) = self.sub_emitters.iter().find(|sub| !sub.inherit_velocity.is_finite()) {
+            return Err(format!("sub-emitter '{}' inherit_velocity must be finite, got {}", sub.emitter, sub.inherit_velocity));
+        }
+        if let Some(burst) = self.bursts.iter().find(|burst| !burst.time.is_finite()) {
+            return Err(format!("burst time must be finite, got {}", burst.time));
+        }
+        // Shorter repeats would fire more than once a step, and tiny ones never move on
+        if let Some(burst) = self.bursts.iter().find(|burst| burst.interval.is_some_and(|interval| interval.is_nan() || interval < SIMULATION_STEP)) {
+            return Err(format!("burst at {}s must repeat at most once per {}s step", burst.time, SIMULATION_STEP));
+        }
+        match self.orientation {
+            Orientation::VelocityStretch { stretch } if !stretch.is_finite() || stretch < 0.0 => {
+                return Err(format!("orientation stretch must not be negative, got {}", stretch));
+            }
+            Orientation::FixedAxis { axis: direction } | Orientation::WorldAligned { normal: direction } if Vec3::from(direction).length_squared() == 0.0 => {
+                return Err("orientation axis must not be zero".to_string());
+            }
+            _ => {}
+        }
+        match self.shape {
+            EmitterShape::Disk { normal, .. } if Vec3::from(normal).length_squared() == 0.0 => Err("disk normal must not be zero".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Where new particles appear
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum EmitterShape {
+    Point { position: [f32; 3] },
+    // Flat disk facing `normal`
+    Disk {
+        center: [f32; 3],
+        radius: f32,
+        #[serde(default = "up")]
+        normal: [f32; 3],
+    },
+    // Anywhere inside the ball
+    Sphere { center: [f32; 3], radius: f32 },
+    // Anywhere inside the box
+    Box { center: [f32; 3], half_extents: [f32; 3] },
+    // On the loaded model's triangles, evenly by area
+    MeshSurface,
+    // On the loaded model's vertices, each as likely as the next
+    MeshVertex,
+}
+
+fn up() -> [f32; 3] {
+    [0.0, 1.0, 0.0]
+}
+
+// A one-off (or repeating) spawn of `count` particles, `time` seconds after start
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Burst {
+    pub time: f32,
+    pub count: u32,
+    // Repeat every `interval` seconds
+    #[serde(default)]
+    pub interval: Option<f32>,
+}
+
+// Which way each particle's quad faces. Whatever the mode, rotation turns it in its own plane
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Orientation {
+    // Square on to the camera
+    #[default]
+    ViewFacing,
+    // Upright along the particle's motion and facing the camera as far as it can, lengthened by
+    // the distance it covers in `stretch` seconds: sparks, rain
+    VelocityStretch { stretch: f32 },
+    // Upright along `axis`, turning about it toward the camera: flames, grass
+    FixedAxis { axis: [f32; 3] },
+    // Fixed in the world facing along `normal`, however the camera looks at it: ripples, decals
+    WorldAligned { normal: [f32; 3] },
+}
+
+impl Orientation {
+    // What the particle shader switches on
+    pub fn index(&self) -> u32 {
+        match self {
+            Orientation::ViewFacing => 0,
+            Orientation::VelocityStretch { .. } => 1,
+            Orientation::FixedAxis { .. } => 2,
+            Orientation::WorldAligned { .. } => 3,
+        }
+    }
+}
+
+// Starting velocity: `direction * speed` plus `radial` away from the shape (along the
+// surface normal for meshes)
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VelocityConfig {
+    pub direction: [f32; 3],
+    pub speed: [f32; 2],
+    pub radial: [f32; 2],
+}
+
+impl Default for VelocityConfig {
+    fn default() -> Self {
+        Self { direction: up(), speed: [180.0, 260.0], radial: [15.0, 40.0] }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ForceConfig {
+    // Constant acceleration: buoyancy for fire, gravity for anything that falls
+    pub acceleration: [f32; 3],
+    // Fraction of velocity lost per second, per axis
+    pub drag: [f32; 3],
+    pub swirl: SwirlConfig,
+    pub waver: WaverConfig,
+}
+
+impl Default for ForceConfig {
+    fn default() -> Self {
+        Self {
+            acceleration: [0.0, 80.0, 0.0],
+            drag: [0.25, 0.0, 0.25],
+            swirl: SwirlConfig::default(),
+            waver: WaverConfig::default(),
+        }
+    }
+}
+
+impl ForceConfig {
+    // None of an emitter's own forces, leaving its particles to their velocity and the fields
+    #[cfg(test)]
+    pub fn none() -> Self {
+        Self {
+            acceleration: [0.0; 3],
+            drag: [0.0; 3],
+            swirl: SwirlConfig { amplitude: 0.0, ..Default::default() },
+            waver: WaverConfig { amplitude: [0.0, 0.0], ..Default::default() },
+        }
+    }
+}
+
+// Horizontal turbulence whose phase rises with time and height
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SwirlConfig {
+    pub amplitude: f32,
+    pub frequency: f32,
+    // Phase change per unit of height
+    pub height_scale: f32,
+}
+
+impl Default for SwirlConfig {
+    fn default() -> Self {
+        Self { amplitude: 30.0, frequency: 3.5, height_scale: 0.01 }
+    }
+}
+
+// Side-to-side push along X, with a per-particle amplitude and frequency
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WaverConfig {
+    pub amplitude: [f32; 2],
+    pub frequency: [f32; 2],
+}
+
+impl Default for WaverConfig {
+    fn default() -> Self {
+        Self { amplitude: [50.0, 120.0], frequency: [3.0, 7.5] }
+    }
+}
+
+// How particles look over their life. Age runs from 0 at birth to 1 as the particle dies, however
+// long its own lifetime turned out
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppearanceConfig {
+    pub color: Gradient,
+    pub alpha: Curve<f32>,
+    // Multiplies the particle's own size
+    pub size: Curve<f32>,
+    // Sprite rotation in radians
+    pub rotation: Curve<f32>,
+}
+
+impl Default for AppearanceConfig {
+    fn default() -> Self {
+        // Flames: pale yellow cooling through orange to red, swelling then shrinking away
+        Self {
+            color: Gradient::new(Interpolation::Step, vec![
+                (0.0, Vec3::new(1.0, 0.95, 0.7)),
+                (0.3, Vec3::new(1.0, 0.6, 0.2)),
+                (0.6, Vec3::new(1.0, 0.2, 0.05)),
+            ]),
+            alpha: Curve::new(Interpolation::Linear, vec![(0.0, 1.0), (0.23, 1.0), (0.73, 0.35)]),
+            size: Curve::new(Interpolation::Smooth, vec![(0.0, 0.0), (0.25, 0.6), (0.5, 0.8), (0.75, 0.6), (1.0, 0.0)]),
+            rotation: Curve::constant(0.0),
+        }
+    }
+}
+
+// What a particle's quad shows and how it's blended over the scene
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpriteConfig {
+    // Image to draw, relative to the particle file; without one particles are soft glowing dots
+    pub texture: Option<PathBuf>,
+    // Columns and rows of a sprite sheet, whose frames play left to right, top to bottom
+    pub frames: [u32; 2],
+    // Times the flipbook plays through over the particle's life
+    pub cycles: f32,
+    // Start each particle on a random frame so neighbours don't animate in lockstep
+    pub random_start_frame: bool,
+    pub blend: BlendMode,
+    // Distance in front of the scene over which particles fade out, instead of clipping hard
+    // where they cross it; 0 turns fading off
+    pub softness: f32,
+}
+
+impl Default for SpriteConfig {
+    fn default() -> Self {
+        Self { texture: None, frames: [1, 1], cycles: 1.0, random_start_frame: false, blend: BlendMode::Additive, softness: 16.0 }
+    }
+}
+
+impl SpriteConfig {
+    pub fn frame_count(&self) -> u32 {
+        self.frames[0] * self.frames[1]
+    }
+
+    // Sheet frame shown at `age` by a particle that started on `start_frame`
+    pub fn frame(&self, age: f32, start_frame: f32) -> f32 {
+        let count = self.frame_count() as f32;
+        (start_frame + (age * self.cycles * count).floor()).rem_euclid(count)
+    }
+
+    // Part of `sheet` (x, y, width, height) that `frame` covers
+    pub fn frame_rect(&self, sheet: [f32; 4], frame: f32) -> [f32; 4] {
+        let [columns, rows] = self.frames.map(|n| n as f32);
+        let (width, height) = (sheet[2] / columns, sheet[3] / rows);
+        [sheet[0] + (frame % columns) * width, sheet[1] + (frame / columns).floor() * height, width, height]
+    }
+}
+
+// A ribbon drawn behind each particle through where it's been, fading out toward the tail
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TrailConfig {
+    // Positions remembered, one per step, including the current one; under 2 draws no trail
+    pub points: u32,
+    // Ribbon width as a fraction of the particle's size
+    pub width: f32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self { points: 0, width: 1.0 }
+    }
+}
+
+impl TrailConfig {
+    // Segments drawn between the points
+    pub fn segments(&self) -> u32 {
+        self.points.saturating_sub(1)
+    }
+}
+
+// Spawns `count` of another emitter's particles whenever one of this emitter's has `event`.
+// They start where it was with `inherit_velocity` times its velocity added to their own, and
+// head out in every direction at the other emitter's radial speed. Particles spawned this way
+// don't set off birth events themselves
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubEmitterConfig {
+    pub event: SubEmitterEvent,
+    // Name of the emitter whose particles are spawned; give it no rate or bursts to only
+    // spawn this way
+    pub emitter: String,
+    #[serde(default = "one_particle")]
+    pub count: u32,
+    #[serde(default = "full_velocity")]
+    pub inherit_velocity: f32,
+}
+
+fn one_particle() -> u32 {
+    1
+}
+
+fn full_velocity() -> f32 {
+    1.0
+}
+
+// Shaders see these by index, so the order matters
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SubEmitterEvent {
+    Birth,
+    // Its life ran out, or it hit a collider that kills
+    Death,
+    // It hit a collider, bouncing or not
+    Collision,
+}
+
+// Shaders see these by index, so the order matters
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendMode {
+    // Adds light to what's behind: fire, sparks, glows
+    #[default]
+    Additive,
+    // Covers what's behind by alpha: smoke, dust
+    Alpha,
+    // Colors already multiplied by alpha, so one texture can both cover and glow
+    Premultiplied,
+}
+
+// A surface particles can't pass through, and what happens to those that hit it
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ColliderConfig {
+    pub shape: ColliderShape,
+    // Fraction of the speed into the surface that bounces back out
+    pub bounce: f32,
+    // Fraction of the speed along the surface lost on each contact
+    pub friction: f32,
+    // Particles die on contact instead of bouncing
+    pub kill: bool,
+}
+
+impl Default for ColliderConfig {
+    fn default() -> Self {
+        // Ground under the mailbox
+        Self {
+            shape: ColliderShape::Plane { point: [0.0, -200.0, 0.0], normal: up() },
+            bounce: 0.5,
+            friction: 0.2,
+            kill: false,
+        }
+    }
+}
+
+impl ColliderConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !self.bounce.is_finite() || self.bounce < 0.0 {
+            return Err(format!("bounce must not be negative, got {}", self.bounce));
+        }
+        if !(0.0..=1.0).contains(&self.friction) {
+            return Err(format!("friction must be between 0 and 1, got {}", self.friction));
+        }
+        match self.shape {
+            ColliderShape::Plane { normal, .. } if Vec3::from(normal).length_squared() == 0.0 => Err("plane normal must not be zero".to_string()),
+            ColliderShape::Sphere { radius, .. } if radius.is_nan() || radius < 0.0 => Err(format!("sphere radius must not be negative, got {}", radius)),
+            ColliderShape::Box { half_extents, .. } if half_extents.iter().any(|half| half.is_nan() || *half < 0.0) => {
+                Err("box half extents must not be negative".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Solid shapes are hit from outside; particles already inside pass out freely
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ColliderShape {
+    // Solid on the side away from `normal`
+    Plane {
+        point: [f32; 3],
+        #[serde(default = "up")]
+        normal: [f32; 3],
+    },
+    Sphere { center: [f32; 3], radius: f32 },
+    // Axis-aligned
+    Box { center: [f32; 3], half_extents: [f32; 3] },
+    // The loaded model's triangles from either side, following the model as it turns
+    Mesh,
+}
+
+// A force and the part of the scene it acts in
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FieldConfig {
+    pub force: FieldForce,
+    pub volume: FieldVolume,
+    // Distance inside the volume's edge over which the force fades in; 0 for a hard edge
+    pub falloff: f32,
+}
+
+impl Default for FieldConfig {
+    fn default() -> Self {
+        // A breeze from the left, everywhere
+        Self {
+            force: FieldForce::Wind { velocity: [60.0, 0.0, 0.0], strength: 0.5 },
+            volume: FieldVolume::Everywhere,
+            falloff: 0.0,
+        }
+    }
+}
+
+impl FieldConfig {
+    fn validate(&self) -> Result<(), String> {
+        if !self.falloff.is_finite() || self.falloff < 0.0 {
+            return Err(format!("falloff must not be negative, got {}", self.falloff));
+        }
+        match self.force {
+            FieldForce::Wind { strength, .. } if strength.is_nan() || strength < 0.0 => {
+                return Err(format!("wind strength must not be negative, got {}", strength));
+            }
+            FieldForce::Vortex { axis, .. } if Vec3::from(axis).length_squared() == 0.0 => return Err("vortex axis must not be zero".to_string()),
+            FieldForce::Turbulence { frequency, .. } if frequency.is_nan() || frequency < 0.0 => {
+                return Err(format!("turbulence frequency must not be negative, got {}", frequency));
+            }
+            FieldForce::Drag { coefficient } if coefficient.is_nan() || coefficient < 0.0 => {
+                return Err(format!("drag coefficient must not be negative, got {}", coefficient));
+            }
+            _ => {}
+        }
+        match self.volume {
+            FieldVolume::Sphere { radius, .. } if radius.is_nan() || radius < 0.0 => Err(format!("sphere radius must not be negative, got {}", radius)),
+            FieldVolume::Box { half_extents, .. } if half_extents.iter().any(|half| half.is_nan() || *half < 0.0) => {
+                Err("box half extents must not be negative".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+// Strengths are accelerations in units per second squared, unless noted
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FieldForce {
+    Gravity { acceleration: [f32; 3] },
+    // Pulls velocity toward the wind's, closing `strength` of the gap per second
+    Wind { velocity: [f32; 3], strength: f32 },
+    // Toward `position`
+    Attractor { position: [f32; 3], strength: f32 },
+    // Away from `position`
+    Repulsor { position: [f32; 3], strength: f32 },
+    // Around the line through `center` along `axis`, counterclockwise looking down it
+    Vortex {
+        center: [f32; 3],
+        #[serde(default = "up")]
+        axis: [f32; 3],
+        strength: f32,
+    },
+    // Swirling curl noise, `frequency` times per unit and drifting `speed` times per second
+    Turbulence { strength: f32, frequency: f32, speed: f32 },
+    // Fraction of velocity lost per second
+    Drag { coefficient: f32 },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub enum FieldVolume {
+    #[default]
+    Everywhere,
+    Sphere { center: [f32; 3], radius: f32 },
+    // Axis-aligned
+    Box { center: [f32; 3], half_extents: [f32; 3] },
+}
+
+// Uniform pick in [min, max), or exactly min for a collapsed range (gen_range panics on those)
+pub fn sample_range(rng: &mut impl Rng, [min, max]: [f32; 2]) -> f32 {
+    if min < max { rng.gen_range(min..max) } else { min }
+}
+
+// Triangles and vertices of the model in world space (model transform times each instance's
+// transform), in the pose it's loaded in, for mesh emitters
+#[derive(Clone, Debug, Default)]
+pub struct EmissionMesh {
+    triangles: Vec<[Vec3; 3]>,
+    // Running total of triangle areas, for picking triangles by area
+    cumulative_area: Vec<f32>,
+    // Each vertex those triangles use, once per mesh instance, with its normal
+    vertices: Vec<[Vec3; 2]>,
+    // How the model has moved from that pose
+    model: Mat4,
+}
+
+impl EmissionMesh {
+    // `transform` places the model the way it's drawn (before any animation)
+    pub fn new(model: &ModelData, transform: Mat4) -> Self {
+        let mut mesh = Self::default();
+        let mut total = 0.0;
+        for instance in model.instances() {
+            let world = transform * instance.transform;
+            let normal_matrix = world.inverse().transpose();
+            let mut used = BTreeSet::<u32>::new();
+            for primitive in &model.meshes[instance.mesh].primitives {
+                let start = primitive.first_index as usize;
+                let indices = &model.indices[start..start + primitive.index_count as usize];
+                for triangle in indices.chunks_exact(3) {
+                    let corners = [0, 1, 2].map(|i| world.transform_point3(Vec3::from(model.vertices[triangle[i] as usize].position)));
+                    let area = (corners[1] - corners[0]).cross(corners[2] - corners[0]).length() * 0.5;
+                    if area > 0.0 {
+                        total += area;
+                        mesh.triangles.push(corners);
+                        mesh.cumulative_area.push(total);
+                        used.extend(triangle);
+                    }
+                }
+            }
+            mesh.vertices.extend(used.into_iter().map(|index| {
+                let vertex = &model.vertices[index as usize];
+                let normal = normal_matrix.transform_vector3(Vec3::from(vertex.normal)).normalize_or_zero();
+                [world.transform_point3(Vec3::from(vertex.position)), normal]
+            }));
+        }
+        mesh
+    }
+
+    // Where the model is now, relative to the pose it was captured in
+    pub fn set_model_transform(&mut self, transform: Mat4) {
+        self.model = transform;
+    }
+
+    pub fn triangles(&self) -> &[[Vec3; 3]] {
+        &self.triangles
+    }
+
+    pub fn cumulative_area(&self) -> &[f32] {
+        &self.cumulative_area
+    }
+
+    pub fn vertices(&self) -> &[[Vec3; 2]] {
+        &self.vertices
+    }
+
+    // A point spread evenly over the surface, and the face normal there, where the model is now
+    fn sample_surface(&self, rng: &mut impl Rng) -> Option<(Vec3, Vec3)> {
+        let total = *self.cumulative_area.last()?;
+        let target = rng.gen_range(0.0..total);
+        let index = self.cumulative_area.partition_point(|&area| area <= target).min(self.triangles.len() - 1);
+        let [a, b, c] = self.triangles[index];
+        // Square root keeps the barycentric pick uniform over the triangle
+        let (u, v) = (rng.gen::<f32>().sqrt(), rng.gen::<f32>());
+        let position = a * (1.0 - u) + b * (u * (1.0 - v)) + c * (u * v);
+        Some(self.place(position, (b - a).cross(c - a)))
+    }
+
+    // Any one vertex and its normal, where the model is now
+    fn sample_vertex(&self, rng: &mut impl Rng) -> Option<(Vec3, Vec3)> {
+        if self.vertices.is_empty() {
+            return None;
+        }
+        let [position, normal] = self.vertices[rng.gen_range(0..self.vertices.len())];
+        Some(self.place(position, normal))
+    }
+
+    fn place(&self, position: Vec3, normal: Vec3) -> (Vec3, Vec3) {
+        (self.model.transform_point3(position), self.model.transform_vector3(normal).normalize_or_zero())
+    }
+}
+
+impl EmitterShape {
+    // A spawn position, and the outward direction used for radial velocity there
+    pub fn sample(&self, rng: &mut impl Rng, mesh: &EmissionMesh) -> (Vec3, Vec3) {
+        match *self {
+            EmitterShape::Point { position } => (Vec3::from(position), Vec3::ZERO),
+            EmitterShape::Disk { center, radius, normal } => {
+                let (center, normal) = (Vec3::from(center), Vec3::from(normal).normalize());
+                // For an upward normal these are X and Z
+                let reference = if normal.z.abs() < 0.999 { Vec3::Z } else { Vec3::X };
+                let tangent = normal.cross(reference).normalize();
+                let bitangent = tangent.cross(normal);
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let r = sample_range(rng, [0.0, radius]);
+                let position = center + tangent * (r * angle.cos()) + bitangent * (r * angle.sin());
+                (position, (position - center).normalize_or_zero())
+            }
+            EmitterShape::Sphere { center, radius } => {
+                let center = Vec3::from(center);
+                let z = rng.gen_range(-1.0f32..1.0);
+                let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                let ring = (1.0 - z * z).sqrt();
+                let direction = Vec3::new(ring * angle.cos(), z, ring * angle.sin());
+                // Cube root spreads points evenly through the volume
+                let r = radius * rng.gen::<f32>().cbrt();
+                (center + direction * r, direction)
+            }
+            EmitterShape::Box { center, half_extents } => {
+                let center = Vec3::from(center);
+                let offset = Vec3::from(half_extents.map(|half| sample_range(rng, [-half, half])));
+                (center + offset, offset.normalize_or_zero())
+            }
+            EmitterShape::MeshSurface => mesh.sample_surface(rng).unwrap_or((Vec3::ZERO, Vec3::ZERO)),
+            EmitterShape::MeshVertex => mesh.sample_vertex(rng).unwrap_or((Vec3::ZERO, Vec3::ZERO)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    #[test]
+    fn shipped_fire_matches_the_built_in_default() {
+        assert_eq!(ParticleConfig::load(DEFAULT_PARTICLES).unwrap(), ParticleConfig::default());
+    }
+
+    #[test]
+    fn shipped_multi_emitter_scene_loads() {
+        let config = ParticleConfig::load("assets/particles/campfire.toml").unwrap();
+        let names: Vec<&str> = config.emitters.iter().map(|emitter| emitter.name.as_str()).collect();
+        assert_eq!(names, ["fire", "smoke", "sparks", "embers", "wisps"]);
+        assert_eq!(config.emitters[1].sprite.texture, Some(PathBuf::from("assets/particles/smoke.png")));
+        assert_eq!(config.emitters[1].sprite.blend, BlendMode::Alpha);
+        assert!(matches!(config.fields[0].force, FieldForce::Wind { .. }));
+        assert_eq!(config.emitters[2].trail.segments(), 7);
+        assert_eq!(config.emitters[2].orientation, Orientation::VelocityStretch { stretch: 0.02 });
+        assert_eq!(config.emitters[3].sub_emitters[0].event, SubEmitterEvent::Death);
+        assert_eq!(config.emitter_index(&config.emitters[3].sub_emitters[0].emitter), Some(4));
+        assert_eq!(config.colliders[1].shape, ColliderShape::Mesh);
+        assert!(!config.colliders[1].kill);
+    }
+
+    #[test]
+    fn shipped_sparks_scene_loads() {
+        let config = ParticleConfig::load("assets/particles/sparks.toml").unwrap();
+        let names: Vec<&str> = config.emitters.iter().map(|emitter| emitter.name.as_str()).collect();
+        assert_eq!(names, ["fire", "sparks", "embers"]);
+        assert_eq!(config.emitters[1].bursts.len(), 1);
+        assert_eq!(config.emitters[2].shape, EmitterShape::MeshSurface);
+    }
+
+    #[test]
+    fn shipped_gpu_scene_asks_for_the_gpu() {
+        let config = ParticleConfig::load("assets/particles/inferno.toml").unwrap();
+        assert_eq!(config.simulation, SimulationBackend::Gpu);
+        assert_eq!(config.emitters[0].max_particles, 250_000);
+    }
+
+    #[test]
+    fn shipped_burning_scene_emits_from_the_mesh() {
+        let config = ParticleConfig::load("assets/particles/burning.toml").unwrap();
+        let shapes: Vec<&EmitterShape> = config.emitters.iter().map(|emitter| &emitter.shape).collect();
+        assert_eq!(shapes, [&EmitterShape::MeshSurface, &EmitterShape::MeshVertex]);
+    }
+
+    #[test]
+    fn parses_every_shape_and_fills_in_defaults() {
+        let config: ParticleConfig = toml::from_str(r#"
+            seed = 7
+
+            [[emitters]]
+            name = "sparks"
+            shape = { type = "point", position = [1.0, 2.0, 3.0] }
+            rate = 0.0
+            bursts = [{ time = 0.5, count = 40, interval = 2.0 }]
+
+            [[emitters]]
+            shape = { type = "sphere", center = [0.0, 0.0, 0.0], radius = 10.0 }
+            orientation = { type = "velocity_stretch", stretch = 0.05 }
+
+            [[emitters]]
+            shape = { type = "box", center = [0.0, 0.0, 0.0], half_extents = [1.0, 2.0, 3.0] }
+            orientation = { type = "fixed_axis", axis = [0.0, 1.0, 0.0] }
+
+            [[emitters]]
+            shape = { type = "mesh_surface" }
+            forces = { acceleration = [0.0, -98.0, 0.0] }
+
+            [[emitters]]
+            shape = { type = "mesh_vertex" }
+        "#).unwrap();
+
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.emitters.len(), 5);
+        assert_eq!(config.emitters[0].bursts, vec![Burst { time: 0.5, count: 40, interval: Some(2.0) }]);
+        assert_eq!(config.emitters[1].lifetime, EmitterConfig::default().lifetime);
+        assert_eq!(config.emitters[0].orientation, Orientation::ViewFacing);
+        assert_eq!(config.emitters[1].orientation, Orientation::VelocityStretch { stretch: 0.05 });
+        assert_eq!(config.emitters[2].orientation.index(), 2);
+        assert_eq!(config.emitters[3].shape, EmitterShape::MeshSurface);
+        assert_eq!(config.emitters[3].forces.acceleration, [0.0, -98.0, 0.0]);
+        assert_eq!(config.emitters[3].forces.drag, ForceConfig::default().drag);
+        assert_eq!(config.emitters[4].shape, EmitterShape::MeshVertex);
+    }
+
+    #[test]
+    fn parses_force_fields_with_their_volumes() {
+        let config: ParticleConfig = toml::from_str(r#"
+            [[fields]]
+            force = { type = "vortex", center = [0.0, 0.0, -300.0], strength = 40.0 }
+            volume = { type = "sphere", center = [0.0, 0.0, -300.0], radius = 200.0 }
+            falloff = 50.0
+
+            [[fields]]
+            force = { type = "turbulence", strength = 30.0, frequency = 0.01, speed = 0.5 }
+        "#).unwrap();
+
+        assert_eq!(config.fields[0].force, FieldForce::Vortex { center: [0.0, 0.0, -300.0], axis: [0.0, 1.0, 0.0], strength: 40.0 });
+        assert_eq!(config.fields[0].falloff, 50.0);
+        assert_eq!(config.fields[1].volume, FieldVolume::Everywhere);
+        let field = FieldConfig { force: FieldForce::Drag { coefficient: -1.0 }, ..Default::default() };
+        assert!(field.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields_and_empty_ranges() {
+        assert!(toml::from_str::<ParticleConfig>("[[emitters]]\nrat = 10.0").is_err());
+        let emitter = EmitterConfig { lifetime: [2.0, 1.0], ..Default::default() };
+        assert!(emitter.validate().is_err());
+        let collider = ColliderConfig { shape: ColliderShape::Plane { point: [0.0; 3], normal: [0.0; 3] }, ..Default::default() };
+        assert!(collider.validate().is_err());
+        let emitter = EmitterConfig { trail: TrailConfig { points: MAX_TRAIL_POINTS + 1, ..Default::default() }, ..Default::default() };
+        assert!(emitter.validate().is_err());
+        let emitter = EmitterConfig { orientation: Orientation::WorldAligned { normal: [0.0; 3] }, ..Default::default() };
+        assert!(emitter.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_bursts_that_would_never_move_on() {
+        let burst = |time: f32, interval: Option<f32>| EmitterConfig { bursts: vec![Burst { time, count: 1, interval }], ..Default::default() };
+        assert!(burst(0.5, Some(2.0)).validate().is_ok());
+        assert!(burst(0.5, Some(SIMULATION_STEP)).validate().is_ok());
+        assert!(burst(f32::NAN, None).validate().is_err());
+        assert!(burst(f32::INFINITY, Some(1.0)).validate().is_err());
+        assert!(burst(0.5, Some(f32::NAN)).validate().is_err());
+        assert!(burst(0.5, Some(1e-9)).validate().is_err());
+        assert!(burst(0.5, Some(0.0)).validate().is_err());
+    }
+
+    #[test]
+    fn flipbooks_play_over_life_from_their_start_frame() {
+        let sprite = SpriteConfig { frames: [4, 2], cycles: 2.0, ..Default::default() };
+        assert_eq!(sprite.frame(0.0, 0.0), 0.0);
+        assert_eq!(sprite.frame(0.3, 0.0), 4.0);
+        // Second time through, and wrapping past the last frame from a later start
+        assert_eq!(sprite.frame(0.6, 0.0), 1.0);
+        assert_eq!(sprite.frame(0.3, 6.0), 2.0);
+        // Frame 5 of the 4x2 sheet is second in the bottom row
+        assert_eq!(sprite.frame_rect([0.0, 0.5, 1.0, 0.5], 5.0), [0.25, 0.75, 0.25, 0.25]);
+    }
+
+    #[test]
+    fn shapes_sample_inside_their_bounds() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let mesh = EmissionMesh::default();
+        for _ in 0..200 {
+            let disk = EmitterShape::Disk { center: [0.0, 5.0, 0.0], radius: 2.0, normal: [1.0, 0.0, 0.0] };
+            let (position, _) = disk.sample(&mut rng, &mesh);
+            assert!((position.x).abs() < 1e-5 && (position - Vec3::new(0.0, 5.0, 0.0)).length() <= 2.0 + 1e-4);
+
+            let sphere = EmitterShape::Sphere { center: [0.0; 3], radius: 3.0 };
+            assert!(sphere.sample(&mut rng, &mesh).0.length() <= 3.0 + 1e-4);
+
+            let cube = EmitterShape::Box { center: [0.0; 3], half_extents: [1.0, 2.0, 3.0] };
+            let (position, _) = cube.sample(&mut rng, &mesh);
+            assert!(position.abs().cmple(Vec3::new(1.0, 2.0, 3.0)).all());
+        }
+    }
+
+    #[test]
+    fn mesh_shapes_follow_the_model_out_along_its_normals() {
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        let mut mesh = EmissionMesh::new(&crate::model_loader::ModelLoader::fallback_cube(), Mat4::IDENTITY);
+        assert_eq!(mesh.triangles().len(), 12);
+        assert_eq!(mesh.vertices().len(), 8);
+        // Turned a quarter about Y and moved off to the side
+        let center = Vec3::new(100.0, 0.0, 0.0);
+        mesh.set_model_transform(Mat4::from_translation(center) * Mat4::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        for shape in [EmitterShape::MeshSurface, EmitterShape::MeshVertex] {
+            for _ in 0..100 {
+                let (position, normal) = shape.sample(&mut rng, &mesh);
+                assert!((position - center).abs().max_element() <= 1.0 + 1e-4, "{shape:?} spawned at {position}");
+                assert!((normal.length() - 1.0).abs() < 1e-4);
+                assert!(normal.dot(position - center) > 0.0, "{shape:?} normal {normal} points into the cube");
+            }
+        }
+        // The front face's corners now face +X
+        assert!((0..100).any(|_| EmitterShape::MeshVertex.sample(&mut rng, &mesh).1.abs_diff_eq(Vec3::X, 1e-4)));
+    }
+}
@@ -0,0 +1,114 @@
+// Scene-level force fields: wind, gravity, attractors, vortices, turbulence and drag, each
+// acting inside its volume. particle_sim.wgsl applies them the same way on the GPU
+use crate::emitter::{FieldConfig, FieldForce, FieldVolume};
+use glam::Vec3;
+
+// Velocity after `fields` act on a particle at `position` for a step of `dt` ending at `time`
+pub fn apply(fields: &[FieldConfig], position: Vec3, mut velocity: Vec3, time: f32, dt: f32) -> Vec3 {
+    for field in fields {
+        let weight = weight(&field.volume, field.falloff, position);
+        if weight == 0.0 {
+            continue;
+        }
+        let dt = dt * weight;
+        match field.force {
+            FieldForce::Gravity { acceleration } => velocity += Vec3::from(acceleration) * dt,
+            FieldForce::Wind { velocity: wind, strength } => velocity += (Vec3::from(wind) - velocity) * (strength * dt).min(1.0),
+            FieldForce::Attractor { position: center, strength } => velocity += (Vec3::from(center) - position).normalize_or_zero() * strength * dt,
+            FieldForce::Repulsor { position: center, strength } => velocity += (position - Vec3::from(center)).normalize_or_zero() * strength * dt,
+            FieldForce::Vortex { center, axis, strength } => {
+                let around = Vec3::from(axis).normalize().cross(position - Vec3::from(center));
+                velocity += around.normalize_or_zero() * strength * dt;
+            }
+            FieldForce::Turbulence { strength, frequency, speed } => velocity += curl_noise(position * frequency, time * speed) * strength * dt,
+            FieldForce::Drag { coefficient } => velocity *= 1.0 - coefficient * dt,
+        }
+    }
+    velocity
+}
+
+// How much of the field reaches `position`: none outside the volume, rising to all of it
+// `falloff` in from the edge
+fn weight(volume: &FieldVolume, falloff: f32, position: Vec3) -> f32 {
+    // Distance in from the edge, negative outside
+    let depth = match *volume {
+        FieldVolume::Everywhere => return 1.0,
+        FieldVolume::Sphere { center, radius } => radius - position.distance(Vec3::from(center)),
+        FieldVolume::Box { center, half_extents } => (Vec3::from(half_extents) - (position - Vec3::from(center)).abs()).min_element(),
+    };
+    if depth < 0.0 {
+        0.0
+    } else if falloff == 0.0 {
+        1.0
+    } else {
+        (depth / falloff).min(1.0)
+    }
+}
+
+// Curl of a potential made of drifting sine waves, so the flow swirls without gathering
+// particles anywhere. Components stay within about ±1.5
+fn curl_noise(p: Vec3, t: f32) -> Vec3 {
+    // The potential is (sin(y + t) + sin(1.7z - 0.6t), sin(z + 1.3t) + sin(1.9x + 0.4t),
+    // sin(x + 0.8t) + sin(1.3y - 1.1t)); these are its partial derivatives
+    let dzdy = 1.3 * (1.3 * p.y - 1.1 * t).cos();
+    let dydz = (p.z + 1.3 * t).cos();
+    let dxdz = 1.7 * (1.7 * p.z - 0.6 * t).cos();
+    let dzdx = (p.x + 0.8 * t).cos();
+    let dydx = 1.9 * (1.9 * p.x + 0.4 * t).cos();
+    let dxdy = (p.y + t).cos();
+    Vec3::new(dzdy - dydz, dxdz - dzdx, dydx - dxdy) * 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(force: FieldForce, volume: FieldVolume, falloff: f32) -> FieldConfig {
+        FieldConfig { force, volume, falloff }
+    }
+
+    #[test]
+    fn forces_fade_in_from_their_volume_edge() {
+        let sphere = FieldVolume::Sphere { center: [0.0; 3], radius: 10.0 };
+        let up = field(FieldForce::Gravity { acceleration: [0.0, 10.0, 0.0] }, sphere, 4.0);
+        let fields = [up];
+        let lift = |x: f32| apply(&fields, Vec3::new(x, 0.0, 0.0), Vec3::ZERO, 0.0, 1.0).y;
+        assert_eq!(lift(0.0), 10.0);
+        assert_eq!(lift(8.0), 5.0);
+        assert_eq!(lift(11.0), 0.0);
+    }
+
+    #[test]
+    fn point_and_vortex_forces_point_the_right_way() {
+        let at = |force: FieldForce, position: Vec3| apply(&[field(force, FieldVolume::Everywhere, 0.0)], position, Vec3::ZERO, 0.0, 1.0);
+        let position = Vec3::new(2.0, 0.0, 0.0);
+        assert_eq!(at(FieldForce::Attractor { position: [0.0; 3], strength: 3.0 }, position), Vec3::new(-3.0, 0.0, 0.0));
+        assert_eq!(at(FieldForce::Repulsor { position: [0.0; 3], strength: 3.0 }, position), Vec3::new(3.0, 0.0, 0.0));
+        // Counterclockwise from above: +x turns toward -z
+        let vortex = FieldForce::Vortex { center: [0.0, 5.0, 0.0], axis: [0.0, 1.0, 0.0], strength: 2.0 };
+        assert!((at(vortex, position) - Vec3::new(0.0, 0.0, -2.0)).length() < 1e-6);
+    }
+
+    #[test]
+    fn wind_and_drag_pull_velocity_toward_theirs() {
+        let wind = field(FieldForce::Wind { velocity: [10.0, 0.0, 0.0], strength: 0.5 }, FieldVolume::Everywhere, 0.0);
+        let fields = [wind];
+        assert_eq!(apply(&fields, Vec3::ZERO, Vec3::ZERO, 0.0, 1.0), Vec3::new(5.0, 0.0, 0.0));
+        // Never overshoots the wind, however long the step
+        assert_eq!(apply(&fields, Vec3::ZERO, Vec3::ZERO, 0.0, 4.0), Vec3::new(10.0, 0.0, 0.0));
+        let drag = field(FieldForce::Drag { coefficient: 0.25 }, FieldVolume::Everywhere, 0.0);
+        assert_eq!(apply(&[drag], Vec3::ZERO, Vec3::new(0.0, 8.0, 0.0), 0.0, 1.0), Vec3::new(0.0, 6.0, 0.0));
+    }
+
+    #[test]
+    fn turbulence_has_no_divergence() {
+        // Central differences of the flow around a few points sum to (almost) nothing
+        let h = 1e-2;
+        for p in [Vec3::new(0.3, 1.2, -0.7), Vec3::new(-2.0, 0.5, 3.1)] {
+            let divergence = (curl_noise(p + Vec3::X * h, 0.4).x - curl_noise(p - Vec3::X * h, 0.4).x
+                + curl_noise(p + Vec3::Y * h, 0.4).y - curl_noise(p - Vec3::Y * h, 0.4).y
+                + curl_noise(p + Vec3::Z * h, 0.4).z - curl_noise(p - Vec3::Z * h, 0.4).z) / (2.0 * h);
+            assert!(divergence.abs() < 1e-2, "divergence {} at {}", divergence, p);
+        }
+    }
+}
@@ -0,0 +1,233 @@
+// Mesh processing for attributes a model file can leave out: normals and tangents
+use glam::Vec3;
+use std::collections::HashMap;
+
+// How missing normals are rebuilt from triangle topology
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum NormalMode {
+    // Every triangle gets its own face normal (faceted look)
+    Flat,
+    // Neighbouring faces are averaged unless they meet at more than `crease_angle` (radians)
+    Smooth { crease_angle: f32 },
+}
+
+impl Default for NormalMode {
+    fn default() -> Self {
+        NormalMode::Smooth { crease_angle: 60.0_f32.to_radians() }
+    }
+}
+
+// A triangle list rebuilt with generated normals. Vertices on a crease are split, so
+// `source[i]` is the input vertex that new vertex `i` copies its other attributes from
+pub struct RebuiltMesh {
+    pub source: Vec<u32>,
+    pub normals: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+}
+
+pub fn generate_normals(positions: &[[f32; 3]], indices: &[u32], mode: NormalMode) -> RebuiltMesh {
+    // Area-weighted face normals (the cross product's length is twice the area)
+    let face_normals: Vec<Vec3> = indices.chunks_exact(3)
+        .map(|triangle| {
+            let [a, b, c] = [0, 1, 2].map(|corner| Vec3::from(positions[triangle[corner] as usize]));
+            (b - a).cross(c - a)
+        })
+        .collect();
+
+    // Faces touching each position; matched by value so already-split seams still smooth
+    let mut faces_at: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    if let NormalMode::Smooth { .. } = mode {
+        for (face, triangle) in indices.chunks_exact(3).enumerate() {
+            for &index in triangle {
+                faces_at.entry(position_key(positions[index as usize])).or_default().push(face);
+            }
+        }
+    }
+
+    let mut rebuilt = RebuiltMesh { source: Vec::new(), normals: Vec::new(), indices: Vec::with_capacity(indices.len()) };
+    // Corners of the same input vertex with the same normal share an output vertex
+    let mut welded: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    for (face, triangle) in indices.chunks_exact(3).enumerate() {
+        let face_normal = face_normals[face].normalize_or_zero();
+        for &index in triangle {
+            let normal = match mode {
+                NormalMode::Flat => face_normal,
+                NormalMode::Smooth { crease_angle } => {
+                    let min_cos = crease_angle.cos();
+                    faces_at[&position_key(positions[index as usize])].iter()
+                        .filter(|&&other| face_normals[other].normalize_or_zero().dot(face_normal) >= min_cos)
+                        .map(|&other| face_normals[other])
+                        .sum::<Vec3>()
+                        .normalize_or_zero()
+                }
+            };
+            // Degenerate triangles have no direction of their own, point them up
+            let normal = if normal == Vec3::ZERO { Vec3::Y } else { normal };
+            let normal = normal.to_array();
+            let vertex = *welded.entry((index, position_key(normal))).or_insert_with(|| {
+                rebuilt.source.push(index);
+                rebuilt.normals.push(normal);
+                rebuilt.source.len() as u32 - 1
+            });
+            rebuilt.indices.push(vertex);
+        }
+    }
+    rebuilt
+}
+
+// Bit pattern to match vectors by; adding zero folds -0.0 into 0.0 so they match too
+fn position_key(position: [f32; 3]) -> [u32; 3] {
+    position.map(|component| (component + 0.0).to_bits())
+}
+
+// MikkTSpace tangents (xyz + handedness in w), as glTF expects when TANGENT is absent
+pub fn generate_tangents(positions: &[[f32; 3]], normals: &[[f32; 3]], uvs: &[[f32; 2]], indices: &[u32]) -> Vec<[f32; 4]> {
+    let mut geometry = TangentGeometry {
+        positions,
+        normals,
+        uvs,
+        indices,
+        tangents: vec![[1.0, 0.0, 0.0, 1.0]; positions.len()],
+    };
+    if !bevy_mikktspace::generate_tangents(&mut geometry) {
+        println!("Warning: tangent generation failed, using default tangents");
+    }
+    geometry.tangents
+}
+
+struct TangentGeometry<'a> {
+    positions: &'a [[f32; 3]],
+    normals: &'a [[f32; 3]],
+    uvs: &'a [[f32; 2]],
+    indices: &'a [u32],
+    tangents: Vec<[f32; 4]>,
+}
+
+impl TangentGeometry<'_> {
+    fn vertex(&self, face: usize, vert: usize) -> usize {
+        self.indices[face * 3 + vert] as usize
+    }
+}
+
+impl bevy_mikktspace::Geometry for TangentGeometry<'_> {
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.positions[self.vertex(face, vert)]
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.normals[self.vertex(face, vert)]
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.uvs[self.vertex(face, vert)]
+    }
+
+    // Corners of a shared vertex agree except on UV seams, where the last face wins
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        let vertex = self.vertex(face, vert);
+        self.tangents[vertex] = tangent;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Eight shared corners, two triangles per face, wound outward
+    fn cube() -> (Vec<[f32; 3]>, Vec<u32>) {
+        let positions = vec![
+            [-1.0, -1.0, 1.0], [1.0, -1.0, 1.0], [1.0, 1.0, 1.0], [-1.0, 1.0, 1.0],
+            [-1.0, -1.0, -1.0], [1.0, -1.0, -1.0], [1.0, 1.0, -1.0], [-1.0, 1.0, -1.0],
+        ];
+        let indices = vec![
+            0, 1, 2, 2, 3, 0, 4, 6, 5, 6, 4, 7, 4, 0, 3, 3, 7, 4,
+            1, 5, 6, 6, 2, 1, 3, 2, 6, 6, 7, 3, 4, 5, 1, 1, 0, 4,
+        ];
+        (positions, indices)
+    }
+
+    // Latitude/longitude sphere with a vertex at each pole, every vertex shared by its faces
+    fn sphere(segments: u32, rings: u32) -> (Vec<[f32; 3]>, Vec<u32>) {
+        let mut positions = vec![[0.0, 1.0, 0.0]];
+        for ring in 1..rings {
+            let (sin_polar, cos_polar) = (ring as f32 / rings as f32 * std::f32::consts::PI).sin_cos();
+            for segment in 0..segments {
+                let (sin_azimuth, cos_azimuth) = (segment as f32 / segments as f32 * std::f32::consts::TAU).sin_cos();
+                positions.push([sin_polar * cos_azimuth, cos_polar, -sin_polar * sin_azimuth]);
+            }
+        }
+        positions.push([0.0, -1.0, 0.0]);
+        let bottom = positions.len() as u32 - 1;
+        let at = |ring: u32, segment: u32| 1 + (ring - 1) * segments + segment % segments;
+        let mut indices = Vec::new();
+        for segment in 0..segments {
+            indices.extend([0, at(1, segment), at(1, segment + 1)]);
+            indices.extend([bottom, at(rings - 1, segment + 1), at(rings - 1, segment)]);
+            for ring in 1..rings - 1 {
+                let [a, b, c, d] = [at(ring, segment), at(ring + 1, segment), at(ring + 1, segment + 1), at(ring, segment + 1)];
+                indices.extend([a, b, c, c, d, a]);
+            }
+        }
+        (positions, indices)
+    }
+
+    #[test]
+    fn cube_stays_faceted_at_a_sixty_degree_crease() {
+        let (positions, indices) = cube();
+        let rebuilt = generate_normals(&positions, &indices, NormalMode::default());
+        // Each corner splits three ways, one per face it's on
+        assert_eq!(rebuilt.normals.len(), 24);
+        for triangle in rebuilt.indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|corner| Vec3::from(rebuilt.normals[triangle[corner] as usize]));
+            assert!(a == b && b == c, "triangle not flat: {} {} {}", a, b, c);
+            assert_eq!(a.abs().max_element(), 1.0);
+            // Facing out of the cube
+            let corner = Vec3::from(positions[rebuilt.source[triangle[0] as usize] as usize]);
+            assert!(a.dot(corner) > 0.0);
+        }
+        // Flat gives the same split here, the cube has no shallower edges to smooth
+        assert_eq!(generate_normals(&positions, &indices, NormalMode::Flat).normals, rebuilt.normals);
+    }
+
+    #[test]
+    fn sphere_smooths_across_its_faces() {
+        let (positions, indices) = sphere(16, 8);
+        let rebuilt = generate_normals(&positions, &indices, NormalMode::default());
+        // Nothing split, and every normal points straight out from the center
+        assert_eq!(rebuilt.normals.len(), positions.len());
+        for (&source, normal) in rebuilt.source.iter().zip(&rebuilt.normals) {
+            let outward = Vec3::from(positions[source as usize]);
+            assert!(Vec3::from(*normal).dot(outward) > 0.99, "{:?} at {}", normal, outward);
+        }
+        // While flat shading gives each face its own corners
+        let flat = generate_normals(&positions, &indices, NormalMode::Flat);
+        assert!(flat.normals.len() > positions.len() * 2);
+    }
+
+    #[test]
+    fn tangents_are_unit_length_and_orthogonal_to_the_normal() {
+        let (positions, indices) = cube();
+        let rebuilt = generate_normals(&positions, &indices, NormalMode::default());
+        let positions: Vec<[f32; 3]> = rebuilt.source.iter().map(|&index| positions[index as usize]).collect();
+        // Each face's UVs laid along the two axes it spans
+        let uvs: Vec<[f32; 2]> = positions.iter().zip(&rebuilt.normals).map(|(&[x, y, z], normal)| {
+            if normal[0] != 0.0 { [z, y] } else if normal[1] != 0.0 { [x, z] } else { [x, y] }
+        }).collect();
+        let tangents = generate_tangents(&positions, &rebuilt.normals, &uvs, &rebuilt.indices);
+        assert_eq!(tangents.len(), positions.len());
+        for (tangent, normal) in tangents.iter().zip(&rebuilt.normals) {
+            let direction = Vec3::new(tangent[0], tangent[1], tangent[2]);
+            assert!((direction.length() - 1.0).abs() < 1e-4, "tangent {:?}", tangent);
+            assert!(direction.dot(Vec3::from(*normal)).abs() < 1e-4, "tangent {:?} against normal {:?}", tangent, normal);
+            assert_eq!(tangent[3].abs(), 1.0);
+        }
+    }
+}
@@ -1,153 +1,215 @@
-use crate::types::Vertex;
+use crate::material::{AlphaMode, Material};
+use crate::model::{Mesh, ModelData, Primitive, SceneNode};
+use crate::model_loader::{GeometryBuilder, LoadError, LoadOptions, PrimitiveData};
+use glam::Mat4;
+use crate::texture::{SamplerSettings, TextureData};
 
+// Reads .gltf (with external or embedded buffers) and binary .glb files
 pub struct GltfLoader;
 
 impl GltfLoader {
-    pub fn load_gltf(path: &str) -> (Vec<Vertex>, Vec<u16>, [f32; 4]) {
-        // Try to load the glTF file with proper error handling
-        let (gltf, buffers, _images) = match gltf::import(path) {
-            Ok(data) => data,
-            Err(e) => {
-                println!("Failed to load glTF file '{}': {}", path, e);
-                println!("Falling back to default cube");
-                let (vertices, indices) = Self::create_fallback_cube();
-                return (vertices, indices, [0.5, 0.5, 0.5, 1.0]);
-            }
-        };
-        
-        // Get material color from first material
-        let base_color = if let Some(material) = gltf.materials().next() {
-            let pbr = material.pbr_metallic_roughness();
-            let color = pbr.base_color_factor();
-            println!("🪨 Using material color: [{:.3}, {:.3}, {:.3}, {:.3}]", 
-                     color[0], color[1], color[2], color[3]);
-            color
-        } else {
-            [0.5, 0.5, 0.5, 1.0] // Default gray
-        };
-        
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+    pub fn load(path: &str, options: &LoadOptions) -> Result<ModelData, LoadError> {
+        let (gltf, buffers, images) = gltf::import(path).map_err(|source| match source {
+            gltf::Error::Io(ref e) if e.kind() == std::io::ErrorKind::NotFound => LoadError::MissingFile { path: path.to_string() },
+            source => LoadError::Import { path: path.to_string(), source },
+        })?;
+
+        // Base-color textures, one entry per glTF texture (image + sampler pair)
+        let mut textures = Vec::new();
+        let mut texture_indices = vec![None; gltf.textures().len()];
+        let mut materials: Vec<Material> = gltf.materials()
+            .map(|material| Self::convert_material(&material, &images, &mut textures, &mut texture_indices))
+            .collect();
+        // Primitives without a material share a default one, appended on first use
+        let mut default_material = None;
+
+        let mut builder = GeometryBuilder::default();
+        let mut meshes = Vec::new();
 
         for mesh in gltf.meshes() {
+            let mut primitives = Vec::new();
             for primitive in mesh.primitives() {
-                // Handle missing buffer data gracefully
-                let reader = primitive.reader(|buffer| {
-                    if buffer.index() < buffers.len() {
-                        Some(&buffers[buffer.index()])
-                    } else {
-                        None
-                    }
-                });
-                
-                // Read positions and normals
-                if let Some(positions) = reader.read_positions() {
-                    let normals = reader.read_normals();
-                    let vertex_offset = vertices.len() as u16;
-                    
-                    // Collect positions and normals
-                    let positions: Vec<[f32; 3]> = positions.collect();
-                    let normals: Vec<[f32; 3]> = if let Some(normals) = normals {
-                        normals.collect()
-                    } else {
-                        // Generate simple normals if not present (pointing up)
-                        vec![[0.0, 1.0, 0.0]; positions.len()]
-                    };
-                    
-                    // Add vertices with normals
-                    for (position, normal) in positions.iter().zip(normals.iter()) {
-                        vertices.push(Vertex {
-                            position: *position,
-                            normal: *normal,
-                        });
-                    }
-                    
-                    // Read indices and keep as triangles (no wireframe conversion)
-                    if let Some(indices_reader) = reader.read_indices() {
-                        let triangle_indices: Vec<u32> = indices_reader.into_u32().collect();
-                        
-                        // Add triangle indices directly
-                        for &index in triangle_indices.iter() {
-                            indices.push((index as u16) + vertex_offset);
+                let (mesh_index, primitive_index) = (mesh.index(), primitive.index());
+                if primitive.mode() != gltf::mesh::Mode::Triangles {
+                    return Err(LoadError::UnsupportedMode { mesh: mesh_index, primitive: primitive_index, mode: primitive.mode() });
+                }
+                // Every accessor must point into a buffer we actually have
+                let accessors = primitive.attributes().map(|(_, accessor)| accessor).chain(primitive.indices());
+                for accessor in accessors {
+                    if let Some(view) = accessor.view() {
+                        if view.buffer().index() >= buffers.len() {
+                            return Err(LoadError::BadBufferIndex { mesh: mesh_index, primitive: primitive_index, buffer: view.buffer().index() });
                         }
                     }
-                } else {
-                    println!("Warning: Mesh primitive has no position data");
                 }
+                let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|data| &data[..]));
+
+                let positions: Vec<[f32; 3]> = match reader.read_positions() {
+                    Some(positions) => positions.collect(),
+                    None => return Err(LoadError::NoPositions { mesh: mesh_index, primitive: primitive_index }),
+                };
+                let data = PrimitiveData {
+                    positions,
+                    normals: reader.read_normals().map(|normals| normals.collect()),
+                    uvs: reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect()),
+                    tangents: reader.read_tangents().map(|tangents| tangents.collect()),
+                    indices: reader.read_indices().map(|indices| indices.into_u32().collect()),
+                };
+                let (first_index, index_count) = builder.push(data, options)
+                    .map_err(|reason| LoadError::InvalidPrimitive { mesh: mesh_index, primitive: primitive_index, reason })?;
+
+                let material = match primitive.material().index() {
+                    Some(index) => index,
+                    None => *default_material.get_or_insert_with(|| {
+                        materials.push(Material::default());
+                        materials.len() - 1
+                    }),
+                };
+                primitives.push(Primitive { first_index, index_count, material });
+            }
+            meshes.push(Mesh { primitives });
+        }
+
+        // Node hierarchy of the default scene (or the first one)
+        let nodes: Vec<SceneNode> = gltf.nodes()
+            .map(|node| SceneNode {
+                transform: Mat4::from_cols_array_2d(&node.transform().matrix()),
+                mesh: node.mesh().map(|mesh| mesh.index()),
+                children: node.children().map(|child| child.index()).collect(),
+            })
+            .collect();
+        let roots: Vec<usize> = match gltf.default_scene().or_else(|| gltf.scenes().next()) {
+            Some(scene) => scene.nodes().map(|node| node.index()).collect(),
+            None => Vec::new(),
+        };
+        if roots.is_empty() {
+            // No scene at all: show every mesh once at the origin
+            println!("Warning: glTF file has no scene, placing each mesh at the origin");
+        }
+        builder.finish(meshes, materials, textures, nodes, roots)
+    }
+
+    fn convert_material(
+        material: &gltf::Material,
+        images: &[gltf::image::Data],
+        textures: &mut Vec<TextureData>,
+        texture_indices: &mut [Option<usize>],
+    ) -> Material {
+        let pbr = material.pbr_metallic_roughness();
+        let base_color = pbr.base_color_factor();
+        println!("🪨 Material {:?}: color [{:.3}, {:.3}, {:.3}, {:.3}]",
+                 material.name().unwrap_or("unnamed"), base_color[0], base_color[1], base_color[2], base_color[3]);
+
+        let mut base_color_texture = None;
+        if let Some(info) = pbr.base_color_texture() {
+            if info.tex_coord() != 0 {
+                println!("Warning: base color texture uses TEXCOORD_{}, only TEXCOORD_0 is supported", info.tex_coord());
             }
+            let texture = info.texture();
+            // Materials can share a texture, only convert it once
+            base_color_texture = match texture_indices[texture.index()] {
+                Some(index) => Some(index),
+                None => match images.get(texture.source().index()) {
+                    Some(image) => {
+                        println!("🖼️ Using base color texture: {}x{}", image.width, image.height);
+                        textures.push(Self::convert_image(image, Self::convert_sampler(&texture.sampler())));
+                        texture_indices[texture.index()] = Some(textures.len() - 1);
+                        Some(textures.len() - 1)
+                    }
+                    None => {
+                        println!("Warning: base color texture references missing image {}", texture.source().index());
+                        None
+                    }
+                },
+            };
         }
 
-        if vertices.is_empty() {
-            println!("No valid geometry found in glTF file, using fallback cube");
-            let (vertices, indices) = Self::create_fallback_cube();
-            return (vertices, indices, [0.5, 0.5, 0.5, 1.0]);
+        Material {
+            base_color,
+            base_color_texture,
+            metallic: pbr.metallic_factor(),
+            roughness: pbr.roughness_factor(),
+            emissive: material.emissive_factor(),
+            alpha_mode: match material.alpha_mode() {
+                gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+                gltf::material::AlphaMode::Mask => AlphaMode::Mask,
+                gltf::material::AlphaMode::Blend => AlphaMode::Blend,
+            },
+            alpha_cutoff: material.alpha_cutoff().unwrap_or(0.5),
+            double_sided: material.double_sided(),
         }
+    }
+
+    // Expand any glTF pixel format to 8-bit RGBA
+    fn convert_image(image: &gltf::image::Data, sampler: SamplerSettings) -> TextureData {
+        use gltf::image::Format;
+
+        let texel_count = (image.width * image.height) as usize;
+        let mut pixels = Vec::with_capacity(texel_count * 4);
+        // 16-bit channels are little-endian, keep the high byte; floats are clamped to [0, 1]
+        let channel = |bytes: &[u8], i: usize, width: usize| -> u8 {
+            match width {
+                1 => bytes[i],
+                2 => bytes[i * 2 + 1],
+                _ => {
+                    let value = f32::from_le_bytes([bytes[i * 4], bytes[i * 4 + 1], bytes[i * 4 + 2], bytes[i * 4 + 3]]);
+                    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+                }
+            }
+        };
+        let (components, width) = match image.format {
+            Format::R8 => (1, 1),
+            Format::R8G8 => (2, 1),
+            Format::R8G8B8 => (3, 1),
+            Format::R8G8B8A8 => (4, 1),
+            Format::R16 => (1, 2),
+            Format::R16G16 => (2, 2),
+            Format::R16G16B16 => (3, 2),
+            Format::R16G16B16A16 => (4, 2),
+            Format::R32G32B32FLOAT => (3, 4),
+            Format::R32G32B32A32FLOAT => (4, 4),
+        };
 
-        // Calculate model dimensions
-        let mut min_x = f32::INFINITY;
-        let mut max_x = f32::NEG_INFINITY;
-        let mut min_y = f32::INFINITY;
-        let mut max_y = f32::NEG_INFINITY;
-        let mut min_z = f32::INFINITY;
-        let mut max_z = f32::NEG_INFINITY;
-
-        for vertex in &vertices {
-            min_x = min_x.min(vertex.position[0]);
-            max_x = max_x.max(vertex.position[0]);
-            min_y = min_y.min(vertex.position[1]);
-            max_y = max_y.max(vertex.position[1]);
-            min_z = min_z.min(vertex.position[2]);
-            max_z = max_z.max(vertex.position[2]);
+        for texel in 0..texel_count {
+            let base = texel * components;
+            let rgba = match components {
+                // Grayscale (+ alpha) spreads the first channel across RGB
+                1 => { let l = channel(&image.pixels, base, width); [l, l, l, 255] }
+                2 => { let l = channel(&image.pixels, base, width); [l, l, l, channel(&image.pixels, base + 1, width)] }
+                3 => [channel(&image.pixels, base, width), channel(&image.pixels, base + 1, width), channel(&image.pixels, base + 2, width), 255],
+                _ => [channel(&image.pixels, base, width), channel(&image.pixels, base + 1, width), channel(&image.pixels, base + 2, width), channel(&image.pixels, base + 3, width)],
+            };
+            pixels.extend_from_slice(&rgba);
         }
 
-        let width = max_x - min_x;
-        let height = max_y - min_y;
-        let depth = max_z - min_z;
-
-        println!("💾 Loaded glTF: {} vertices, {} triangle indices", vertices.len(), indices.len());
-        println!("📏 Model dimensions:");
-        println!("  Width (X): {:.4} (from {:.4} to {:.4})", width, min_x, max_x);
-        println!("  Height (Y): {:.4} (from {:.4} to {:.4})", height, min_y, max_y);
-        println!("  Depth (Z): {:.4} (from {:.4} to {:.4})", depth, min_z, max_z);
-        println!("  Center: ({:.4}, {:.4}, {:.4})", 
-                 (min_x + max_x) / 2.0, 
-                 (min_y + max_y) / 2.0, 
-                 (min_z + max_z) / 2.0);
-        
-        (vertices, indices, base_color)
+        TextureData { width: image.width, height: image.height, pixels, sampler }
     }
-    
-    // safety cube!!! 🧊
-    fn create_fallback_cube() -> (Vec<Vertex>, Vec<u16>) {
-        let vertices = vec![
-            // Front face
-            Vertex { position: [-1.0, -1.0,  1.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 1.0, -1.0,  1.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 1.0,  1.0,  1.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [-1.0,  1.0,  1.0], normal: [0.0, 0.0, 1.0] },
-            // Back face
-            Vertex { position: [-1.0, -1.0, -1.0], normal: [0.0, 0.0, -1.0] },
-            Vertex { position: [ 1.0, -1.0, -1.0], normal: [0.0, 0.0, -1.0] },
-            Vertex { position: [ 1.0,  1.0, -1.0], normal: [0.0, 0.0, -1.0] },
-            Vertex { position: [-1.0,  1.0, -1.0], normal: [0.0, 0.0, -1.0] },
-        ];
-
-        let indices = vec![
-            // Front face
-            0, 1, 2,  2, 3, 0,
-            // Back face  
-            4, 6, 5,  6, 4, 7,
-            // Left face
-            4, 0, 3,  3, 7, 4,
-            // Right face
-            1, 5, 6,  6, 2, 1,
-            // Top face
-            3, 2, 6,  6, 7, 3,
-            // Bottom face
-            4, 5, 1,  1, 0, 4,
-        ];
-
-        println!("Using fallback cube: {} vertices, {} triangle indices", vertices.len(), indices.len());
-        (vertices, indices)
+
+    fn convert_sampler(sampler: &gltf::texture::Sampler) -> SamplerSettings {
+        use gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+        let address_mode = |mode: WrappingMode| match mode {
+            WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+            WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+            WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+        };
+        let mag_filter = match sampler.mag_filter() {
+            Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+            _ => wgpu::FilterMode::Linear,
+        };
+        let (min_filter, mipmap_filter) = match sampler.min_filter() {
+            Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => (wgpu::FilterMode::Nearest, wgpu::MipmapFilterMode::Nearest),
+            Some(MinFilter::NearestMipmapLinear) => (wgpu::FilterMode::Nearest, wgpu::MipmapFilterMode::Linear),
+            Some(MinFilter::LinearMipmapNearest) => (wgpu::FilterMode::Linear, wgpu::MipmapFilterMode::Nearest),
+            _ => (wgpu::FilterMode::Linear, wgpu::MipmapFilterMode::Linear),
+        };
+
+        SamplerSettings {
+            address_mode_u: address_mode(sampler.wrap_s()),
+            address_mode_v: address_mode(sampler.wrap_t()),
+            mag_filter,
+            min_filter,
+            mipmap_filter,
+        }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,1134 @@
+// Particle simulation in compute shaders, for pools far beyond what the CPU path can step
+// each frame. Spawn counts still come from the CPU so rates and bursts behave exactly as
+// they do there; everything per particle happens in shaders/particle_sim.wgsl
+use crate::collision::Bvh;
+use crate::emitter::{ColliderConfig, ColliderShape, EmissionMesh, EmitterConfig, EmitterShape, FieldConfig, FieldForce, FieldVolume, Orientation, ParticleConfig, SubEmitterConfig};
+use crate::particles::SpawnSchedule;
+use crate::types::{ParticleInstance, TrailSegment};
+use bytemuck::{Pod, Zeroable};
+use glam::Mat4;
+use wgpu::util::DeviceExt;
+
+// Must match the constants in particle_sim.wgsl
+pub const MAX_EMITTERS: usize = 16;
+const MAX_COLLIDERS: usize = 8;
+const MAX_FIELDS: usize = 8;
+const MAX_SUB_EMITTERS: usize = 16;
+const CURVE_SAMPLES: usize = 64;
+const WORKGROUP_SIZE: u32 = 64;
+
+// Size of the shader's Particle struct, padded to the 16-byte alignment of its vec3s
+const PARTICLE_SIZE: u64 = 96;
+// Byte offsets of the particle and trail draw arguments, and spawn_events' dispatch, in the
+// indirect buffer
+const DRAW_ARGS_OFFSET: u64 = 16;
+const TRAIL_DRAW_ARGS_OFFSET: u64 = 36;
+const SPAWN_DISPATCH_OFFSET: u64 = 56;
+// Size of the shader's Event struct
+const EVENT_SIZE: u64 = 32;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct Globals {
+    dt: f32,
+    time: f32,
+    seed: u32,
+    step: u32,
+    emitter_count: u32,
+    spawn_total: u32,
+    triangle_count: u32,
+    interpolation: f32,
+    sort_size: u32,
+    trail_segments: u32,
+    event_capacity: u32,
+    vertex_count: u32,
+    vertex_offset: u32,
+    _padding: [u32; 3],
+    view_depth: [f32; 4],
+}
+
+// One compare-and-swap pass of the bitonic sort
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct SortStep {
+    block: u32,
+    distance: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct GpuEmitter {
+    center_radius: [f32; 4],
+    axis: [f32; 4],
+    direction_growth: [f32; 4],
+    speed_radial: [f32; 4],
+    lifetime_size: [f32; 4],
+    acceleration: [f32; 4],
+    drag: [f32; 4],
+    swirl: [f32; 4],
+    waver: [f32; 4],
+    sprite_rect: [f32; 4],
+    sprite: [f32; 4],
+    spin: [f32; 4],
+    orientation_axis: [f32; 4],
+    pool_base: u32,
+    pool_size: u32,
+    spawn_count: u32,
+    spawn_offset: u32,
+    shape: u32,
+    random_start_frame: u32,
+    softness: f32,
+    trail_points: u32,
+    trail_base: u32,
+    trail_width: f32,
+    sub_base: u32,
+    sub_count: u32,
+    orientation: u32,
+    _padding: [u32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct GpuSubEmitter {
+    emitter: u32,
+    event: u32,
+    count: u32,
+    inherit_velocity: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct GpuField {
+    a: [f32; 4],
+    b: [f32; 4],
+    volume_center: [f32; 4],
+    volume_half_extents: [f32; 4],
+    force: u32,
+    volume: u32,
+    falloff: f32,
+    _padding: u32,
+}
+
+impl GpuField {
+    fn new(config: &FieldConfig) -> Self {
+        let extend = |[x, y, z]: [f32; 3], w: f32| [x, y, z, w];
+        // Repulsors are attractors pulling the other way
+        let (force, a, b) = match config.force {
+            FieldForce::Gravity { acceleration } => (0, extend(acceleration, 0.0), [0.0; 4]),
+            FieldForce::Wind { velocity, strength } => (1, extend(velocity, strength), [0.0; 4]),
+            FieldForce::Attractor { position, strength } => (2, extend(position, strength), [0.0; 4]),
+            FieldForce::Repulsor { position, strength } => (2, extend(position, -strength), [0.0; 4]),
+            FieldForce::Vortex { center, axis, strength } => (3, extend(center, strength), extend(axis, 0.0)),
+            FieldForce::Turbulence { strength, frequency, speed } => (4, [0.0, 0.0, 0.0, strength], [frequency, speed, 0.0, 0.0]),
+            FieldForce::Drag { coefficient } => (5, [0.0, 0.0, 0.0, coefficient], [0.0; 4]),
+        };
+        let (volume, volume_center, volume_half_extents) = match config.volume {
+            FieldVolume::Everywhere => (0, [0.0; 4], [0.0; 4]),
+            FieldVolume::Sphere { center, radius } => (1, extend(center, radius), [0.0; 4]),
+            FieldVolume::Box { center, half_extents } => (2, extend(center, 0.0), extend(half_extents, 0.0)),
+        };
+        Self { a, b, volume_center, volume_half_extents, force, volume, falloff: config.falloff, _padding: 0 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct GpuFields {
+    count: u32,
+    _padding: [u32; 3],
+    fields: [GpuField; MAX_FIELDS],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, Pod, Zeroable)]
+struct GpuCollider {
+    a: [f32; 4],
+    b: [f32; 4],
+    bounce: f32,
+    friction: f32,
+    kill: u32,
+    shape: u32,
+}
+
+impl GpuCollider {
+    fn new(config: &ColliderConfig) -> Self {
+        let extend = |[x, y, z]: [f32; 3], w: f32| [x, y, z, w];
+        let (shape, a, b) = match config.shape {
+            ColliderShape::Plane { point, normal } => (0, extend(point, 0.0), extend(normal, 0.0)),
+            ColliderShape::Sphere { center, radius } => (1, extend(center, radius), [0.0; 4]),
+            ColliderShape::Box { center, half_extents } => (2, extend(center, 0.0), extend(half_extents, 0.0)),
+            ColliderShape::Mesh => (3, [0.0; 4], [0.0; 4]),
+        };
+        Self {
+            a,
+            b,
+            bounce: config.bounce,
+            friction: config.friction,
+            kill: config.kill as u32,
+            shape,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct GpuColliders {
+    model: [[f32; 4]; 4],
+    model_inverse: [[f32; 4]; 4],
+    count: u32,
+    bvh_nodes: u32,
+    // Where the BVH starts in the mesh buffer, in vec4s
+    bvh_offset: u32,
+    _padding: u32,
+    colliders: [GpuCollider; MAX_COLLIDERS],
+}
+
+impl GpuSubEmitter {
+    // None when `config` names no emitter
+    fn new(particles: &ParticleConfig, config: &SubEmitterConfig) -> Option<Self> {
+        Some(Self {
+            emitter: particles.emitter_index(&config.emitter)? as u32,
+            event: config.event as u32,
+            count: config.count,
+            inherit_velocity: config.inherit_velocity,
+        })
+    }
+}
+
+impl GpuEmitter {
+    fn new(config: &EmitterConfig, pool_base: u32, sprite_region: Option<[f32; 4]>) -> Self {
+        let (shape, center, radius, axis) = match config.shape {
+            EmitterShape::Point { position } => (0, position, 0.0, [0.0; 3]),
+            EmitterShape::Disk { center, radius, normal } => (1, center, radius, normal),
+            EmitterShape::Sphere { center, radius } => (2, center, radius, [0.0; 3]),
+            EmitterShape::Box { center, half_extents } => (3, center, 0.0, half_extents),
+            EmitterShape::MeshSurface => (4, [0.0; 3], 0.0, [0.0; 3]),
+            EmitterShape::MeshVertex => (5, [0.0; 3], 0.0, [0.0; 3]),
+        };
+        let [x, y, z] = center;
+        let direction = config.velocity.direction;
+        let orientation_axis = match config.orientation {
+            Orientation::ViewFacing => [0.0; 4],
+            Orientation::VelocityStretch { stretch } => [0.0, 0.0, 0.0, stretch],
+            Orientation::FixedAxis { axis: direction } | Orientation::WorldAligned { normal: direction } => glam::Vec3::from(direction).normalize().extend(0.0).to_array(),
+        };
+        let forces = &config.forces;
+        let sprite = &config.sprite;
+        Self {
+            center_radius: [x, y, z, radius],
+            axis: [axis[0], axis[1], axis[2], 0.0],
+            direction_growth: [direction[0], direction[1], direction[2], config.growth],
+            speed_radial: [config.velocity.speed[0], config.velocity.speed[1], config.velocity.radial[0], config.velocity.radial[1]],
+            lifetime_size: [config.lifetime[0], config.lifetime[1], config.size[0], config.size[1]],
+            acceleration: [forces.acceleration[0], forces.acceleration[1], forces.acceleration[2], 0.0],
+            drag: [forces.drag[0], forces.drag[1], forces.drag[2], 0.0],
+            swirl: [forces.swirl.amplitude, forces.swirl.frequency, forces.swirl.height_scale, 0.0],
+            waver: [forces.waver.amplitude[0], forces.waver.amplitude[1], forces.waver.frequency[0], forces.waver.frequency[1]],
+            sprite_rect: sprite_region.unwrap_or_default(),
+            sprite: [sprite.frames[0] as f32, sprite.frames[1] as f32, sprite.cycles, sprite.blend as u32 as f32],
+            spin: [config.rotation[0], config.rotation[1], config.angular_velocity[0], config.angular_velocity[1]],
+            orientation_axis,
+            pool_base,
+            pool_size: config.max_particles as u32,
+            shape,
+            random_start_frame: sprite.random_start_frame as u32,
+            softness: sprite.softness,
+            trail_points: if config.trail.segments() > 0 { config.trail.points } else { 0 },
+            trail_width: config.trail.width,
+            orientation: config.orientation.index(),
+            ..Default::default()
+        }
+    }
+}
+
+pub struct GpuSimulation {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    configs: Vec<EmitterConfig>,
+    schedules: Vec<SpawnSchedule>,
+    emitters: Vec<GpuEmitter>,
+    globals: Globals,
+    globals_buffer: wgpu::Buffer,
+    emitter_buffer: wgpu::Buffer,
+    // Where the model mesh emitters and colliders follow is, rewritten at the start of the
+    // buffer every step
+    model_transform: Mat4,
+    collider_buffer: wgpu::Buffer,
+    // Bindings for stepping and for drawing: [i] reads alive list i (and steps into the other)
+    step_bind_groups: [wgpu::BindGroup; 2],
+    draw_bind_groups: [wgpu::BindGroup; 2],
+    // Which alive list holds the current particles
+    current: usize,
+    // The indirect args as storage, for the kernels that write them
+    indirect_bind_group: wgpu::BindGroup,
+    // Every pass of the sort, one per dynamic offset
+    sort_bind_group: wgpu::BindGroup,
+    sort_steps: u32,
+    sort_step_stride: u32,
+    // Draw back to front, for emitters whose blending depends on order
+    sorted: bool,
+    pub instance_buffer: wgpu::Buffer,
+    // Every particle's trail segments, unused ones zero-width
+    pub trail_buffer: wgpu::Buffer,
+    pub indirect_buffer: wgpu::Buffer,
+    begin_step: wgpu::ComputePipeline,
+    simulate: wgpu::ComputePipeline,
+    emit: wgpu::ComputePipeline,
+    // Spawn what sub-emitters queued, when there are any
+    sub_emitters: bool,
+    begin_events: wgpu::ComputePipeline,
+    spawn_events: wgpu::ComputePipeline,
+    begin_draw: wgpu::ComputePipeline,
+    begin_sort: wgpu::ComputePipeline,
+    sort: wgpu::ComputePipeline,
+    write_instances: wgpu::ComputePipeline,
+}
+
+impl GpuSimulation {
+    // Why this device can't run `config` on the GPU, if it can't
+    pub fn check_support(device: &wgpu::Device, config: &ParticleConfig) -> Result<(), String> {
+        let limits = device.limits();
+        if config.emitters.len() > MAX_EMITTERS {
+            return Err(format!("{} emitters, at most {} fit on the GPU", config.emitters.len(), MAX_EMITTERS));
+        }
+        if config.fields.len() > MAX_FIELDS {
+            return Err(format!("{} force fields, at most {} fit on the GPU", config.fields.len(), MAX_FIELDS));
+        }
+        let sub_emitters = config.emitters.iter().map(|emitter| emitter.sub_emitters.len()).sum::<usize>();
+        if sub_emitters > MAX_SUB_EMITTERS {
+            return Err(format!("{} sub-emitters, at most {} fit on the GPU", sub_emitters, MAX_SUB_EMITTERS));
+        }
+        if config.colliders.len() > MAX_COLLIDERS {
+            return Err(format!("{} colliders, at most {} fit on the GPU", config.colliders.len(), MAX_COLLIDERS));
+        }
+        if limits.max_compute_invocations_per_workgroup < WORKGROUP_SIZE || limits.max_storage_buffers_per_shader_stage < 8 {
+            return Err("no compute shader support".to_string());
+        }
+        let capacity = capacity(config) as u64;
+        let sort_size = capacity.max(1).next_power_of_two();
+        let trail_segments = config.emitters.iter().map(|emitter| emitter.trail.segments()).max().unwrap_or(0) as u64;
+        let largest = (capacity * PARTICLE_SIZE)
+            .max(capacity * std::mem::size_of::<ParticleInstance>() as u64)
+            .max(capacity * trail_segments * std::mem::size_of::<TrailSegment>() as u64)
+            .max(event_capacity(config) as u64 * EVENT_SIZE);
+        if largest > limits.max_storage_buffer_binding_size as u64 || largest > limits.max_buffer_size {
+            return Err(format!("{} particles don't fit in a storage buffer", capacity));
+        }
+        if sort_size.div_ceil(WORKGROUP_SIZE as u64) > limits.max_compute_workgroups_per_dimension as u64 {
+            return Err(format!("{} particles need too many workgroups", capacity));
+        }
+        Ok(())
+    }
+
+    // `sprite_regions` places each emitter's sprite sheet in the atlas
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, config: &ParticleConfig, mesh: &EmissionMesh, seed: u64,
+               sprite_regions: &[Option<[f32; 4]>]) -> Self {
+        let capacity = capacity(config);
+        // Zero-sized bindings aren't allowed, so every pool has room for at least one
+        let slots = capacity.max(1) as u64;
+        // The bitonic sort works on a power of two, the alive lists are padded out to it
+        let sort_size = slots.next_power_of_two();
+
+        // Pools, trail histories and sub-emitters laid out emitter after emitter. Sub-emitters
+        // naming no emitter are left out
+        let mut pool_base = 0;
+        let mut trail_base = 0;
+        let mut sub_emitters = Vec::new();
+        let emitters: Vec<GpuEmitter> = config.emitters.iter().zip(sprite_regions).map(|(emitter, &region)| {
+            let mut gpu = GpuEmitter::new(emitter, pool_base, region);
+            gpu.trail_base = trail_base;
+            gpu.sub_base = sub_emitters.len() as u32;
+            sub_emitters.extend(emitter.sub_emitters.iter().filter_map(|sub| GpuSubEmitter::new(config, sub)));
+            gpu.sub_count = sub_emitters.len() as u32 - gpu.sub_base;
+            pool_base += gpu.pool_size;
+            trail_base += gpu.pool_size * gpu.trail_points;
+            gpu
+        }).collect();
+        let event_capacity = event_capacity(config);
+        // Every particle gets room for the longest trail's segments
+        let trail_segments = config.emitters.iter().map(|emitter| emitter.trail.segments()).max().unwrap_or(0);
+
+        let globals = Globals {
+            seed: (seed ^ (seed >> 32)) as u32,
+            emitter_count: emitters.len() as u32,
+            triangle_count: mesh.triangles().len() as u32,
+            vertex_count: mesh.vertices().len() as u32,
+            // Past the triangles' corners, or the placeholder triangle standing in for them
+            vertex_offset: (mesh.triangles().len().max(1) * 3) as u32,
+            sort_size: sort_size as u32,
+            trail_segments,
+            event_capacity,
+            ..Default::default()
+        };
+        let globals_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Sim Globals"),
+            contents: bytemuck::bytes_of(&globals),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let emitter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Sim Emitters"),
+            size: (MAX_EMITTERS * std::mem::size_of::<GpuEmitter>()) as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let particle_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Pool"),
+            size: slots * PARTICLE_SIZE,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        // Count followed by the indices
+        let alive_lists = ["Particle Alive List A", "Particle Alive List B"].map(|label| device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: 4 + sort_size * 4,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        }));
+        // Every slot starts free: per-emitter counts, then the slot indices in pool order
+        let mut dead_list = vec![0u32; MAX_EMITTERS];
+        for (count, emitter) in dead_list.iter_mut().zip(&emitters) {
+            *count = emitter.pool_size;
+        }
+        dead_list.extend(0..slots as u32);
+        let dead_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Dead List"),
+            contents: bytemuck::cast_slice(&dead_list),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+        let indirect_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Indirect Args"),
+            size: SPAWN_DISPATCH_OFFSET + 3 * 4,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::INDIRECT | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Instance Buffer"),
+            size: slots * std::mem::size_of::<ParticleInstance>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        // Each trail particle's last positions, a ring of its emitter's trail points
+        let trail_history_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Trail History"),
+            size: trail_base.max(1) as u64 * 16,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+        let trail_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Trail Buffer"),
+            size: (slots * trail_segments as u64).max(1) * std::mem::size_of::<TrailSegment>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let mut sub_emitter_table = [GpuSubEmitter::default(); MAX_SUB_EMITTERS];
+        sub_emitter_table[..sub_emitters.len()].copy_from_slice(&sub_emitters);
+        let sub_emitter_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Sub-Emitters"),
+            contents: bytemuck::cast_slice(&sub_emitter_table),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        // Count (padded to the events' alignment) followed by the events
+        let event_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Events"),
+            size: 16 + event_capacity.max(1) as u64 * EVENT_SIZE,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        // Curves baked to a table per emitter: (rgb, alpha) and (size, rotation, -, -)
+        let mut appearance = Vec::with_capacity(config.emitters.len().max(1) * CURVE_SAMPLES * 2);
+        for emitter in &config.emitters {
+            let curves = &emitter.appearance;
+            for i in 0..CURVE_SAMPLES {
+                let age = i as f32 / (CURVE_SAMPLES - 1) as f32;
+                let color = curves.color.sample(age);
+                appearance.push([color.x, color.y, color.z, curves.alpha.sample(age)]);
+                appearance.push([curves.size.sample(age), curves.rotation.sample(age), 0.0, 0.0]);
+            }
+        }
+        if appearance.is_empty() {
+            appearance.push([0.0; 4]);
+        }
+        let appearance_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Appearance Curves"),
+            contents: bytemuck::cast_slice(&appearance),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // The model for emission and collisions, in one buffer to stay within the storage
+        // buffer limit. Emission triangles' corners come first, with the running area in the
+        // first corner's w
+        let mut mesh_data: Vec<[f32; 4]> = mesh.triangles().iter().zip(mesh.cumulative_area()).flat_map(|([a, b, c], &area)| {
+            [a.extend(area).to_array(), b.extend(0.0).to_array(), c.extend(0.0).to_array()]
+        }).collect();
+        if mesh_data.is_empty() {
+            mesh_data.extend([[0.0; 4]; 3]);
+        }
+        // Then the emission vertices as (position, normal), from globals.vertex_offset
+        mesh_data.extend(mesh.vertices().iter().flatten().map(|v| v.extend(0.0).to_array()));
+        // Then the BVH, if a collider needs it: nodes as (min, index) and (max, count) with the
+        // integers' bits in w, then the triangles' corners in BVH order
+        let bvh_offset = mesh_data.len() as u32;
+        let bvh = config.colliders.iter().any(|collider| collider.shape == ColliderShape::Mesh).then(|| Bvh::new(mesh.triangles()));
+        if let Some(bvh) = &bvh {
+            for node in &bvh.nodes {
+                mesh_data.push(node.min.extend(f32::from_bits(node.index)).to_array());
+                mesh_data.push(node.max.extend(f32::from_bits(node.count)).to_array());
+            }
+            mesh_data.extend(bvh.triangles.iter().flatten().map(|corner| corner.extend(0.0).to_array()));
+        }
+        let mesh_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Mesh"),
+            contents: bytemuck::cast_slice(&mesh_data),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let mut colliders = GpuColliders {
+            model: Mat4::IDENTITY.to_cols_array_2d(),
+            model_inverse: Mat4::IDENTITY.to_cols_array_2d(),
+            count: config.colliders.len() as u32,
+            bvh_nodes: bvh.map_or(0, |bvh| bvh.nodes.len() as u32),
+            bvh_offset,
+            _padding: 0,
+            colliders: [GpuCollider::default(); MAX_COLLIDERS],
+        };
+        for (gpu, collider) in colliders.colliders.iter_mut().zip(&config.colliders) {
+            *gpu = GpuCollider::new(collider);
+        }
+        let collider_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Colliders"),
+            contents: bytemuck::bytes_of(&colliders),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let mut fields = GpuFields { count: config.fields.len() as u32, _padding: [0; 3], fields: [GpuField::default(); MAX_FIELDS] };
+        for (gpu, field) in fields.fields.iter_mut().zip(&config.fields) {
+            *gpu = GpuField::new(field);
+        }
+        let field_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Force Fields"),
+            contents: bytemuck::bytes_of(&fields),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let storage = |binding: u32, read_only: bool| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Storage { read_only }, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        let uniform = |binding: u32| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+            count: None,
+        };
+        // Stepping and drawing each see only the buffers they use, to stay within the storage
+        // buffer limit
+        let step_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_step_bind_group_layout"),
+            entries: &[
+                uniform(0), uniform(1),
+                storage(2, false), storage(3, false), storage(4, false), storage(5, false),
+                storage(8, true), uniform(9), uniform(10), storage(11, false), uniform(13), storage(14, false),
+            ],
+        });
+        let draw_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_draw_bind_group_layout"),
+            entries: &[
+                uniform(0), uniform(1),
+                storage(2, false), storage(3, false), storage(6, false), storage(7, true),
+                storage(11, false), storage(12, false),
+            ],
+        });
+        let bind_group = |label: &str, layout: &wgpu::BindGroupLayout, buffers: &[(u32, &wgpu::Buffer)]| {
+            let entries: Vec<wgpu::BindGroupEntry> = buffers.iter()
+                .map(|&(binding, buffer)| wgpu::BindGroupEntry { binding, resource: buffer.as_entire_binding() })
+                .collect();
+            device.create_bind_group(&wgpu::BindGroupDescriptor { label: Some(label), layout, entries: &entries })
+        };
+        let step_bind_group = |alive_in: &wgpu::Buffer, alive_out: &wgpu::Buffer| bind_group("particle_step_bind_group", &step_layout, &[
+            (0, &globals_buffer), (1, &emitter_buffer), (2, &particle_buffer), (3, alive_in), (4, alive_out), (5, &dead_buffer),
+            (8, &mesh_buffer), (9, &collider_buffer), (10, &field_buffer), (11, &trail_history_buffer),
+            (13, &sub_emitter_buffer), (14, &event_buffer),
+        ]);
+        let draw_bind_group = |alive_in: &wgpu::Buffer| bind_group("particle_draw_bind_group", &draw_layout, &[
+            (0, &globals_buffer), (1, &emitter_buffer), (2, &particle_buffer), (3, alive_in), (6, &instance_buffer),
+            (7, &appearance_buffer), (11, &trail_history_buffer), (12, &trail_buffer),
+        ]);
+        let step_bind_groups = [step_bind_group(&alive_lists[0], &alive_lists[1]), step_bind_group(&alive_lists[1], &alive_lists[0])];
+        let draw_bind_groups = [draw_bind_group(&alive_lists[0]), draw_bind_group(&alive_lists[1])];
+
+        // A buffer can't be storage and indirect args in the same dispatch, so it gets its own group
+        let indirect_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_indirect_bind_group_layout"),
+            entries: &[storage(0, false)],
+        });
+        let indirect_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle_indirect_bind_group"),
+            layout: &indirect_layout,
+            entries: &[wgpu::BindGroupEntry { binding: 0, resource: indirect_buffer.as_entire_binding() }],
+        });
+
+        // Every (block, distance) pass of a bitonic sort over `sort_size` entries, each at an
+        // offset a uniform binding can start from
+        let mut sort_steps = Vec::new();
+        let mut block = 2;
+        while block <= sort_size as u32 {
+            let mut distance = block / 2;
+            while distance > 0 {
+                sort_steps.push(SortStep { block, distance });
+                distance /= 2;
+            }
+            block *= 2;
+        }
+        let sort_step_stride = device.limits().min_uniform_buffer_offset_alignment.max(std::mem::size_of::<SortStep>() as u32);
+        let mut sort_step_bytes = vec![0u8; sort_steps.len().max(1) * sort_step_stride as usize];
+        for (i, step) in sort_steps.iter().enumerate() {
+            let offset = i * sort_step_stride as usize;
+            sort_step_bytes[offset..offset + std::mem::size_of::<SortStep>()].copy_from_slice(bytemuck::bytes_of(step));
+        }
+        let sort_step_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Sort Steps"),
+            contents: &sort_step_bytes,
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let sort_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("particle_sort_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::COMPUTE,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: wgpu::BufferSize::new(std::mem::size_of::<SortStep>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let sort_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("particle_sort_bind_group"),
+            layout: &sort_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &sort_step_buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<SortStep>() as u64),
+                }),
+            }],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Particle Simulation Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/particle_sim.wgsl").into()),
+        });
+        let pipeline_layout = |label: &str, bind_group_layouts: &[&wgpu::BindGroupLayout]| {
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor { label: Some(label), bind_group_layouts, immediate_size: 0 })
+        };
+        let step_pipeline_layout = pipeline_layout("Particle Step Pipeline Layout", &[&step_layout]);
+        let step_indirect_pipeline_layout = pipeline_layout("Particle Step Indirect Pipeline Layout", &[&step_layout, &indirect_layout]);
+        let draw_pipeline_layout = pipeline_layout("Particle Draw Pipeline Layout", &[&draw_layout]);
+        let draw_indirect_pipeline_layout = pipeline_layout("Particle Draw Indirect Pipeline Layout", &[&draw_layout, &indirect_layout]);
+        let sort_pipeline_layout = pipeline_layout("Particle Sort Pipeline Layout", &[&draw_layout, &sort_layout]);
+        let pipeline = |entry_point: &str, layout: &wgpu::PipelineLayout| device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some(entry_point),
+            layout: Some(layout),
+            module: &shader,
+            entry_point: Some(entry_point),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+
+        Self {
+            device: device.clone(),
+            queue: queue.clone(),
+            configs: config.emitters.clone(),
+            schedules: config.emitters.iter().map(SpawnSchedule::new).collect(),
+            emitters,
+            globals,
+            globals_buffer,
+            emitter_buffer,
+            model_transform: Mat4::IDENTITY,
+            collider_buffer,
+            step_bind_groups,
+            draw_bind_groups,
+            current: 0,
+            indirect_bind_group,
+            sort_bind_group,
+            sort_steps: sort_steps.len() as u32,
+            sort_step_stride,
+            sorted: config.sorts(),
+            instance_buffer,
+            trail_buffer,
+            indirect_buffer,
+            begin_step: pipeline("begin_step", &step_indirect_pipeline_layout),
+            simulate: pipeline("simulate", &step_pipeline_layout),
+            emit: pipeline("emit", &step_pipeline_layout),
+            sub_emitters: !sub_emitters.is_empty(),
+            begin_events: pipeline("begin_events", &step_indirect_pipeline_layout),
+            spawn_events: pipeline("spawn_events", &step_pipeline_layout),
+            begin_draw: pipeline("begin_draw", &draw_indirect_pipeline_layout),
+            begin_sort: pipeline("begin_sort", &draw_pipeline_layout),
+            sort: pipeline("sort", &sort_pipeline_layout),
+            write_instances: pipeline("write_instances", &draw_pipeline_layout),
+        }
+    }
+
+    // One step: age and move the live particles, then spawn what's due. Submitted right away
+    // since every step needs its own globals
+    pub fn update(&mut self, dt: f32) {
+        self.globals.dt = dt;
+        self.globals.time += dt;
+        self.globals.step += 1;
+
+        let mut spawn_total = 0;
+        for ((emitter, config), schedule) in self.emitters.iter_mut().zip(&self.configs).zip(&mut self.schedules) {
+            // More than the pool holds would only fail to find free slots
+            emitter.spawn_count = schedule.due(config, dt, self.globals.time).min(emitter.pool_size as usize) as u32;
+            emitter.spawn_offset = spawn_total;
+            spawn_total += emitter.spawn_count;
+        }
+        self.globals.spawn_total = spawn_total;
+        self.queue.write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&self.globals));
+        if !self.emitters.is_empty() {
+            self.queue.write_buffer(&self.emitter_buffer, 0, bytemuck::cast_slice(&self.emitters));
+        }
+        let model = [self.model_transform.to_cols_array_2d(), self.model_transform.inverse().to_cols_array_2d()];
+        self.queue.write_buffer(&self.collider_buffer, 0, bytemuck::cast_slice(&model));
+
+        let mut encoder = self.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Particle Step") });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Particle Step Pass"), timestamp_writes: None });
+            pass.set_bind_group(0, &self.step_bind_groups[self.current], &[]);
+            pass.set_bind_group(1, &self.indirect_bind_group, &[]);
+            pass.set_pipeline(&self.begin_step);
+            pass.dispatch_workgroups(1, 1, 1);
+            pass.set_pipeline(&self.simulate);
+            pass.dispatch_workgroups_indirect(&self.indirect_buffer, 0);
+            if spawn_total > 0 {
+                pass.set_pipeline(&self.emit);
+                pass.dispatch_workgroups(spawn_total.div_ceil(WORKGROUP_SIZE), 1, 1);
+            }
+            if self.sub_emitters {
+                pass.set_pipeline(&self.begin_events);
+                pass.dispatch_workgroups(1, 1, 1);
+                pass.set_pipeline(&self.spawn_events);
+                pass.dispatch_workgroups_indirect(&self.indirect_buffer, SPAWN_DISPATCH_OFFSET);
+            }
+        }
+        self.queue.submit([encoder.finish()]);
+        self.current = 1 - self.current;
+    }
+
+    // Where the model is now, relative to the pose its mesh was captured in
+    pub fn set_model_transform(&mut self, transform: Mat4) {
+        self.model_transform = transform;
+    }
+
+    // Fill `instance_buffer`, `trail_buffer` and the draw arguments for the current particles,
+    // sorted back to front for `view` when the emitters need it
+    pub fn prepare_draw(&mut self, encoder: &mut wgpu::CommandEncoder, interpolation: f32, view: Mat4) {
+        self.globals.interpolation = interpolation;
+        // View-space depth is this row of the view matrix dotted with the position
+        self.globals.view_depth = view.row(2).to_array();
+        self.queue.write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&self.globals));
+
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("Particle Instance Pass"), timestamp_writes: None });
+        pass.set_bind_group(0, &self.draw_bind_groups[self.current], &[]);
+        pass.set_bind_group(1, &self.indirect_bind_group, &[]);
+        pass.set_pipeline(&self.begin_draw);
+        pass.dispatch_workgroups(1, 1, 1);
+        if self.sorted {
+            // Sorts the current alive list in place, the simulation doesn't mind its order
+            let workgroups = self.globals.sort_size.div_ceil(WORKGROUP_SIZE);
+            pass.set_pipeline(&self.begin_sort);
+            pass.dispatch_workgroups(workgroups, 1, 1);
+            pass.set_pipeline(&self.sort);
+            for step in 0..self.sort_steps {
+                pass.set_bind_group(1, &self.sort_bind_group, &[step * self.sort_step_stride]);
+                pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+        }
+        pass.set_pipeline(&self.write_instances);
+        pass.dispatch_workgroups_indirect(&self.indirect_buffer, 0);
+    }
+
+    pub fn draw_args_offset(&self) -> u64 {
+        DRAW_ARGS_OFFSET
+    }
+
+    pub fn trail_draw_args_offset(&self) -> u64 {
+        TRAIL_DRAW_ARGS_OFFSET
+    }
+
+    // Live particle count, read back from the GPU (blocks). begin_draw copies it into the draw
+    // arguments' instance count, which is easier to reach than the alive list it comes from
+    #[cfg(test)]
+    pub fn alive_count(&self) -> u32 {
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        {
+            let mut pass = encoder.begin_compute_pass(&Default::default());
+            pass.set_bind_group(0, &self.draw_bind_groups[self.current], &[]);
+            pass.set_bind_group(1, &self.indirect_bind_group, &[]);
+            pass.set_pipeline(&self.begin_draw);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+        self.queue.submit([encoder.finish()]);
+        let args = self.read_back(&self.indirect_buffer, DRAW_ARGS_OFFSET + 8);
+        bytemuck::pod_read_unaligned(&args[DRAW_ARGS_OFFSET as usize + 4..])
+    }
+
+    // The first `count` instances of the last prepare_draw (blocks)
+    #[cfg(test)]
+    pub fn instances(&self, count: u32) -> Vec<ParticleInstance> {
+        let size = std::mem::size_of::<ParticleInstance>();
+        let bytes = self.read_back(&self.instance_buffer, count as u64 * size as u64);
+        bytes.chunks_exact(size).map(bytemuck::pod_read_unaligned).collect()
+    }
+
+    // The first `count` trail segments of the last prepare_draw (blocks)
+    #[cfg(test)]
+    pub fn trail_segments(&self, count: u32) -> Vec<TrailSegment> {
+        let size = std::mem::size_of::<TrailSegment>();
+        let bytes = self.read_back(&self.trail_buffer, count as u64 * size as u64);
+        bytes.chunks_exact(size).map(bytemuck::pod_read_unaligned).collect()
+    }
+
+    #[cfg(test)]
+    fn read_back(&self, buffer: &wgpu::Buffer, size: u64) -> Vec<u8> {
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Readback"),
+            size,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+        self.queue.submit([encoder.finish()]);
+        staging.map_async(wgpu::MapMode::Read, .., |result| result.expect("failed to map particle buffer"));
+        self.device.poll(wgpu::PollType::wait_indefinitely()).expect("failed to wait for the GPU");
+        let bytes = staging.get_mapped_range(..).to_vec();
+        staging.unmap();
+        bytes
+    }
+}
+
+// Slots in the pool, every emitter at its cap
+fn capacity(config: &ParticleConfig) -> u32 {
+    config.emitters.iter().map(|emitter| emitter.max_particles as u32).sum()
+}
+
+// Events a step can queue: each sub-emitter fires at most once per particle of its emitter
+fn event_capacity(config: &ParticleConfig) -> u32 {
+    config.emitters.iter().map(|emitter| (emitter.max_particles * emitter.sub_emitters.len()) as u32).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{Curve, Interpolation};
+    use crate::emitter::{BlendMode, Burst, ForceConfig, SpriteConfig, SubEmitterEvent, TrailConfig, VelocityConfig};
+    use crate::headless;
+
+    fn simulation(config: &ParticleConfig) -> GpuSimulation {
+        simulation_on(config, &EmissionMesh::default())
+    }
+
+    // Fails rather than skips without compute shaders, so a passing run means the tests ran
+    fn simulation_on(config: &ParticleConfig, mesh: &EmissionMesh) -> GpuSimulation {
+        let (device, queue) = pollster::block_on(headless::request_device());
+        if let Err(reason) = GpuSimulation::check_support(&device, config) {
+            panic!("GPU simulation unsupported: {}", reason);
+        }
+        let regions = vec![None; config.emitters.len()];
+        GpuSimulation::new(&device, &queue, config, mesh, 1, &regions)
+    }
+
+    #[test]
+    fn spawns_what_the_schedule_asks_for() {
+        let config = ParticleConfig::default();
+        let mut gpu = simulation(&config);
+        // 900/s for half a second, and nothing lives less than 1.2s
+        for _ in 0..30 {
+            gpu.update(1.0 / 60.0);
+        }
+        assert_eq!(gpu.alive_count(), 450);
+    }
+
+    #[test]
+    fn every_particle_starts_at_the_beginning_of_its_curves() {
+        // Fading out linearly over lives anywhere from one to two seconds
+        let mut emitter = EmitterConfig { rate: 0.0, bursts: vec![Burst { time: 0.0, count: 50, interval: None }], lifetime: [1.0, 2.0], ..Default::default() };
+        emitter.appearance.alpha = Curve::new(Interpolation::Linear, vec![(0.0, 1.0), (1.0, 0.0)]);
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut gpu = simulation(&config);
+        gpu.update(0.1);
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        let instances = gpu.instances(gpu.alive_count());
+        assert_eq!(instances.len(), 50);
+        // A particle of the shortest life would be halfway faded if age ran over the longest
+        assert!(instances.iter().all(|instance| instance.color[3] > 0.85), "{:?}", instances.iter().map(|instance| instance.color[3]).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn pools_cap_their_emitter_and_recycle_dead_slots() {
+        let emitter = EmitterConfig { max_particles: 100, lifetime: [0.2, 0.2], ..Default::default() };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut gpu = simulation(&config);
+        // 15 spawn per step and each lives 12 steps; the cap of 100 is hit on step 7
+        for _ in 0..7 {
+            gpu.update(1.0 / 60.0);
+        }
+        assert_eq!(gpu.alive_count(), 100);
+        // Long after the first wave died, new particles are still being spawned into freed slots
+        for _ in 0..60 {
+            gpu.update(1.0 / 60.0);
+        }
+        assert_eq!(gpu.alive_count(), 100);
+    }
+
+    #[test]
+    fn sorts_alpha_blended_particles_back_to_front() {
+        let smoke = EmitterConfig {
+            shape: EmitterShape::Box { center: [0.0; 3], half_extents: [1.0; 3] },
+            sprite: SpriteConfig { blend: BlendMode::Alpha, ..Default::default() },
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![smoke, EmitterConfig::default()], ..Default::default() };
+        let mut gpu = simulation(&config);
+        for _ in 0..20 {
+            gpu.update(1.0 / 60.0);
+        }
+        let view = Mat4::look_at_rh(glam::Vec3::new(0.0, 0.0, 5.0), glam::Vec3::ZERO, glam::Vec3::Y);
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, view);
+        gpu.queue.submit([encoder.finish()]);
+
+        let count = gpu.alive_count();
+        assert!(count > 100);
+        let depths: Vec<f32> = gpu.instances(count).iter().map(|instance| instance.position[2]).collect();
+        assert!(depths.windows(2).all(|pair| pair[0] <= pair[1]), "not back to front: {:?}", depths);
+    }
+
+    #[test]
+    fn particles_land_on_colliders() {
+        let falling = EmitterConfig {
+            shape: EmitterShape::Box { center: [0.0, 20.0, 0.0], half_extents: [10.0, 1.0, 10.0] },
+            forces: ForceConfig { acceleration: [0.0, -500.0, 0.0], ..Default::default() },
+            lifetime: [5.0, 5.0],
+            ..Default::default()
+        };
+        let ground = ColliderConfig { shape: ColliderShape::Plane { point: [0.0; 3], normal: [0.0, 1.0, 0.0] }, bounce: 0.0, ..Default::default() };
+        let config = ParticleConfig { emitters: vec![falling], colliders: vec![ground], ..Default::default() };
+        let mut gpu = simulation(&config);
+        for _ in 0..60 {
+            gpu.update(1.0 / 60.0);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        let count = gpu.alive_count();
+        assert!(count > 100);
+        // Nothing fell through, and the first to spawn have come to rest on the ground
+        let heights: Vec<f32> = gpu.instances(count).iter().map(|instance| instance.position[1]).collect();
+        assert!(heights.iter().all(|&y| y >= 0.0), "below the ground: {:?}", heights);
+        assert!(heights.iter().any(|&y| y < 0.1));
+    }
+
+    #[test]
+    fn force_fields_push_particles() {
+        // Particles spawned at rest, with none of the emitter's own forces
+        let still = EmitterConfig {
+            shape: EmitterShape::Point { position: [0.0; 3] },
+            velocity: VelocityConfig { speed: [0.0, 0.0], radial: [0.0, 0.0], ..Default::default() },
+            forces: ForceConfig::none(),
+            ..Default::default()
+        };
+        let away = FieldConfig { force: FieldForce::Repulsor { position: [-10.0, 0.0, 0.0], strength: 100.0 }, ..Default::default() };
+        let config = ParticleConfig { emitters: vec![still], fields: vec![away], ..Default::default() };
+        let mut gpu = simulation(&config);
+        for _ in 0..10 {
+            gpu.update(1.0 / 60.0);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        let count = gpu.alive_count();
+        assert!(count > 100);
+        // Pushed straight along +x, all but the last two steps' 15 each: forces act after a
+        // particle moves, so they haven't moved yet
+        let moved = gpu.instances(count).iter().filter(|instance| instance.position[0] > 0.0).count() as u32;
+        assert_eq!(moved, count - 30);
+        assert!(gpu.instances(count).iter().all(|instance| instance.position[1] == 0.0 && instance.position[2] == 0.0));
+    }
+
+    #[test]
+    fn trails_follow_each_particle_back_along_its_path() {
+        // Particles moving along +x one unit a step, with none of the emitter's own forces
+        let streaks = EmitterConfig {
+            shape: EmitterShape::Point { position: [0.0; 3] },
+            velocity: VelocityConfig { direction: [1.0, 0.0, 0.0], speed: [60.0, 60.0], radial: [0.0, 0.0] },
+            forces: ForceConfig::none(),
+            trail: TrailConfig { points: 4, width: 0.5 },
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![streaks], ..Default::default() };
+        let mut gpu = simulation(&config);
+        for _ in 0..10 {
+            gpu.update(1.0 / 60.0);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        // Up to three segments a particle, each a step long: the last three steps' 15 each have
+        // remembered one, two and three positions, and their unused segments are left empty
+        let count = gpu.alive_count();
+        assert_eq!(count, 150);
+        let segments = gpu.trail_segments(count * 3);
+        let drawn: Vec<&TrailSegment> = segments.iter().filter(|segment| segment.start != segment.end).collect();
+        assert_eq!(drawn.len(), 15 + 15 * 2 + 105 * 3);
+        assert!(drawn.iter().all(|segment| (segment.start[0] - segment.end[0] - 1.0).abs() < 1e-3), "not a step long: {:?}", drawn);
+    }
+
+    #[test]
+    fn sub_emitters_spawn_where_particles_die() {
+        // Ten particles heading along +x at 100 units a second for a quarter of a second, each
+        // dying into two puffs that keep half its velocity
+        let sparks = EmitterConfig {
+            name: "sparks".to_string(),
+            shape: EmitterShape::Point { position: [0.0; 3] },
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 10, interval: None }],
+            lifetime: [0.25, 0.25],
+            velocity: VelocityConfig { direction: [1.0, 0.0, 0.0], speed: [100.0, 100.0], radial: [0.0, 0.0] },
+            forces: ForceConfig::none(),
+            sub_emitters: vec![SubEmitterConfig { event: SubEmitterEvent::Death, emitter: "puffs".to_string(), count: 2, inherit_velocity: 0.5 }],
+            ..Default::default()
+        };
+        let puffs = EmitterConfig {
+            name: "puffs".to_string(),
+            rate: 0.0,
+            lifetime: [10.0, 10.0],
+            velocity: VelocityConfig { speed: [0.0, 0.0], radial: [0.0, 0.0], ..Default::default() },
+            forces: ForceConfig::none(),
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![sparks, puffs], ..Default::default() };
+        let mut gpu = simulation(&config);
+        // The sparks die on the fourth step, at x = 20, and the puffs move on the fifth
+        for _ in 0..5 {
+            gpu.update(0.1);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        let count = gpu.alive_count();
+        assert_eq!(count, 20);
+        let positions: Vec<[f32; 3]> = gpu.instances(count).iter().map(|instance| instance.position).collect();
+        assert!(positions.iter().all(|&position| (glam::Vec3::from(position) - glam::Vec3::new(25.0, 0.0, 0.0)).length() < 1e-3),
+                "not where they should be: {:?}", positions);
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_particles() {
+        // Sparks of random speeds and lives, each dying into puffs, so the events' spawns (queued
+        // in whatever order the threads get there) have to replay too
+        let sparks = EmitterConfig {
+            name: "sparks".to_string(),
+            lifetime: [0.1, 0.4],
+            sub_emitters: vec![SubEmitterConfig { event: SubEmitterEvent::Death, emitter: "puffs".to_string(), count: 3, inherit_velocity: 0.5 }],
+            ..Default::default()
+        };
+        let puffs = EmitterConfig { name: "puffs".to_string(), rate: 0.0, max_particles: 10_000, ..Default::default() };
+        let config = ParticleConfig { emitters: vec![sparks, puffs], ..Default::default() };
+        // Every particle's instance, in an order that doesn't depend on which slot it took
+        let run = || {
+            let mut gpu = simulation(&config);
+            for _ in 0..30 {
+                gpu.update(1.0 / 60.0);
+            }
+            let mut encoder = gpu.device.create_command_encoder(&Default::default());
+            gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+            gpu.queue.submit([encoder.finish()]);
+            let mut instances: Vec<Vec<u32>> = gpu.instances(gpu.alive_count()).iter().map(|instance| bytemuck::cast_slice(&[*instance]).to_vec()).collect();
+            instances.sort();
+            instances
+        };
+        let first = run();
+        assert!(first.len() > 450, "no puffs spawned: {}", first.len());
+        assert_eq!(first, run());
+    }
+
+    #[test]
+    fn particles_spin_and_stretch_along_their_motion() {
+        let emitter = EmitterConfig {
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 4, interval: None }],
+            lifetime: [10.0, 10.0],
+            rotation: [1.0, 1.0],
+            angular_velocity: [2.0, 2.0],
+            orientation: Orientation::VelocityStretch { stretch: 0.1 },
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut gpu = simulation(&config);
+        for _ in 0..6 {
+            gpu.update(0.1);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        // Born on the first step and turned on the five after; rising, so stretched upward
+        let instances = gpu.instances(gpu.alive_count());
+        assert_eq!(instances.len(), 4);
+        for instance in instances {
+            assert!((instance.rotation - 2.0).abs() < 1e-5, "rotation {}", instance.rotation);
+            assert_eq!(instance.orientation, 1.0);
+            assert!(instance.axis[1] > 0.0, "axis {:?}", instance.axis);
+        }
+    }
+
+    #[test]
+    fn mesh_emitters_follow_the_model_out_along_its_normals() {
+        // Launched straight off the cube's vertices, whose normals face along Z
+        let emitter = EmitterConfig {
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 20, interval: None }],
+            lifetime: [10.0, 10.0],
+            shape: EmitterShape::MeshVertex,
+            velocity: VelocityConfig { speed: [0.0, 0.0], radial: [10.0, 10.0], ..Default::default() },
+            forces: ForceConfig::none(),
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mesh = EmissionMesh::new(&crate::model_loader::ModelLoader::fallback_cube(), Mat4::IDENTITY);
+        let mut gpu = simulation_on(&config, &mesh);
+        // Turned a quarter about Y, so those normals now face along X, and moved off to the side
+        gpu.set_model_transform(Mat4::from_translation(glam::Vec3::new(100.0, 0.0, 0.0)) * Mat4::from_rotation_y(std::f32::consts::FRAC_PI_2));
+        for _ in 0..3 {
+            gpu.update(0.1);
+        }
+        let mut encoder = gpu.device.create_command_encoder(&Default::default());
+        gpu.prepare_draw(&mut encoder, 1.0, Mat4::IDENTITY);
+        gpu.queue.submit([encoder.finish()]);
+
+        let instances = gpu.instances(gpu.alive_count());
+        assert_eq!(instances.len(), 20);
+        for instance in instances {
+            let [x, y, z] = instance.position;
+            assert!((y.abs() - 1.0).abs() < 1e-3 && (z.abs() - 1.0).abs() < 1e-3, "off the cube's corners: {:?}", instance.position);
+            assert!((x - 100.0).abs() > 1.5, "didn't move out along the normal: {:?}", instance.position);
+        }
+    }
+}
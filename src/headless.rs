@@ -0,0 +1,158 @@
+// Offscreen rendering for machines without a display (CI, servers): frames are drawn into
+// a texture, read back and written out as numbered PNGs
+use crate::camera::{Camera, Projection};
+use crate::emitter::ParticleConfig;
+use crate::model::{ModelData, ModelFit};
+use crate::renderer::Renderer;
+use crate::timestep::{FixedTimestep, SIMULATION_STEP};
+use std::path::PathBuf;
+
+// The offscreen target is sRGB like the window's view, so PNGs match what's on screen
+pub const COLOR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+#[derive(Clone, Debug)]
+pub struct HeadlessOptions {
+    pub frames: u32,
+    pub width: u32,
+    pub height: u32,
+    // Output frames per simulated second
+    pub fps: f32,
+    pub output_dir: PathBuf,
+}
+
+impl Default for HeadlessOptions {
+    fn default() -> Self {
+        Self {
+            frames: 60,
+            width: 800,
+            height: 600,
+            fps: 60.0,
+            output_dir: PathBuf::from("frames"),
+        }
+    }
+}
+
+// Prefer a real GPU, but fall back to the software adapter when there isn't one
+pub async fn request_device() -> (wgpu::Device, wgpu::Queue) {
+    let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+        backends: wgpu::Backends::all(),
+        ..Default::default()
+    });
+    let adapter = match instance.request_adapter(&wgpu::RequestAdapterOptions::default()).await {
+        Ok(adapter) => adapter,
+        Err(_) => {
+            println!("⚠️ No GPU adapter found, falling back to software rendering");
+            instance
+                .request_adapter(&wgpu::RequestAdapterOptions { force_fallback_adapter: true, ..Default::default() })
+                .await
+                .expect("no software adapter available")
+        }
+    };
+    println!("🖥️ Rendering offscreen on {} ({:?})", adapter.get_info().name, adapter.get_info().backend);
+    adapter.request_device(&wgpu::DeviceDescriptor::default()).await.unwrap()
+}
+
+// Color texture the frames are drawn into, plus the buffer they're copied back through
+pub struct OffscreenTarget {
+    texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    readback_buffer: wgpu::Buffer,
+    // Rows are padded to wgpu's copy alignment in the readback buffer
+    padded_bytes_per_row: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl OffscreenTarget {
+    pub fn new(device: &wgpu::Device, width: u32, height: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Offscreen Color Texture"),
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: COLOR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let padded_bytes_per_row = (width * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Offscreen Readback Buffer"),
+            size: (padded_bytes_per_row * height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self { texture, view, readback_buffer, padded_bytes_per_row, width, height }
+    }
+
+    // Copy the last rendered frame back to the CPU (blocks until the GPU is done)
+    pub fn read(&self, device: &wgpu::Device, queue: &wgpu::Queue) -> image::RgbaImage {
+        let mut encoder = device.create_command_encoder(&Default::default());
+        encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_bytes_per_row),
+                    rows_per_image: Some(self.height),
+                },
+            },
+            wgpu::Extent3d { width: self.width, height: self.height, depth_or_array_layers: 1 },
+        );
+        queue.submit([encoder.finish()]);
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.readback_buffer.map_async(wgpu::MapMode::Read, .., move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(wgpu::PollType::wait_indefinitely()).expect("failed to wait for the GPU");
+        receiver.recv().expect("readback callback dropped").expect("failed to map readback buffer");
+
+        // Strip the row padding
+        let row_bytes = (self.width * 4) as usize;
+        let mut pixels = Vec::with_capacity(row_bytes * self.height as usize);
+        {
+            let data = self.readback_buffer.get_mapped_range(..);
+            for row in data.chunks_exact(self.padded_bytes_per_row as usize) {
+                pixels.extend_from_slice(&row[..row_bytes]);
+            }
+        }
+        self.readback_buffer.unmap();
+
+        image::RgbaImage::from_raw(self.width, self.height, pixels).expect("readback size matches the image")
+    }
+}
+
+// Render `options.frames` frames, 1/fps simulated seconds apart, and save them as frame_NNNN.png
+pub fn run(model: &ModelData, model_fit: ModelFit, particles: &ParticleConfig, options: &HeadlessOptions, projection: Projection,
+           particle_seed: u64) -> Result<(), Box<dyn std::error::Error>> {
+    let (device, queue) = pollster::block_on(request_device());
+    let mut renderer = Renderer::new(device, queue, COLOR_FORMAT, model, model_fit, particles, particle_seed);
+    renderer.camera = Camera::new(projection);
+    let target = OffscreenTarget::new(&renderer.device, options.width, options.height);
+    std::fs::create_dir_all(&options.output_dir)?;
+
+    // Same fixed step as the window, so frames match it whatever the output rate
+    let mut timestep = FixedTimestep::new(SIMULATION_STEP);
+    timestep.max_frame_time = f32::INFINITY;
+    let frame_time = 1.0 / options.fps;
+    for frame in 0..options.frames {
+        for _ in 0..timestep.advance(frame_time) {
+            renderer.update(timestep.step);
+        }
+        renderer.render(&target.view, (target.width, target.height), timestep.alpha());
+        let path = options.output_dir.join(format!("frame_{:04}.png", frame));
+        target.read(&renderer.device, &renderer.queue).save(&path)?;
+    }
+    println!("📸 Wrote {} frames to {}", options.frames, options.output_dir.display());
+    Ok(())
+}
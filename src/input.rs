@@ -1,9 +1,51 @@
 use winit::{
-    event::WindowEvent,
+    dpi::PhysicalPosition,
+    event::{ElementState, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::ActiveEventLoop,
+    keyboard::{KeyCode, ModifiersState, PhysicalKey},
 };
 
-pub struct InputHandler;
+// Camera radians turned per pixel dragged, and per arrow key press
+const ORBIT_PER_PIXEL: f32 = 0.005;
+const ORBIT_PER_KEY: f32 = 0.08;
+// Fraction of the camera's distance panned per pixel dragged, and per Shift+arrow press
+const PAN_PER_PIXEL: f32 = 0.0015;
+const PAN_PER_KEY: f32 = 0.05;
+// Distance or field of view kept per wheel notch or `-` press
+const DOLLY_PER_STEP: f32 = 0.9;
+const ZOOM_PER_STEP: f32 = 0.95;
+// Touchpads scroll in pixels; about this many make a wheel notch
+const PIXELS_PER_LINE: f32 = 40.0;
+
+// Simulation controls the app applies to its clock, and camera moves
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum InputAction {
+    TogglePause,
+    // Advance one step while paused
+    Step,
+    // Multiply the time scale by this factor
+    ScaleTime(f32),
+    ResetTimeScale,
+    // Turn the camera about its target by (yaw, pitch) radians
+    Orbit(f32, f32),
+    // Slide the camera across the view by (right, up) fractions of its distance
+    Pan(f32, f32),
+    // Multiply the camera's distance from its target
+    Dolly(f32),
+    // Multiply the field of view
+    Zoom(f32),
+    FrameModel,
+    ResetView,
+}
+
+// Keeps what camera drags need between events: held modifiers, and the button being dragged
+// with and where the cursor last was
+#[derive(Default)]
+pub struct InputHandler {
+    modifiers: ModifiersState,
+    dragging: Option<MouseButton>,
+    cursor: Option<PhysicalPosition<f64>>,
+}
 
 impl InputHandler {
     pub fn handle_window_event(
@@ -35,4 +77,188 @@ impl InputHandler {
             _ => false,
         }
     }
-}
\ No newline at end of file
+
+    pub fn action(&mut self, event: &WindowEvent) -> Option<InputAction> {
+        match event {
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = modifiers.state();
+                None
+            }
+            WindowEvent::KeyboardInput { event, .. } if event.state.is_pressed() => self.key_action(event.physical_key, event.repeat),
+            WindowEvent::MouseInput { state, button, .. } => {
+                match state {
+                    ElementState::Pressed if self.dragging.is_none() => self.dragging = Some(*button),
+                    ElementState::Released if self.dragging == Some(*button) => self.dragging = None,
+                    _ => {}
+                }
+                None
+            }
+            WindowEvent::CursorMoved { position, .. } => self.drag_action(*position),
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor = None;
+                None
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let notches = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => position.y as f32 / PIXELS_PER_LINE,
+                };
+                // Scrolling up moves in
+                Some(self.dolly_or_zoom(notches))
+            }
+            _ => None,
+        }
+    }
+
+    // Space pauses, `.` single-steps, `[` / `]` halve / double the speed and Backspace resets it.
+    // Arrows orbit the camera (pan with Shift), `=` / `-` dolly it in and out (zoom with Ctrl),
+    // F frames the model and R resets the view
+    fn key_action(&self, key: PhysicalKey, repeat: bool) -> Option<InputAction> {
+        match key {
+            PhysicalKey::Code(KeyCode::Space) if !repeat => Some(InputAction::TogglePause),
+            // Holding the key keeps stepping
+            PhysicalKey::Code(KeyCode::Period) => Some(InputAction::Step),
+            PhysicalKey::Code(KeyCode::BracketLeft) => Some(InputAction::ScaleTime(0.5)),
+            PhysicalKey::Code(KeyCode::BracketRight) => Some(InputAction::ScaleTime(2.0)),
+            PhysicalKey::Code(KeyCode::Backspace) => Some(InputAction::ResetTimeScale),
+            PhysicalKey::Code(KeyCode::ArrowLeft) => Some(self.arrow_action(-1.0, 0.0)),
+            PhysicalKey::Code(KeyCode::ArrowRight) => Some(self.arrow_action(1.0, 0.0)),
+            PhysicalKey::Code(KeyCode::ArrowUp) => Some(self.arrow_action(0.0, -1.0)),
+            PhysicalKey::Code(KeyCode::ArrowDown) => Some(self.arrow_action(0.0, 1.0)),
+            PhysicalKey::Code(KeyCode::Equal) => Some(self.dolly_or_zoom(1.0)),
+            PhysicalKey::Code(KeyCode::Minus) => Some(self.dolly_or_zoom(-1.0)),
+            PhysicalKey::Code(KeyCode::KeyF) if !repeat => Some(InputAction::FrameModel),
+            PhysicalKey::Code(KeyCode::KeyR) if !repeat => Some(InputAction::ResetView),
+            _ => None,
+        }
+    }
+
+    // Left drag orbits; right or middle drag, or Shift with the left, pans
+    fn drag_action(&mut self, position: PhysicalPosition<f64>) -> Option<InputAction> {
+        let last = self.cursor.replace(position)?;
+        let button = self.dragging?;
+        let (dx, dy) = ((position.x - last.x) as f32, (position.y - last.y) as f32);
+        if button == MouseButton::Left && !self.modifiers.shift_key() {
+            // The model follows the cursor round
+            Some(InputAction::Orbit(-dx * ORBIT_PER_PIXEL, dy * ORBIT_PER_PIXEL))
+        } else {
+            // And is dragged across the view with it
+            Some(InputAction::Pan(-dx * PAN_PER_PIXEL, dy * PAN_PER_PIXEL))
+        }
+    }
+
+    // An arrow moves the view as a drag the same way would, (x, y) in screen directions
+    fn arrow_action(&self, x: f32, y: f32) -> InputAction {
+        if self.modifiers.shift_key() {
+            InputAction::Pan(-x * PAN_PER_KEY, y * PAN_PER_KEY)
+        } else {
+            InputAction::Orbit(-x * ORBIT_PER_KEY, y * ORBIT_PER_KEY)
+        }
+    }
+
+    // `notches` toward the model (negative for away)
+    fn dolly_or_zoom(&self, notches: f32) -> InputAction {
+        if self.modifiers.control_key() {
+            InputAction::Zoom(ZOOM_PER_STEP.powf(notches))
+        } else {
+            InputAction::Dolly(DOLLY_PER_STEP.powf(notches))
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::event::{DeviceId, TouchPhase};
+
+    fn mouse(state: ElementState, button: MouseButton) -> WindowEvent {
+        WindowEvent::MouseInput { device_id: DeviceId::dummy(), state, button }
+    }
+
+    fn moved(x: f64, y: f64) -> WindowEvent {
+        WindowEvent::CursorMoved { device_id: DeviceId::dummy(), position: PhysicalPosition::new(x, y) }
+    }
+
+    fn wheel(delta: MouseScrollDelta) -> WindowEvent {
+        WindowEvent::MouseWheel { device_id: DeviceId::dummy(), delta, phase: TouchPhase::Moved }
+    }
+
+    fn holding(modifiers: ModifiersState) -> WindowEvent {
+        WindowEvent::ModifiersChanged(modifiers.into())
+    }
+
+    fn key(input: &InputHandler, code: KeyCode) -> Option<InputAction> {
+        input.key_action(PhysicalKey::Code(code), false)
+    }
+
+    #[test]
+    fn a_drag_moves_from_where_the_cursor_was_while_its_button_is_held() {
+        let mut input = InputHandler::default();
+        assert_eq!(input.action(&mouse(ElementState::Pressed, MouseButton::Left)), None);
+        // The first move only tells us where the cursor is, so the view doesn't jump
+        assert_eq!(input.action(&moved(100.0, 100.0)), None);
+        assert_eq!(input.action(&moved(110.0, 95.0)), Some(InputAction::Orbit(-10.0 * ORBIT_PER_PIXEL, -5.0 * ORBIT_PER_PIXEL)));
+        // Another button mid-drag neither takes it over nor ends it
+        input.action(&mouse(ElementState::Pressed, MouseButton::Right));
+        input.action(&mouse(ElementState::Released, MouseButton::Right));
+        assert_eq!(input.action(&moved(120.0, 95.0)), Some(InputAction::Orbit(-10.0 * ORBIT_PER_PIXEL, 0.0)));
+        input.action(&mouse(ElementState::Released, MouseButton::Left));
+        assert_eq!(input.action(&moved(130.0, 95.0)), None);
+    }
+
+    #[test]
+    fn right_middle_and_shift_left_drags_pan() {
+        for (button, modifiers) in [(MouseButton::Right, ModifiersState::empty()), (MouseButton::Middle, ModifiersState::empty()), (MouseButton::Left, ModifiersState::SHIFT)] {
+            let mut input = InputHandler::default();
+            input.action(&holding(modifiers));
+            input.action(&moved(100.0, 100.0));
+            input.action(&mouse(ElementState::Pressed, button));
+            assert_eq!(input.action(&moved(90.0, 120.0)), Some(InputAction::Pan(10.0 * PAN_PER_PIXEL, 20.0 * PAN_PER_PIXEL)), "{:?}", button);
+        }
+    }
+
+    #[test]
+    fn leaving_the_window_forgets_the_cursor() {
+        let mut input = InputHandler::default();
+        input.action(&moved(100.0, 100.0));
+        input.action(&mouse(ElementState::Pressed, MouseButton::Left));
+        input.action(&WindowEvent::CursorLeft { device_id: DeviceId::dummy() });
+        // Coming back in somewhere else doesn't count as dragging all the way there
+        assert_eq!(input.action(&moved(500.0, 300.0)), None);
+        assert_eq!(input.action(&moved(505.0, 300.0)), Some(InputAction::Orbit(-5.0 * ORBIT_PER_PIXEL, 0.0)));
+    }
+
+    #[test]
+    fn wheel_notches_and_touchpad_pixels_dolly_alike_or_zoom_with_ctrl() {
+        let mut input = InputHandler::default();
+        let notches = wheel(MouseScrollDelta::LineDelta(0.0, 2.0));
+        let pixels = wheel(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0.0, 2.0 * PIXELS_PER_LINE as f64)));
+        assert_eq!(input.action(&notches), Some(InputAction::Dolly(DOLLY_PER_STEP.powf(2.0))));
+        assert_eq!(input.action(&pixels), Some(InputAction::Dolly(DOLLY_PER_STEP.powf(2.0))));
+        assert_eq!(input.action(&wheel(MouseScrollDelta::LineDelta(0.0, -1.0))), Some(InputAction::Dolly(DOLLY_PER_STEP.powf(-1.0))));
+        input.action(&holding(ModifiersState::CONTROL));
+        assert_eq!(input.action(&notches), Some(InputAction::Zoom(ZOOM_PER_STEP.powf(2.0))));
+        assert_eq!(input.action(&pixels), Some(InputAction::Zoom(ZOOM_PER_STEP.powf(2.0))));
+    }
+
+    #[test]
+    fn keys_orbit_and_dolly_or_pan_with_shift_and_zoom_with_ctrl() {
+        let mut input = InputHandler::default();
+        assert_eq!(key(&input, KeyCode::ArrowLeft), Some(InputAction::Orbit(ORBIT_PER_KEY, 0.0)));
+        assert_eq!(key(&input, KeyCode::Equal), Some(InputAction::Dolly(DOLLY_PER_STEP)));
+        input.action(&holding(ModifiersState::SHIFT));
+        assert_eq!(key(&input, KeyCode::ArrowLeft), Some(InputAction::Pan(PAN_PER_KEY, 0.0)));
+        assert_eq!(key(&input, KeyCode::ArrowUp), Some(InputAction::Pan(0.0, -PAN_PER_KEY)));
+        input.action(&holding(ModifiersState::CONTROL));
+        assert_eq!(key(&input, KeyCode::Minus), Some(InputAction::Zoom(ZOOM_PER_STEP.powf(-1.0))));
+        assert_eq!(key(&input, KeyCode::ArrowDown), Some(InputAction::Orbit(0.0, ORBIT_PER_KEY)));
+    }
+
+    #[test]
+    fn held_keys_repeat_steps_but_not_toggles() {
+        let input = InputHandler::default();
+        assert_eq!(input.key_action(PhysicalKey::Code(KeyCode::Space), true), None);
+        assert_eq!(input.key_action(PhysicalKey::Code(KeyCode::Period), true), Some(InputAction::Step));
+        assert_eq!(input.key_action(PhysicalKey::Code(KeyCode::KeyF), true), None);
+        assert_eq!(key(&input, KeyCode::KeyF), Some(InputAction::FrameModel));
+    }
+}
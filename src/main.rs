@@ -1,18 +1,38 @@
 mod types;
 mod gltf_loader;
+mod obj_loader;
+mod model_loader;
 mod input;
 mod audio;
 mod particles;
+mod texture;
+mod material;
+mod model;
+mod geometry;
+mod renderer;
+mod headless;
+mod cli;
+mod timestep;
+mod emitter;
+mod curve;
+mod gpu_particles;
+mod sprite_atlas;
+mod collision;
+mod force_field;
+mod camera;
+#[cfg(test)]
+mod snapshot_tests;
 
 use std::sync::Arc;
-use types::{Vertex, Uniforms};
-use particles::ParticleSystem;
-use gltf_loader::GltfLoader;
-use input::InputHandler;
+use model::ModelFit;
+use model_loader::{FallbackPolicy, LoadOptions, ModelLoader};
+use input::{InputAction, InputHandler};
 use audio::AudioSystem;
-use glam::{Mat4, Vec3};
-use rand::Rng;
-use wgpu::util::DeviceExt;
+use renderer::Renderer;
+use camera::{Camera, Projection};
+use cli::CliArgs;
+use emitter::ParticleConfig;
+use timestep::{FixedTimestep, SIMULATION_STEP};
 
 use winit::{
     application::ApplicationHandler,
@@ -25,34 +45,19 @@ use winit::{
 
 struct State {
     window: Arc<Window>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
     size: winit::dpi::PhysicalSize<u32>,
     surface: wgpu::Surface<'static>,
     surface_format: wgpu::TextureFormat,
-    render_pipeline: wgpu::RenderPipeline,
-    vertex_buffer: wgpu::Buffer,
-    index_buffer: wgpu::Buffer,
-    uniform_buffer: wgpu::Buffer,
-    uniform_bind_group: wgpu::BindGroup,
-    num_indices: u32,
-    // Background fire quad
-    background_pipeline: wgpu::RenderPipeline,
-    background_vertex_buffer: wgpu::Buffer,
-    background_index_buffer: wgpu::Buffer,
-    background_uniform_buffer: wgpu::Buffer,
-    background_bind_group: wgpu::BindGroup,
-    rotation: (f32, f32), // (x_rotation, y_rotation)
-    base_color: [f32; 4],
+    renderer: Renderer,
     start_time: std::time::Instant,
     last_frame_time: f32,
+    timestep: FixedTimestep,
     audio_system: AudioSystem,
-    // Particle system
-    particle_system: ParticleSystem,
 }
 
 impl State {
-    async fn new(_display: OwnedDisplayHandle, window: Arc<Window>) -> State {
+    async fn new(_display: OwnedDisplayHandle, window: Arc<Window>, model: &model::ModelData, model_fit: ModelFit, particles: &ParticleConfig,
+                 particle_seed: u64, projection: Projection) -> State {
         let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
@@ -76,182 +81,22 @@ impl State {
         let surface_format = cap.formats[0];
 
         // Initialize audio system 🎵
-        let mut audio_system = AudioSystem::new().expect("Failed to initialize audio system");
+        let audio_system = AudioSystem::new().expect("Failed to initialize audio system");
         audio_system.set_volume(0.3); // 30% volume
 
-        // Load glTF file 
-        let (vertices, indices, base_color) = GltfLoader::load_gltf("assets/9-5_mailbox/9-5_mailbox.gltf");
-
-        // Create vertex/index buffers
-        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(&vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(&indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        let num_indices = indices.len() as u32;
-
-        // Create uniform buffer and bind group
-        let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Uniform Buffer"),
-            size: std::mem::size_of::<Uniforms>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-            entries: &[wgpu::BindGroupLayoutEntry {
-                binding: 0,
-                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
-                count: None,
-            }],
-            label: Some("uniform_bind_group_layout"),
-        });
-        let uniform_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }],
-            label: Some("uniform_bind_group"),
-        });
-
-        // Load shaders
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Solid Lambert Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/solid_lambert.wgsl").into()),
-        });
-        let fire_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some("Fire Shader"),
-            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/fire.wgsl").into()),
-        });
-
-        // Create render pipeline layout and pipeline
-        let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-            label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&bind_group_layout],
-            immediate_size: 0,
-        });
-        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Solid Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState { module: &shader, entry_point: Some("vs_main"), buffers: &[Vertex::desc()], compilation_options: Default::default() },
-            fragment: Some(wgpu::FragmentState { module: &shader, entry_point: Some("fs_main"), targets: &[Some(wgpu::ColorTargetState { format: surface_format, blend: Some(wgpu::BlendState::REPLACE), write_mask: wgpu::ColorWrites::ALL })], compilation_options: Default::default() }),
-            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, strip_index_format: None, front_face: wgpu::FrontFace::Ccw, cull_mode: None, polygon_mode: wgpu::PolygonMode::Fill, unclipped_depth: false, conservative: false },
-            depth_stencil: Some(wgpu::DepthStencilState { format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: true, depth_compare: wgpu::CompareFunction::Less, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default() }),
-            multisample: wgpu::MultisampleState { count: 1, mask: !0, alpha_to_coverage_enabled: false },
-            multiview_mask: Default::default(),
-            cache: None,
-        });
-        
-        // Create fullscreen quad vertices for background fire effect
-        let background_vertices = vec![
-            Vertex { position: [-1.0, -1.0, 0.0], normal: [0.0, 0.0, 1.0] }, // Bottom-left
-            Vertex { position: [1.0, -1.0, 0.0], normal: [0.0, 0.0, 1.0] },  // Bottom-right
-            Vertex { position: [1.0, 1.0, 0.0], normal: [0.0, 0.0, 1.0] },   // Top-right
-            Vertex { position: [-1.0, 1.0, 0.0], normal: [0.0, 0.0, 1.0] },  // Top-left
-        ];
-        let background_indices: Vec<u16> = vec![0, 1, 2, 0, 2, 3];
-        
-        // Create background vertex buffer
-        let background_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Background Vertex Buffer"),
-            contents: bytemuck::cast_slice(&background_vertices),
-            usage: wgpu::BufferUsages::VERTEX,
-        });
-        
-        // Create background index buffer
-        let background_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
-            label: Some("Background Index Buffer"),
-            contents: bytemuck::cast_slice(&background_indices),
-            usage: wgpu::BufferUsages::INDEX,
-        });
-        
-        // Create background uniform buffer
-        let background_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Background Uniform Buffer"),
-            size: std::mem::size_of::<Uniforms>() as u64,
-            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
-        
-        // Create background bind group
-        let background_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: background_uniform_buffer.as_entire_binding(),
-            }],
-            label: Some("background_bind_group"),
-        });
-        
-        // Create background render pipeline with fire shader
-        let background_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Background Fire Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: wgpu::VertexState {
-                module: &fire_shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc()],
-                compilation_options: Default::default(),
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &fire_shader,
-                entry_point: Some("fs_main"),
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: surface_format,
-                    blend: Some(wgpu::BlendState::REPLACE),
-                    write_mask: wgpu::ColorWrites::ALL,
-                })],
-                compilation_options: Default::default(),
-            }),
-            primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: None,
-                polygon_mode: wgpu::PolygonMode::Fill,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: None, // Background doesn't need depth testing
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            multiview_mask: Default::default(),
-            cache: None,
-        });
-
-        // Initialize particle system
-        let particle_system = ParticleSystem::new(&device, surface_format, &bind_group_layout);
+        let mut renderer = Renderer::new(device, queue, surface_format, model, model_fit, particles, particle_seed);
+        renderer.camera = Camera::new(projection);
 
         let state = State {
             window,
-            device,
-            queue,
             size,
             surface,
             surface_format,
-            render_pipeline,
-            vertex_buffer,
-            index_buffer,
-            uniform_buffer,
-            uniform_bind_group,
-            num_indices,
-            background_pipeline,
-            background_vertex_buffer,
-            background_index_buffer,
-            background_uniform_buffer,
-            background_bind_group,
-            rotation: (0.0, 0.0),
-            base_color,
+            renderer,
             start_time: std::time::Instant::now(),
             last_frame_time: 0.0,
+            timestep: FixedTimestep::new(SIMULATION_STEP),
             audio_system,
-            particle_system,
         };
 
         // Configure surface for the first time
@@ -259,7 +104,7 @@ impl State {
 
         state
     }
-    
+
     fn load_background_music(&mut self) {
         // LET THE MUSIC PLAY! 🎶
         if let Err(e) = self.audio_system.play_file_looped("assets/251461__joshuaempyre__arcade-music-loop.wav", 1.0) {
@@ -267,33 +112,6 @@ impl State {
         }
     }
     
-    // Generate a rainbow color based on elapsed time 🌈
-    fn get_rainbow_color(&self) -> wgpu::Color {
-        let elapsed = self.start_time.elapsed().as_secs_f32();
-        let hue = (elapsed * 0.5) % 1.0; // Complete rainbow cycle every 2 seconds
-        
-        // Convert HSV to RGB (with S=1, V=1 for vibrant colors)
-        let c = 1.0;
-        let x = c * (1.0 - ((hue * 6.0) % 2.0 - 1.0).abs());
-        let m = 0.0;
-        
-        let (r, g, b) = match (hue * 6.0) as i32 {
-            0 => (c, x, 0.0),      // Red to Yellow
-            1 => (x, c, 0.0),      // Yellow to Green  
-            2 => (0.0, c, x),      // Green to Cyan
-            3 => (0.0, x, c),      // Cyan to Blue
-            4 => (x, 0.0, c),      // Blue to Magenta
-            _ => (c, 0.0, x),      // Magenta to Red
-        };
-        
-        wgpu::Color {
-            r: (r + m) as f64,
-            g: (g + m) as f64, 
-            b: (b + m) as f64,
-            a: 1.0,
-        }
-    }
-
     fn get_window(&self) -> &Window {
         &self.window
     }
@@ -311,7 +129,7 @@ impl State {
                 desired_maximum_frame_latency: 2,
                 present_mode: wgpu::PresentMode::AutoVsync,
             };
-            self.surface.configure(&self.device, &surface_config);
+            self.surface.configure(&self.renderer.device, &surface_config);
         }
     }
 
@@ -326,10 +144,31 @@ impl State {
         self.configure_surface();
     }
 
-    // Particle update now handled by ParticleSystem
+    fn apply(&mut self, action: InputAction) {
+        match action {
+            InputAction::TogglePause => {
+                self.timestep.toggle_pause();
+                println!("{}", if self.timestep.paused { "⏸️ Paused" } else { "▶️ Resumed" });
+            }
+            InputAction::Step => self.timestep.step_once(),
+            InputAction::ScaleTime(factor) => {
+                self.timestep.scale_time(factor);
+                println!("⏩ Time scale: {}x", self.timestep.time_scale);
+            }
+            InputAction::ResetTimeScale => {
+                self.timestep.time_scale = 1.0;
+                println!("⏩ Time scale: 1x");
+            }
+            InputAction::Orbit(yaw, pitch) => self.renderer.camera.orbit(yaw, pitch),
+            InputAction::Pan(right, up) => self.renderer.camera.pan(right, up),
+            InputAction::Dolly(factor) => self.renderer.camera.dolly(factor),
+            InputAction::Zoom(factor) => self.renderer.camera.zoom(factor),
+            InputAction::FrameModel => self.renderer.frame_model(),
+            InputAction::ResetView => self.renderer.camera.reset(),
+        }
+    }
 
     fn render(&mut self) {
-        // Update particles
         let time = self.start_time.elapsed().as_secs_f32();
         let mut dt = time - self.last_frame_time;
         if self.last_frame_time == 0.0 {
@@ -339,41 +178,12 @@ impl State {
         } else {
             self.last_frame_time = time;
         }
-        self.particle_system.update(dt, time);
-        // Update rotation for animation
-        self.rotation.0 += 0.01; // Rotate around X axis
-        self.rotation.1 += 0.01; // Rotate around Y axis
-        
-        // Update MVP matrix
-        let aspect = self.size.width as f32 / self.size.height as f32;
-        let projection = Mat4::perspective_rh(45.0_f32.to_radians(), aspect, 0.1, 2000.0);
-        let view = Mat4::look_at_rh(
-            Vec3::new(0.0, 0.0, 800.0), // Eye position - moved back along Z
-            Vec3::new(0.0, 0.0, 0.0),   // Look at center
-            Vec3::new(0.0, 1.0, 0.0),     // Up vector
-        );
-        
-        // Apply correct scaling to match original FBX dimensions
-        // Original: X=158.61, Y=359.09, Z=149.86
-        // Trying different coordinate mapping - height (359.09) to Z axis
-        let scale = Mat4::from_scale(Vec3::new(
-            158.61 / 2.0,  // X scale factor: 79.305
-            149.86 / 2.0,  // Y scale factor: 74.93  
-            359.09 / 2.0   // Z scale factor (height): 179.545
-        ));
-        
-        let rotation_x = Mat4::from_rotation_x(self.rotation.0);
-        let rotation_y = Mat4::from_rotation_y(self.rotation.1);
-        let model = rotation_y * rotation_x * scale;
-        let mvp = projection * view * model;
-        
-        let uniforms = Uniforms {
-            mvp_matrix: mvp.to_cols_array_2d(),
-            model_matrix: model.to_cols_array_2d(),
-            base_color: self.base_color,
-        };
-        
-        self.queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        // Spend the frame's time in whole simulation steps, then draw between the last two
+        for _ in 0..self.timestep.advance(dt) {
+            self.renderer.update(self.timestep.step);
+        }
+        // In real time, so the view still moves while paused
+        self.renderer.camera.update(dt);
 
         // Get surface texture
         let surface_texture = self
@@ -386,118 +196,33 @@ impl State {
                 format: Some(self.surface_format.add_srgb_suffix()),
                 ..Default::default()
             });
-            
-        // Create depth texture (needed for 3D rendering)
-        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
-            size: wgpu::Extent3d {
-                width: self.size.width,
-                height: self.size.height,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            label: Some("depth_texture"),
-            view_formats: &[],
-        });
-        
-        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
-
-        let mut encoder = self.device.create_command_encoder(&Default::default());
-        
-        // First pass: Render background fire effect
-        {
-            // Use time to animate the fire effect
-            let time = self.start_time.elapsed().as_secs_f32();
-            // Encode time in the model matrix translation to pass to shader
-            let time_matrix = Mat4::from_translation(Vec3::new(time, time * 0.5, 0.0));
-            
-            let background_uniforms = Uniforms {
-                mvp_matrix: Mat4::IDENTITY.to_cols_array_2d(),
-                model_matrix: time_matrix.to_cols_array_2d(),
-                base_color: [1.0, 0.5, 0.0, 1.0], // Orange color base
-            };
-            self.queue.write_buffer(&self.background_uniform_buffer, 0, bytemuck::cast_slice(&[background_uniforms]));
-            
-            let mut background_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Background Fire Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: None,
-                timestamp_writes: None,
-                occlusion_query_set: None,
-                multiview_mask: None,
-            });
-            
-            background_pass.set_pipeline(&self.background_pipeline);
-            background_pass.set_bind_group(0, &self.background_bind_group, &[]);
-            background_pass.set_vertex_buffer(0, self.background_vertex_buffer.slice(..));
-            background_pass.set_index_buffer(self.background_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            background_pass.draw_indexed(0..6, 0, 0..1);
-        }
-        
-        // Second pass: Render 3D model on top
-        {
-            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Model Render Pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &texture_view,
-                    depth_slice: None,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load, // Keep the background we just rendered
-                        store: wgpu::StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: &depth_view,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
-                        store: wgpu::StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-                multiview_mask: None,
-            });
-            
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.uniform_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..1);
-        }
-
-        // Third pass: Render fire particles
-        let aspect = self.size.width as f32 / self.size.height as f32;
-        let projection = Mat4::perspective_rh(45.0_f32.to_radians(), aspect, 0.1, 2000.0);
-        let view = Mat4::look_at_rh(
-            Vec3::new(0.0, 0.0, 800.0),
-            Vec3::new(0.0, 0.0, 0.0),
-            Vec3::new(0.0, 1.0, 0.0),
-        );
-        let time = self.start_time.elapsed().as_secs_f32();
-        self.particle_system.render(&self.queue, &mut encoder, &texture_view, &depth_view, projection, view, time);
 
-        self.queue.submit([encoder.finish()]);
+        self.renderer.render(&texture_view, (self.size.width, self.size.height), self.timestep.alpha());
         self.window.pre_present_notify();
         surface_texture.present();
     }
 }
 
-#[derive(Default)]
+// Load the model (.gltf, .glb or .obj) with its materials. In a window a broken asset
+// still gives us something to look at, but says so loudly; headless runs stop on it
+fn load_model(path: &str, options: &LoadOptions, policy: FallbackPolicy) -> model::ModelData {
+    match ModelLoader::load_or(path, options, policy) {
+        Ok(model) => model,
+        Err(e) => {
+            eprintln!("Error: failed to load model '{}': {}", path, e);
+            std::process::exit(1);
+        }
+    }
+}
+
 struct App {
     state: Option<State>,
+    model: model::ModelData,
+    model_fit: ModelFit,
+    particles: ParticleConfig,
+    particle_seed: u64,
+    projection: Projection,
+    input: InputHandler,
 }
 
 impl ApplicationHandler for App {
@@ -516,6 +241,11 @@ impl ApplicationHandler for App {
         let state = pollster::block_on(State::new(
             event_loop.owned_display_handle(),
             window.clone(),
+            &self.model,
+            self.model_fit,
+            &self.particles,
+            self.particle_seed,
+            self.projection,
         ));
         self.state = Some(state);
         
@@ -534,6 +264,10 @@ impl ApplicationHandler for App {
         if InputHandler::handle_window_event(&event, event_loop) {
             return;
         }
+        if let Some(action) = self.input.action(&event) {
+            state.apply(action);
+            return;
+        }
         
         match event {
             WindowEvent::RedrawRequested => {
@@ -558,6 +292,35 @@ fn main() {
     // documentation for more information.
     env_logger::init();
 
+    let args = match CliArgs::parse() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    let mut particles = match ParticleConfig::load(&args.particles_path) {
+        Ok(particles) => particles,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let particle_seed = args.particle_seed(&mut particles);
+
+    let policy = if args.headless { FallbackPolicy::Fail } else { FallbackPolicy::Cube };
+    let model = load_model(&args.model_path, &args.load_options, policy);
+
+    // No window, no event loop: render straight to PNGs
+    if args.headless {
+        if let Err(e) = headless::run(&model, args.model_fit, &particles, &args.headless_options, args.projection, particle_seed) {
+            eprintln!("Headless render failed: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let event_loop = EventLoop::new().unwrap();
 
     // When the current loop iteration finishes, immediately begin a new
@@ -572,6 +335,14 @@ fn main() {
     // the background.
     // event_loop.set_control_flow(ControlFlow::Wait);
 
-    let mut app = App::default();
+    let mut app = App {
+        state: None,
+        model,
+        model_fit: args.model_fit,
+        particles,
+        particle_seed,
+        projection: args.projection,
+        input: InputHandler::default(),
+    };
     event_loop.run_app(&mut app).unwrap();
 }
\ No newline at end of file
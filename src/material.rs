@@ -0,0 +1,126 @@
+// Materials: CPU-side description from the asset plus the GPU bind group it's drawn with
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+use crate::texture::Texture;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum AlphaMode {
+    Opaque,
+    Mask,
+    Blend,
+}
+
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub base_color: [f32; 4],
+    pub base_color_texture: Option<usize>, // index into ModelData::textures
+    pub metallic: f32,
+    pub roughness: f32,
+    pub emissive: [f32; 3],
+    pub alpha_mode: AlphaMode,
+    pub alpha_cutoff: f32,
+    pub double_sided: bool,
+}
+
+impl Default for Material {
+    // Plain gray dielectric, what untextured models have always rendered with
+    fn default() -> Self {
+        Self {
+            base_color: [0.5, 0.5, 0.5, 1.0],
+            base_color_texture: None,
+            metallic: 0.0,
+            roughness: 1.0,
+            emissive: [0.0, 0.0, 0.0],
+            alpha_mode: AlphaMode::Opaque,
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
+
+// GPU-side copy of the material factors (group 1, binding 0)
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct MaterialUniforms {
+    pub base_color: [f32; 4],
+    pub emissive: [f32; 4],
+    // metallic, roughness, alpha_cutoff (negative when alpha testing is off), unused
+    pub params: [f32; 4],
+}
+
+impl MaterialUniforms {
+    pub fn from_material(material: &Material) -> Self {
+        let alpha_cutoff = if material.alpha_mode == AlphaMode::Mask { material.alpha_cutoff } else { -1.0 };
+        Self {
+            base_color: material.base_color,
+            emissive: [material.emissive[0], material.emissive[1], material.emissive[2], 0.0],
+            params: [material.metallic, material.roughness, alpha_cutoff, 0.0],
+        }
+    }
+}
+
+pub struct GpuMaterial {
+    pub bind_group: wgpu::BindGroup,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool,
+}
+
+impl GpuMaterial {
+    pub fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        material: &Material,
+        textures: &[Texture],
+        white: &Texture,
+    ) -> Self {
+        let uniforms = MaterialUniforms::from_material(material);
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Material Uniform Buffer"),
+            contents: bytemuck::cast_slice(&[uniforms]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+        let texture = material.base_color_texture.and_then(|i| textures.get(i)).unwrap_or(white);
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::TextureView(&texture.view) },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&texture.sampler) },
+            ],
+            label: Some("material_bind_group"),
+        });
+
+        Self { bind_group, alpha_mode: material.alpha_mode, double_sided: material.double_sided }
+    }
+
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("material_bind_group_layout"),
+        })
+    }
+}
+
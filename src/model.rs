@@ -0,0 +1,392 @@
+// Loaded model data and the GPU-side renderer that draws it
+use crate::material::{AlphaMode, GpuMaterial, Material};
+use crate::texture::{Texture, TextureData};
+use crate::types::Vertex;
+use bytemuck::{Pod, Zeroable};
+use glam::{Mat4, Vec3};
+use std::collections::HashMap;
+use wgpu::util::DeviceExt;
+
+// A range of the shared index buffer drawn with a single material
+#[derive(Copy, Clone, Debug)]
+pub struct Primitive {
+    pub first_index: u32,
+    pub index_count: u32,
+    pub material: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Mesh {
+    pub primitives: Vec<Primitive>,
+}
+
+// Node in the scene graph; transform is relative to the parent
+#[derive(Clone, Debug)]
+pub struct SceneNode {
+    pub transform: Mat4,
+    pub mesh: Option<usize>,
+    pub children: Vec<usize>,
+}
+
+// A mesh placed in the world by a node
+#[derive(Copy, Clone, Debug)]
+pub struct MeshInstance {
+    pub mesh: usize,
+    pub transform: Mat4,
+}
+
+// Axis-aligned box around the model in world space
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+// World-space extents of every mesh instance, after node transforms
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+// Which axis points up in the source asset
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UpAxis {
+    Y,
+    Z,
+}
+
+// How a loaded model is placed in the scene: recentered on the origin, converted to
+// Y-up, then scaled by the unit conversion or to fit a sphere of the given radius
+#[derive(Copy, Clone, Debug)]
+pub struct ModelFit {
+    pub up_axis: UpAxis,
+    // Scene units per asset unit (e.g. 100.0 for a model authored in meters shown in centimeters)
+    pub unit_scale: f32,
+    // Overrides unit_scale so the bounding sphere has this radius
+    pub fit_radius: Option<f32>,
+}
+
+impl ModelFit {
+    pub fn transform(&self, bounds: &Bounds) -> Mat4 {
+        // Z-up assets are turned so +Z becomes +Y
+        let axis = match self.up_axis {
+            UpAxis::Y => Mat4::IDENTITY,
+            UpAxis::Z => Mat4::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+        };
+        let scale = match self.fit_radius {
+            Some(radius) if bounds.sphere.radius > 0.0 => radius / bounds.sphere.radius,
+            _ => self.unit_scale,
+        };
+        Mat4::from_scale(Vec3::splat(scale)) * axis * Mat4::from_translation(-bounds.sphere.center)
+    }
+}
+
+// Everything the renderer needs from a loaded model
+pub struct ModelData {
+    pub vertices: Vec<Vertex>,
+    // Per-vertex MikkTSpace tangents (w = handedness), kept CPU-side until normal maps land
+    #[allow(dead_code)]
+    pub tangents: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+    pub meshes: Vec<Mesh>,
+    pub materials: Vec<Material>,
+    pub textures: Vec<TextureData>,
+    pub nodes: Vec<SceneNode>,
+    pub roots: Vec<usize>,
+    pub bounds: Bounds,
+}
+
+impl ModelData {
+    // 16-bit indices when every vertex is addressable with them, halving the index buffer
+    pub fn index_format(&self) -> wgpu::IndexFormat {
+        if self.vertices.len() <= u16::MAX as usize + 1 {
+            wgpu::IndexFormat::Uint16
+        } else {
+            wgpu::IndexFormat::Uint32
+        }
+    }
+
+    // Walk the scene graph and collect every mesh with its world matrix
+    pub fn instances(&self) -> Vec<MeshInstance> {
+        let mut instances = Vec::new();
+        // (node, parent world matrix, depth) - depth guards against cycles in malformed files
+        let mut stack: Vec<(usize, Mat4, usize)> = self.roots.iter().rev().map(|&root| (root, Mat4::IDENTITY, 0)).collect();
+        while let Some((index, parent, depth)) = stack.pop() {
+            let Some(node) = self.nodes.get(index) else { continue };
+            if depth > self.nodes.len() { continue; }
+            let world = parent * node.transform;
+            if let Some(mesh) = node.mesh {
+                instances.push(MeshInstance { mesh, transform: world });
+            }
+            stack.extend(node.children.iter().rev().map(|&child| (child, world, depth + 1)));
+        }
+        instances
+    }
+
+    // Box and sphere around every drawn vertex; the sphere shares the box center
+    pub fn compute_bounds(&self) -> Bounds {
+        let mut positions = Vec::new();
+        for instance in self.instances() {
+            for primitive in &self.meshes[instance.mesh].primitives {
+                let range = primitive.first_index as usize..(primitive.first_index + primitive.index_count) as usize;
+                positions.extend(self.indices[range].iter()
+                    .map(|&index| instance.transform.transform_point3(Vec3::from(self.vertices[index as usize].position))));
+            }
+        }
+        if positions.is_empty() {
+            return Bounds::default();
+        }
+
+        let aabb = positions.iter().fold(
+            Aabb { min: Vec3::splat(f32::INFINITY), max: Vec3::splat(f32::NEG_INFINITY) },
+            |aabb, &position| Aabb { min: aabb.min.min(position), max: aabb.max.max(position) },
+        );
+        let center = aabb.center();
+        let radius = positions.iter().map(|&position| position.distance(center)).fold(0.0, f32::max);
+        Bounds { aabb, sphere: BoundingSphere { center, radius } }
+    }
+
+    // Replace the scene graph with one root node per mesh at the origin
+    pub fn place_meshes_at_origin(&mut self) {
+        self.nodes = (0..self.meshes.len())
+            .map(|mesh| SceneNode { transform: Mat4::IDENTITY, mesh: Some(mesh), children: Vec::new() })
+            .collect();
+        self.roots = (0..self.nodes.len()).collect();
+    }
+}
+
+// Pipeline state that varies per material (and per instance for mirrored transforms)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    blend: bool,
+    double_sided: bool,
+    mirrored: bool,
+}
+
+// Matches Uniforms in solid_lambert.wgsl; colors come from the material bind group
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct InstanceUniforms {
+    mvp_matrix: [[f32; 4]; 4],
+    model_matrix: [[f32; 4]; 4],
+}
+
+// Per-instance uniforms: world matrix from the scene graph
+struct GpuInstance {
+    mesh: usize,
+    transform: Mat4,
+    uniform_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+pub struct ModelRenderer {
+    pipelines: HashMap<PipelineKey, wgpu::RenderPipeline>,
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    instances: Vec<GpuInstance>,
+    materials: Vec<GpuMaterial>,
+    meshes: Vec<Mesh>,
+}
+
+impl ModelRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        surface_format: wgpu::TextureFormat,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        model: &ModelData,
+    ) -> Self {
+        let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Vertex Buffer"),
+            contents: bytemuck::cast_slice(&model.vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let index_format = model.index_format();
+        let index_bytes: Vec<u8> = match index_format {
+            wgpu::IndexFormat::Uint16 => {
+                let indices: Vec<u16> = model.indices.iter().map(|&index| index as u16).collect();
+                bytemuck::cast_slice(&indices).to_vec()
+            }
+            wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(&model.indices).to_vec(),
+        };
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Index Buffer"),
+            contents: &index_bytes,
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        // One uniform buffer per mesh instance in the scene
+        let instances: Vec<GpuInstance> = model.instances().into_iter().map(|instance| {
+            let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Uniform Buffer"),
+                size: std::mem::size_of::<InstanceUniforms>() as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                layout: uniform_bind_group_layout,
+                entries: &[wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() }],
+                label: Some("uniform_bind_group"),
+            });
+            GpuInstance { mesh: instance.mesh, transform: instance.transform, uniform_buffer, bind_group }
+        }).collect();
+
+        // Upload textures, then one bind group per material (white texture when it has none)
+        let textures: Vec<Texture> = model.textures.iter()
+            .map(|data| Texture::from_data(device, queue, data, "Base Color Texture"))
+            .collect();
+        let white = Texture::from_data(device, queue, &TextureData::white(), "Default White Texture");
+        let material_bind_group_layout = GpuMaterial::bind_group_layout(device);
+        let materials: Vec<GpuMaterial> = model.materials.iter()
+            .map(|material| GpuMaterial::new(device, &material_bind_group_layout, material, &textures, &white))
+            .collect();
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Solid Lambert Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/solid_lambert.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Render Pipeline Layout"),
+            bind_group_layouts: &[uniform_bind_group_layout, &material_bind_group_layout],
+            immediate_size: 0,
+        });
+
+        // Only build the pipeline variants the instances actually use
+        let mut pipelines = HashMap::new();
+        for instance in &instances {
+            for primitive in &model.meshes[instance.mesh].primitives {
+                let key = Self::pipeline_key(&materials[primitive.material], instance.transform);
+                pipelines.entry(key).or_insert_with(|| Self::create_pipeline(device, &pipeline_layout, &shader, surface_format, key));
+            }
+        }
+
+        Self {
+            pipelines,
+            vertex_buffer,
+            index_buffer,
+            index_format,
+            instances,
+            materials,
+            meshes: model.meshes.clone(),
+        }
+    }
+
+    fn pipeline_key(material: &GpuMaterial, transform: Mat4) -> PipelineKey {
+        PipelineKey {
+            blend: material.alpha_mode == AlphaMode::Blend,
+            double_sided: material.double_sided,
+            // A negative determinant flips triangle winding (per the glTF spec)
+            mirrored: transform.determinant() < 0.0,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        key: PipelineKey,
+    ) -> wgpu::RenderPipeline {
+        // Blended materials are drawn after opaque ones and don't write depth
+        let blend = if key.blend { wgpu::BlendState::ALPHA_BLENDING } else { wgpu::BlendState::REPLACE };
+        let cull_mode = if key.double_sided { None } else { Some(wgpu::Face::Back) };
+        let front_face = if key.mirrored { wgpu::FrontFace::Cw } else { wgpu::FrontFace::Ccw };
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Solid Render Pipeline"),
+            layout: Some(layout),
+            vertex: wgpu::VertexState { module: shader, entry_point: Some("vs_main"), buffers: &[Vertex::desc()], compilation_options: Default::default() },
+            fragment: Some(wgpu::FragmentState { module: shader, entry_point: Some("fs_main"), targets: &[Some(wgpu::ColorTargetState { format: surface_format, blend: Some(blend), write_mask: wgpu::ColorWrites::ALL })], compilation_options: Default::default() }),
+            primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, strip_index_format: None, front_face, cull_mode, polygon_mode: wgpu::PolygonMode::Fill, unclipped_depth: false, conservative: false },
+            depth_stencil: Some(wgpu::DepthStencilState { format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: !key.blend, depth_compare: wgpu::CompareFunction::Less, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default() }),
+            multisample: wgpu::MultisampleState { count: 1, mask: !0, alpha_to_coverage_enabled: false },
+            multiview_mask: Default::default(),
+            cache: None,
+        })
+    }
+
+    // `model` is applied on top of each instance's world matrix
+    pub fn update(&self, queue: &wgpu::Queue, view_projection: Mat4, model: Mat4) {
+        for instance in &self.instances {
+            let world = model * instance.transform;
+            let uniforms = InstanceUniforms {
+                mvp_matrix: (view_projection * world).to_cols_array_2d(),
+                model_matrix: world.to_cols_array_2d(),
+            };
+            queue.write_buffer(&instance.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        }
+    }
+
+    pub fn draw(&self, pass: &mut wgpu::RenderPass) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        pass.set_index_buffer(self.index_buffer.slice(..), self.index_format);
+
+        // Opaque and masked primitives first so blended ones composite over them
+        for blended in [false, true] {
+            for instance in &self.instances {
+                pass.set_bind_group(0, &instance.bind_group, &[]);
+                for primitive in &self.meshes[instance.mesh].primitives {
+                    let material = &self.materials[primitive.material];
+                    if (material.alpha_mode == AlphaMode::Blend) != blended { continue; }
+                    pass.set_pipeline(&self.pipelines[&Self::pipeline_key(material, instance.transform)]);
+                    pass.set_bind_group(1, &material.bind_group, &[]);
+                    pass.draw_indexed(primitive.first_index..primitive.first_index + primitive.index_count, 0, 0..1);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(center: Vec3, radius: f32) -> Bounds {
+        Bounds { aabb: Aabb::default(), sphere: BoundingSphere { center, radius } }
+    }
+
+    #[test]
+    fn z_up_models_are_stood_upright() {
+        let fit = ModelFit { up_axis: UpAxis::Z, unit_scale: 1.0, fit_radius: None };
+        let transform = fit.transform(&bounds(Vec3::ZERO, 1.0));
+        assert!(transform.transform_point3(Vec3::Z).abs_diff_eq(Vec3::Y, 1e-6));
+        assert!(transform.transform_point3(Vec3::Y).abs_diff_eq(-Vec3::Z, 1e-6));
+        assert!(transform.transform_point3(Vec3::X).abs_diff_eq(Vec3::X, 1e-6));
+    }
+
+    #[test]
+    fn recenters_then_scales_by_the_unit_conversion() {
+        let fit = ModelFit { up_axis: UpAxis::Y, unit_scale: 100.0, fit_radius: None };
+        let transform = fit.transform(&bounds(Vec3::new(10.0, 0.0, 0.0), 2.0));
+        assert!(transform.transform_point3(Vec3::new(10.0, 0.0, 0.0)).abs_diff_eq(Vec3::ZERO, 1e-4));
+        assert!(transform.transform_point3(Vec3::new(11.0, 0.0, 0.0)).abs_diff_eq(Vec3::new(100.0, 0.0, 0.0), 1e-4));
+    }
+
+    #[test]
+    fn fit_radius_overrides_the_unit_scale() {
+        let fit = ModelFit { up_axis: UpAxis::Z, unit_scale: 100.0, fit_radius: Some(200.0) };
+        let transform = fit.transform(&bounds(Vec3::new(0.0, 0.0, 5.0), 50.0));
+        // The top of the sphere lands 200 units up
+        assert!(transform.transform_point3(Vec3::new(0.0, 0.0, 55.0)).abs_diff_eq(Vec3::new(0.0, 200.0, 0.0), 1e-3));
+        // With no size to fit, it falls back to the unit scale
+        let transform = fit.transform(&bounds(Vec3::ZERO, 0.0));
+        assert!(transform.transform_point3(Vec3::Z).abs_diff_eq(Vec3::new(0.0, 100.0, 0.0), 1e-4));
+    }
+}
@@ -0,0 +1,358 @@
+// Format-independent model loading: picks a reader from the file extension, then every
+// format appends its primitives to the same vertex/index layout through GeometryBuilder
+use crate::geometry::{self, NormalMode};
+use crate::gltf_loader::GltfLoader;
+use crate::material::Material;
+use crate::model::{Bounds, Mesh, ModelData, Primitive, SceneNode};
+use crate::obj_loader::ObjLoader;
+use crate::texture::TextureData;
+use crate::types::Vertex;
+use std::fmt;
+use std::path::Path;
+
+// Why a model file couldn't be turned into a model
+#[derive(Debug)]
+pub enum LoadError {
+    MissingFile { path: String },
+    UnsupportedFormat { path: String },
+    Import { path: String, source: gltf::Error },
+    Obj { path: String, source: tobj::LoadError },
+    BadBufferIndex { mesh: usize, primitive: usize, buffer: usize },
+    NoPositions { mesh: usize, primitive: usize },
+    UnsupportedMode { mesh: usize, primitive: usize, mode: gltf::mesh::Mode },
+    InvalidPrimitive { mesh: usize, primitive: usize, reason: String },
+    NoGeometry,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::MissingFile { path } => write!(f, "file '{}' not found", path),
+            LoadError::UnsupportedFormat { path } => write!(f, "'{}' is not a .gltf, .glb or .obj file", path),
+            LoadError::Import { path, source } => write!(f, "failed to import '{}': {}", path, source),
+            LoadError::Obj { path, source } => write!(f, "failed to read OBJ '{}': {}", path, source),
+            LoadError::BadBufferIndex { mesh, primitive, buffer } => {
+                write!(f, "mesh {} primitive {} references missing buffer {}", mesh, primitive, buffer)
+            }
+            LoadError::NoPositions { mesh, primitive } => write!(f, "mesh {} primitive {} has no position data", mesh, primitive),
+            LoadError::UnsupportedMode { mesh, primitive, mode } => {
+                write!(f, "mesh {} primitive {} uses unsupported mode {:?}, only triangle lists are drawn", mesh, primitive, mode)
+            }
+            LoadError::InvalidPrimitive { mesh, primitive, reason } => write!(f, "mesh {} primitive {}: {}", mesh, primitive, reason),
+            LoadError::NoGeometry => write!(f, "no geometry found"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Import { source, .. } => Some(source),
+            LoadError::Obj { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+// What to do when a model fails to load
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FallbackPolicy {
+    // Hand the error back to the caller
+    Fail,
+    // Log the error and substitute the safety cube
+    Cube,
+}
+
+// Knobs for filling in what the file leaves out
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LoadOptions {
+    // Used for primitives without normals
+    pub normals: NormalMode,
+}
+
+// One primitive's attributes as the file stored them; anything missing is filled in
+#[derive(Default)]
+pub struct PrimitiveData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Option<Vec<[f32; 3]>>,
+    pub uvs: Option<Vec<[f32; 2]>>,
+    pub tangents: Option<Vec<[f32; 4]>>,
+    // Non-indexed primitives draw their vertices in order
+    pub indices: Option<Vec<u32>>,
+}
+
+// The shared vertex and index buffers every primitive is appended to
+#[derive(Default)]
+pub struct GeometryBuilder {
+    vertices: Vec<Vertex>,
+    tangents: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl GeometryBuilder {
+    // Validate one primitive, fill in its missing attributes and append it.
+    // Returns the primitive's first index and index count in the shared buffer
+    pub fn push(&mut self, data: PrimitiveData, options: &LoadOptions) -> Result<(u32, u32), String> {
+        let PrimitiveData { positions, normals, uvs, tangents, indices } = data;
+        let vertex_count = positions.len();
+        let normal_count = normals.as_ref().map_or(vertex_count, Vec::len);
+        let uvs = uvs.unwrap_or_else(|| vec![[0.0, 0.0]; vertex_count]);
+        if normal_count != vertex_count || uvs.len() != vertex_count {
+            return Err(format!("attribute counts differ ({} positions, {} normals, {} UVs)", vertex_count, normal_count, uvs.len()));
+        }
+        if vertex_count > u32::MAX as usize {
+            return Err(format!("{} vertices don't fit in 32-bit indices", vertex_count));
+        }
+        let indices = indices.unwrap_or_else(|| (0..vertex_count as u32).collect());
+        if !indices.len().is_multiple_of(3) {
+            return Err(format!("{} indices is not a whole number of triangles", indices.len()));
+        }
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertex_count) {
+            return Err(format!("index {} out of range for {} vertices", index, vertex_count));
+        }
+
+        // Missing normals are rebuilt from the triangles, which may split vertices on creases.
+        // File tangents were authored against the file's normals, so only keep them together
+        let (positions, normals, uvs, indices, tangents) = match normals {
+            Some(normals) => {
+                let tangents = tangents.filter(|tangents| tangents.len() == vertex_count);
+                (positions, normals, uvs, indices, tangents)
+            }
+            None => {
+                let rebuilt = geometry::generate_normals(&positions, &indices, options.normals);
+                let positions = rebuilt.source.iter().map(|&index| positions[index as usize]).collect();
+                let uvs = rebuilt.source.iter().map(|&index| uvs[index as usize]).collect();
+                (positions, rebuilt.normals, uvs, rebuilt.indices, None)
+            }
+        };
+        let tangents = tangents.unwrap_or_else(|| geometry::generate_tangents(&positions, &normals, &uvs, &indices));
+
+        // Rebase the primitive's indices onto the shared vertex buffer
+        let vertex_offset = self.vertices.len() as u32;
+        let first_index = self.indices.len() as u32;
+        for ((position, normal), uv) in positions.iter().zip(normals.iter()).zip(uvs.iter()) {
+            self.vertices.push(Vertex { position: *position, normal: *normal, uv: *uv });
+        }
+        self.tangents.extend(tangents);
+        self.indices.extend(indices.iter().map(|&index| index + vertex_offset));
+        Ok((first_index, self.indices.len() as u32 - first_index))
+    }
+
+    // Assemble the model; without any scene roots every mesh is shown once at the origin
+    pub fn finish(
+        self,
+        meshes: Vec<Mesh>,
+        materials: Vec<Material>,
+        textures: Vec<TextureData>,
+        nodes: Vec<SceneNode>,
+        roots: Vec<usize>,
+    ) -> Result<ModelData, LoadError> {
+        if self.vertices.is_empty() {
+            return Err(LoadError::NoGeometry);
+        }
+        let mut model = ModelData {
+            vertices: self.vertices,
+            tangents: self.tangents,
+            indices: self.indices,
+            meshes,
+            materials,
+            textures,
+            nodes,
+            roots,
+            bounds: Bounds::default(),
+        };
+        if model.roots.is_empty() {
+            model.place_meshes_at_origin();
+        }
+        model.bounds = model.compute_bounds();
+        Ok(model)
+    }
+}
+
+pub struct ModelLoader;
+
+impl ModelLoader {
+    // Load a model, applying the caller's fallback policy on failure
+    pub fn load_or(path: &str, options: &LoadOptions, policy: FallbackPolicy) -> Result<ModelData, LoadError> {
+        match (Self::load(path, options), policy) {
+            (Ok(model), _) => Ok(model),
+            (Err(e), FallbackPolicy::Cube) => {
+                println!("Failed to load model '{}': {}", path, e);
+                println!("Falling back to default cube");
+                Ok(Self::fallback_cube())
+            }
+            (Err(e), FallbackPolicy::Fail) => Err(e),
+        }
+    }
+
+    // .gltf and .glb go through the glTF importer, .obj (with its .mtl) through tobj
+    pub fn load(path: &str, options: &LoadOptions) -> Result<ModelData, LoadError> {
+        let extension = Path::new(path).extension()
+            .and_then(|extension| extension.to_str())
+            .map(|extension| extension.to_ascii_lowercase());
+        let model = match extension.as_deref() {
+            Some("gltf") | Some("glb") => GltfLoader::load(path, options)?,
+            Some("obj") => ObjLoader::load(path, options)?,
+            _ => return Err(LoadError::UnsupportedFormat { path: path.to_string() }),
+        };
+
+        let Bounds { aabb, sphere } = model.bounds;
+        let size = aabb.size();
+        println!("💾 Loaded {}: {} vertices, {} triangle indices, {} meshes, {} instances, {} materials",
+                 path, model.vertices.len(), model.indices.len(), model.meshes.len(), model.instances().len(), model.materials.len());
+        println!("📏 Model dimensions:");
+        println!("  Width (X): {:.4} (from {:.4} to {:.4})", size.x, aabb.min.x, aabb.max.x);
+        println!("  Height (Y): {:.4} (from {:.4} to {:.4})", size.y, aabb.min.y, aabb.max.y);
+        println!("  Depth (Z): {:.4} (from {:.4} to {:.4})", size.z, aabb.min.z, aabb.max.z);
+        println!("  Center: ({:.4}, {:.4}, {:.4})", aabb.center().x, aabb.center().y, aabb.center().z);
+        println!("  Bounding radius: {:.4}", sphere.radius);
+
+        Ok(model)
+    }
+
+    // safety cube!!! 🧊
+    pub fn fallback_cube() -> ModelData {
+        let vertices = [
+            // Front face
+            Vertex { position: [-1.0, -1.0,  1.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 1.0] },
+            Vertex { position: [ 1.0, -1.0,  1.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 1.0] },
+            Vertex { position: [ 1.0,  1.0,  1.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 0.0] },
+            Vertex { position: [-1.0,  1.0,  1.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0] },
+            // Back face
+            Vertex { position: [-1.0, -1.0, -1.0], normal: [0.0, 0.0, -1.0], uv: [1.0, 1.0] },
+            Vertex { position: [ 1.0, -1.0, -1.0], normal: [0.0, 0.0, -1.0], uv: [0.0, 1.0] },
+            Vertex { position: [ 1.0,  1.0, -1.0], normal: [0.0, 0.0, -1.0], uv: [0.0, 0.0] },
+            Vertex { position: [-1.0,  1.0, -1.0], normal: [0.0, 0.0, -1.0], uv: [1.0, 0.0] },
+        ];
+
+        let indices = vec![
+            // Front face
+            0, 1, 2,  2, 3, 0,
+            // Back face  
+            4, 6, 5,  6, 4, 7,
+            // Left face
+            4, 0, 3,  3, 7, 4,
+            // Right face
+            1, 5, 6,  6, 2, 1,
+            // Top face
+            3, 2, 6,  6, 7, 3,
+            // Bottom face
+            4, 5, 1,  1, 0, 4,
+        ];
+
+        println!("Using fallback cube: {} vertices, {} triangle indices", vertices.len(), indices.len());
+        let mut builder = GeometryBuilder::default();
+        let data = PrimitiveData {
+            positions: vertices.iter().map(|vertex| vertex.position).collect(),
+            normals: Some(vertices.iter().map(|vertex| vertex.normal).collect()),
+            uvs: Some(vertices.iter().map(|vertex| vertex.uv).collect()),
+            tangents: None,
+            indices: Some(indices),
+        };
+        let (first_index, index_count) = builder.push(data, &LoadOptions::default()).expect("fallback cube is valid");
+        let primitives = vec![Primitive { first_index, index_count, material: 0 }];
+        builder.finish(vec![Mesh { primitives }], vec![Material::default()], Vec::new(), Vec::new(), Vec::new())
+            .expect("fallback cube has geometry")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    // Vertices along the X axis with every attribute supplied, so nothing needs generating
+    fn primitive(vertex_count: usize, indices: Vec<u32>) -> PrimitiveData {
+        PrimitiveData {
+            positions: (0..vertex_count).map(|i| [i as f32, 0.0, 0.0]).collect(),
+            normals: Some(vec![[0.0, 1.0, 0.0]; vertex_count]),
+            uvs: Some(vec![[0.0, 0.0]; vertex_count]),
+            tangents: Some(vec![[1.0, 0.0, 0.0, 1.0]; vertex_count]),
+            indices: Some(indices),
+        }
+    }
+
+    fn build(data: PrimitiveData) -> ModelData {
+        let mut builder = GeometryBuilder::default();
+        let (first_index, index_count) = builder.push(data, &LoadOptions::default()).expect("valid primitive");
+        let primitives = vec![Primitive { first_index, index_count, material: 0 }];
+        builder.finish(vec![Mesh { primitives }], vec![Material::default()], Vec::new(), Vec::new(), Vec::new())
+            .expect("has geometry")
+    }
+
+    #[test]
+    fn switches_to_32_bit_indices_past_65536_vertices() {
+        // The last vertex 16 bits can still address
+        let model = build(primitive(65_536, vec![0, 1, 65_535]));
+        assert_eq!(model.index_format(), wgpu::IndexFormat::Uint16);
+        let model = build(primitive(65_537, vec![0, 1, 65_536]));
+        assert_eq!(model.index_format(), wgpu::IndexFormat::Uint32);
+        assert_eq!(model.indices, [0, 1, 65_536]);
+    }
+
+    #[test]
+    fn rejects_malformed_primitives() {
+        let mut builder = GeometryBuilder::default();
+        let options = LoadOptions::default();
+        assert_eq!(builder.push(primitive(3, vec![0, 1, 2, 0]), &options),
+                   Err("4 indices is not a whole number of triangles".to_string()));
+        assert_eq!(builder.push(primitive(3, vec![0, 1, 3]), &options),
+                   Err("index 3 out of range for 3 vertices".to_string()));
+        let mismatched = PrimitiveData { uvs: Some(vec![[0.0, 0.0]; 2]), ..primitive(3, vec![0, 1, 2]) };
+        assert_eq!(builder.push(mismatched, &options),
+                   Err("attribute counts differ (3 positions, 3 normals, 2 UVs)".to_string()));
+        // Nothing from the rejected primitives made it in
+        assert!(builder.vertices.is_empty() && builder.indices.is_empty());
+        // Offsets carry on from the primitives before
+        assert_eq!(builder.push(primitive(3, vec![0, 1, 2]), &options), Ok((0, 3)));
+        assert_eq!(builder.push(primitive(3, vec![2, 1, 0]), &options), Ok((3, 3)));
+        assert_eq!(builder.indices, [0, 1, 2, 5, 4, 3]);
+    }
+
+    fn load_error(path: &str) -> LoadError {
+        match ModelLoader::load(path, &LoadOptions::default()) {
+            Ok(_) => panic!("'{}' loaded", path),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn missing_files_are_reported_for_every_format() {
+        for name in ["missing.gltf", "missing.glb", "missing.obj"] {
+            assert!(matches!(load_error(&fixture(name)), LoadError::MissingFile { .. }), "{}", name);
+        }
+    }
+
+    #[test]
+    fn unknown_extensions_are_unsupported() {
+        for path in ["model.fbx", "model", "tests/fixtures"] {
+            assert!(matches!(load_error(path), LoadError::UnsupportedFormat { .. }), "{}", path);
+        }
+    }
+
+    #[test]
+    fn primitives_without_position_data_are_rejected() {
+        // Its POSITION accessor asks for four vertices from a buffer holding three
+        let e = load_error(&fixture("no_positions.gltf"));
+        assert!(matches!(e, LoadError::NoPositions { mesh: 0, primitive: 0 }), "{}", e);
+    }
+
+    #[test]
+    fn only_triangle_lists_are_accepted() {
+        // The same three vertices, drawn as points
+        let e = load_error(&fixture("points.gltf"));
+        assert!(matches!(e, LoadError::UnsupportedMode { mesh: 0, primitive: 0, mode: gltf::mesh::Mode::Points }), "{}", e);
+    }
+
+    #[test]
+    fn fallback_policy_decides_between_the_error_and_the_cube() {
+        let path = fixture("missing.gltf");
+        let failed = ModelLoader::load_or(&path, &LoadOptions::default(), FallbackPolicy::Fail);
+        assert!(matches!(failed, Err(LoadError::MissingFile { .. })));
+        let cube = ModelLoader::load_or(&path, &LoadOptions::default(), FallbackPolicy::Cube).expect("cube fallback");
+        assert_eq!(cube.indices.len(), 36);
+    }
+}
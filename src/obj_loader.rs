@@ -0,0 +1,114 @@
+use crate::material::{AlphaMode, Material};
+use crate::model::{Mesh, ModelData, Primitive};
+use crate::model_loader::{GeometryBuilder, LoadError, LoadOptions, PrimitiveData};
+use crate::texture::{SamplerSettings, TextureData};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+// Reads Wavefront .obj files and the .mtl libraries they reference
+pub struct ObjLoader;
+
+impl ObjLoader {
+    pub fn load(path: &str, options: &LoadOptions) -> Result<ModelData, LoadError> {
+        // Triangulated, with positions, normals and UVs sharing one index like glTF
+        let (models, obj_materials) = tobj::load_obj(path, &tobj::GPU_LOAD_OPTIONS).map_err(|source| match source {
+            tobj::LoadError::OpenFileFailed if !Path::new(path).exists() => LoadError::MissingFile { path: path.to_string() },
+            source => LoadError::Obj { path: path.to_string(), source },
+        })?;
+        // A broken or missing .mtl still leaves usable geometry
+        let obj_materials = obj_materials.unwrap_or_else(|e| {
+            println!("Warning: could not load materials for '{}': {}", path, e);
+            Vec::new()
+        });
+
+        // Texture paths in the .mtl are relative to the .obj
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+        let mut textures = Vec::new();
+        let mut texture_indices = HashMap::new();
+        let mut materials: Vec<Material> = obj_materials.iter()
+            .map(|material| Self::convert_material(material, directory, &mut textures, &mut texture_indices))
+            .collect();
+        // Groups without a usemtl share a default material, appended on first use
+        let mut default_material = None;
+
+        let mut builder = GeometryBuilder::default();
+        let mut meshes = Vec::new();
+
+        // tobj splits groups by material, so each model is one single-primitive mesh
+        for (mesh_index, model) in models.iter().enumerate() {
+            let mesh = &model.mesh;
+            if mesh.positions.is_empty() {
+                return Err(LoadError::NoPositions { mesh: mesh_index, primitive: 0 });
+            }
+            let data = PrimitiveData {
+                positions: mesh.positions.chunks_exact(3).map(|p| [p[0], p[1], p[2]]).collect(),
+                normals: (!mesh.normals.is_empty()).then(|| mesh.normals.chunks_exact(3).map(|n| [n[0], n[1], n[2]]).collect()),
+                // OBJ puts v = 0 at the bottom of the image, glTF and wgpu at the top
+                uvs: (!mesh.texcoords.is_empty()).then(|| mesh.texcoords.chunks_exact(2).map(|t| [t[0], 1.0 - t[1]]).collect()),
+                tangents: None,
+                indices: Some(mesh.indices.clone()),
+            };
+            let (first_index, index_count) = builder.push(data, options)
+                .map_err(|reason| LoadError::InvalidPrimitive { mesh: mesh_index, primitive: 0, reason })?;
+
+            let material = match mesh.material_id {
+                Some(index) if index < obj_materials.len() => index,
+                _ => *default_material.get_or_insert_with(|| {
+                    materials.push(Material::default());
+                    materials.len() - 1
+                }),
+            };
+            meshes.push(Mesh { primitives: vec![Primitive { first_index, index_count, material }] });
+        }
+
+        // OBJ has no node hierarchy, every mesh sits at the origin
+        builder.finish(meshes, materials, textures, Vec::new(), Vec::new())
+    }
+
+    fn convert_material(
+        material: &tobj::Material,
+        directory: &Path,
+        textures: &mut Vec<TextureData>,
+        texture_indices: &mut HashMap<PathBuf, Option<usize>>,
+    ) -> Material {
+        // Materials can share a texture, only load it once
+        let base_color_texture = material.diffuse_texture.as_ref().and_then(|file| {
+            let texture_path = directory.join(file);
+            *texture_indices.entry(texture_path.clone()).or_insert_with(|| match image::open(&texture_path) {
+                Ok(image) => {
+                    let image = image.to_rgba8();
+                    println!("🖼️ Using base color texture: {} ({}x{})", texture_path.display(), image.width(), image.height());
+                    textures.push(TextureData {
+                        width: image.width(),
+                        height: image.height(),
+                        pixels: image.into_raw(),
+                        sampler: SamplerSettings::default(),
+                    });
+                    Some(textures.len() - 1)
+                }
+                Err(e) => {
+                    println!("Warning: could not load texture '{}': {}", texture_path.display(), e);
+                    None
+                }
+            })
+        });
+
+        // Kd multiplies map_Kd, so a textured material without one shows the texture as-is
+        let default_color = if base_color_texture.is_some() { [1.0; 3] } else { [0.5; 3] };
+        let [r, g, b] = material.diffuse.unwrap_or(default_color);
+        let alpha = material.dissolve.unwrap_or(1.0);
+        println!("🪨 Material {:?}: color [{:.3}, {:.3}, {:.3}, {:.3}]", material.name, r, g, b, alpha);
+
+        Material {
+            base_color: [r, g, b, alpha],
+            base_color_texture,
+            metallic: 0.0,
+            // Usual Blinn-Phong exponent to roughness mapping
+            roughness: material.shininess.map_or(1.0, |shininess| (2.0 / (shininess.max(0.0) + 2.0)).sqrt()),
+            emissive: material.emissive.unwrap_or([0.0; 3]),
+            alpha_mode: if alpha < 1.0 { AlphaMode::Blend } else { AlphaMode::Opaque },
+            alpha_cutoff: 0.5,
+            double_sided: false,
+        }
+    }
+}
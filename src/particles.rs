@@ -1,27 +1,396 @@
-use crate::types::{Vertex, Uniforms, Particle, ParticleInstance};
+use crate::collision::{self, Colliders};
+use crate::emitter::{sample_range, EmissionMesh, EmitterConfig, EmitterShape, FieldConfig, Orientation, ParticleConfig, SimulationBackend, SubEmitterEvent};
+use crate::force_field;
+use crate::gpu_particles::GpuSimulation;
+use crate::sprite_atlas::SpriteAtlas;
+use crate::texture::Texture;
+use crate::types::{Vertex, Uniforms, Particle, ParticleInstance, TrailSegment};
 use glam::{Mat4, Vec3};
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::VecDeque;
 use wgpu::util::DeviceExt;
 
-pub struct ParticleSystem {
+// When an emitter's particles are due: its continuous rate plus its bursts. Shared by the
+// CPU and GPU simulations so both spawn the same counts
+pub struct SpawnSchedule {
+    // Fraction of a particle the continuous rate still owes
+    spawn_debt: f32,
+    // When each of the config's bursts fires next; None once it's done
+    next_bursts: Vec<Option<f32>>,
+}
+
+impl SpawnSchedule {
+    pub fn new(config: &EmitterConfig) -> Self {
+        Self { spawn_debt: 0.0, next_bursts: config.bursts.iter().map(|burst| Some(burst.time)).collect() }
+    }
+
+    // Particles due in the step of `dt` ending at `time`
+    pub fn due(&mut self, config: &EmitterConfig, dt: f32, time: f32) -> usize {
+        // Continuous rate, carrying fractions over so low rates still spawn
+        self.spawn_debt += config.rate * dt;
+        let mut desired = self.spawn_debt.floor();
+        self.spawn_debt -= desired;
+        for (burst, next) in config.bursts.iter().zip(&mut self.next_bursts) {
+            while let Some(at) = *next {
+                if at > time || time.is_nan() { break; }
+                desired += burst.count as f32;
+                // A repeat too short to move the time on ends the burst rather than spinning
+                *next = burst.interval.map(|interval| at + interval).filter(|&after| after > at);
+            }
+        }
+        desired as usize
+    }
+}
+
+// One configured emitter and the particles it has alive
+pub struct Emitter {
+    pub config: EmitterConfig,
     pub particles: Vec<Particle>,
-    pub max_particles: usize,
+    schedule: SpawnSchedule,
+    // Which emitter each of the config's sub-emitters spawns from, if it names one
+    sub_targets: Vec<Option<usize>>,
+}
+
+// Particles a sub-emitter owes another emitter, queued during a step and spawned at its end
+struct SpawnEvent {
+    emitter: usize,
+    count: u32,
+    position: Vec3,
+    // What the particles inherit
+    velocity: Vec3,
+}
+
+// The step every emitter takes, and the scene it takes it in
+struct StepContext<'a> {
+    dt: f32,
+    // The simulation clock after this step
+    time: f32,
+    mesh: &'a EmissionMesh,
+    fields: &'a [FieldConfig],
+    colliders: &'a Colliders,
+}
+
+// CPU-side particle state, kept apart from the GPU resources so it can run without a device.
+// Everything it does follows from the config, the seed and the dt sequence: it keeps its own
+// clock and uses ChaCha8, whose stream (unlike StdRng's) is fixed across platforms and rand releases
+pub struct ParticleSimulation {
+    pub emitters: Vec<Emitter>,
+    // Surface for mesh-surface emitters
+    mesh: EmissionMesh,
+    fields: Vec<FieldConfig>,
+    colliders: Colliders,
+    rng: ChaCha8Rng,
+    // Simulated seconds, the sum of every dt so far
+    time: f32,
+}
+
+// Where the particles are stepped; both end up in the same instanced draw. The simulations
+// are boxed, being far larger than the buffers beside them
+enum Backend {
+    // Instances and trail segments are built on the CPU and uploaded every frame
+    Cpu { simulation: Box<ParticleSimulation>, instance_buffer: wgpu::Buffer, trail_buffer: wgpu::Buffer },
+    // Instances and their count are written on the GPU, and drawn indirectly
+    Gpu(Box<GpuSimulation>),
+}
+
+// The renderer's GPU objects the particles are built on
+pub struct ParticleContext<'a> {
+    pub device: &'a wgpu::Device,
+    pub queue: &'a wgpu::Queue,
+    // What the particles draw into
+    pub surface_format: wgpu::TextureFormat,
+    // The uniforms layout at group 0, shared with the model
+    pub bind_group_layout: &'a wgpu::BindGroupLayout,
+}
+
+// What a frame draws the particles with and into
+pub struct ParticleFrame<'a> {
+    pub target: &'a wgpu::TextureView,
+    // The model pass's depth, for hiding and softening particles against the model
+    pub depth_view: &'a wgpu::TextureView,
+    pub projection: Mat4,
+    pub view: Mat4,
+    // Simulated seconds at this frame
+    pub time: f32,
+    // Fraction of the way from the previous step to the current one
+    pub interpolation: f32,
+}
+
+pub struct ParticleSystem {
+    backend: Backend,
     pipeline: wgpu::RenderPipeline,
+    // Draws trail segments, when some emitter leaves trails
+    trail_pipeline: wgpu::RenderPipeline,
+    trails: bool,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
-    instance_buffer: wgpu::Buffer,
     uniform_buffer: wgpu::Buffer,
     bind_group: wgpu::BindGroup,
+    // The sprite atlas (group 1)
+    sprite_bind_group: wgpu::BindGroup,
+    // Per emitter, where its sheet is in the atlas
+    sprite_regions: Vec<Option<[f32; 4]>>,
+    // The model pass's depth (group 2), bound each frame since each frame has its own
+    scene_bind_group_layout: wgpu::BindGroupLayout,
+    scene_uniform_buffer: wgpu::Buffer,
+    // Draw back to front, for emitters whose blending depends on order
+    sorted: bool,
+}
+
+impl Emitter {
+    fn new(config: EmitterConfig, sub_targets: Vec<Option<usize>>) -> Self {
+        let schedule = SpawnSchedule::new(&config);
+        Self { config, particles: Vec::new(), schedule, sub_targets }
+    }
+
+    // Sub-emitter spawns go on `events`
+    fn update(&mut self, step: &StepContext, rng: &mut ChaCha8Rng, events: &mut Vec<SpawnEvent>) {
+        let StepContext { dt, time, mesh, fields, colliders } = *step;
+        let config = &self.config;
+        let forces = &config.forces;
+        let mut fire = |event: SubEmitterEvent, position: [f32; 3], velocity: [f32; 3]| {
+            for (sub, &target) in config.sub_emitters.iter().zip(&self.sub_targets) {
+                if let (true, Some(emitter)) = (sub.event == event, target) {
+                    events.push(SpawnEvent { emitter, count: sub.count, position: position.into(), velocity: Vec3::from(velocity) * sub.inherit_velocity });
+                }
+            }
+        };
+
+        // Update existing with constant acceleration, drag and lateral turbulence
+        self.particles.retain_mut(|p| {
+            p.life -= dt;
+            if p.life <= 0.0 {
+                fire(SubEmitterEvent::Death, p.position, p.velocity);
+                return false;
+            }
+            p.prev_position = p.position;
+            // Integrate position
+            p.position[0] += p.velocity[0] * dt;
+            p.position[1] += p.velocity[1] * dt;
+            p.position[2] += p.velocity[2] * dt;
+            // Stop at the first collider crossed, bouncing off or dying there
+            if let Some(hit) = colliders.hit(Vec3::from(p.prev_position), Vec3::from(p.position)) {
+                p.position = hit.position.into();
+                let Some(velocity) = collision::respond(&colliders.configs[hit.collider], Vec3::from(p.velocity), hit.normal) else {
+                    fire(SubEmitterEvent::Collision, p.position, p.velocity);
+                    fire(SubEmitterEvent::Death, p.position, p.velocity);
+                    return false;
+                };
+                p.velocity = velocity.into();
+                fire(SubEmitterEvent::Collision, p.position, p.velocity);
+            }
+            // Where it ended up, for its trail
+            if config.trail.segments() > 0 {
+                p.trail.push_front(p.position);
+                p.trail.truncate(config.trail.points as usize);
+            }
+            // Buoyancy / gravity
+            for axis in 0..3 {
+                p.velocity[axis] += forces.acceleration[axis] * dt;
+            }
+            // Lateral turbulence (swirl)
+            let angle = p.phase + time * forces.swirl.frequency + p.position[1] * forces.swirl.height_scale;
+            p.velocity[0] += forces.swirl.amplitude * angle.sin() * dt;
+            p.velocity[2] += forces.swirl.amplitude * angle.cos() * dt;
+            // Strict X waver
+            let w = (time * p.waver_freq).sin();
+            p.velocity[0] += p.waver_amp * w * dt;
+            // Drag
+            for axis in 0..3 {
+                p.velocity[axis] *= 1.0 - forces.drag[axis] * dt;
+            }
+            // The scene's force fields
+            if !fields.is_empty() {
+                p.velocity = force_field::apply(fields, Vec3::from(p.position), Vec3::from(p.velocity), time, dt).into();
+            }
+            // Slight growth over life
+            p.size += config.growth * dt;
+            p.rotation += p.angular_velocity * dt;
+            true
+        });
+
+        for _ in 0..self.schedule.due(config, dt, time) {
+            if self.particles.len() >= config.max_particles { break; }
+            let (pos, outward) = config.shape.sample(rng, mesh);
+            let p = spawn(config, rng, pos, outward, Vec3::ZERO);
+            fire(SubEmitterEvent::Birth, p.position, p.velocity);
+            self.particles.push(p);
+        }
+    }
+
+    // The particles `event` owes this emitter, bursting out of where it happened
+    fn spawn_from(&mut self, event: &SpawnEvent, rng: &mut ChaCha8Rng, mesh: &EmissionMesh) {
+        // A sphere with no radius gives the point and a direction to head off in
+        let shape = EmitterShape::Sphere { center: event.position.into(), radius: 0.0 };
+        for _ in 0..event.count {
+            if self.particles.len() >= self.config.max_particles { break; }
+            let (pos, outward) = shape.sample(rng, mesh);
+            self.particles.push(spawn(&self.config, rng, pos, outward, event.velocity));
+        }
+    }
+}
+
+// A new particle of `config`'s at `pos`, with `outward` setting the way its radial speed takes
+// it and `inherited` added to its velocity
+fn spawn(config: &EmitterConfig, rng: &mut ChaCha8Rng, pos: Vec3, outward: Vec3, inherited: Vec3) -> Particle {
+    let forces = &config.forces;
+    let speed = sample_range(rng, config.velocity.speed);
+    let radial = outward * sample_range(rng, config.velocity.radial);
+    let vel = Vec3::from(config.velocity.direction) * speed + radial + inherited;
+    let life = sample_range(rng, config.lifetime);
+
+    Particle {
+        position: [pos.x, pos.y, pos.z],
+        prev_position: [pos.x, pos.y, pos.z],
+        velocity: [vel.x, vel.y, vel.z],
+        life,
+        // Its own lifetime, so every particle runs the whole length of the appearance curves.
+        // Kept off zero for particles that die as they're born
+        max_life: life.max(1e-6),
+        size: sample_range(rng, config.size),
+        phase: rng.gen_range(0.0..std::f32::consts::TAU),
+        waver_amp: sample_range(rng, forces.waver.amplitude),
+        waver_freq: sample_range(rng, forces.waver.frequency),
+        // Only drawn when asked for, so emitters without flipbooks keep their stream
+        start_frame: if config.sprite.random_start_frame { rng.gen_range(0..config.sprite.frame_count()) as f32 } else { 0.0 },
+        rotation: sample_range(rng, config.rotation),
+        angular_velocity: sample_range(rng, config.angular_velocity),
+        trail: if config.trail.segments() > 0 { VecDeque::from([[pos.x, pos.y, pos.z]]) } else { VecDeque::new() },
+    }
+}
+
+impl ParticleSimulation {
+    pub fn new(config: &ParticleConfig, mesh: EmissionMesh, seed: u64) -> Self {
+        Self {
+            emitters: config.emitters.iter().map(|emitter| {
+                let sub_targets = emitter.sub_emitters.iter().map(|sub| config.emitter_index(&sub.emitter)).collect();
+                Emitter::new(emitter.clone(), sub_targets)
+            }).collect(),
+            fields: config.fields.clone(),
+            colliders: Colliders::new(&config.colliders, mesh.triangles()),
+            mesh,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            time: 0.0,
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        let step = StepContext { dt, time: self.time, mesh: &self.mesh, fields: &self.fields, colliders: &self.colliders };
+        let mut events = Vec::new();
+        for emitter in &mut self.emitters {
+            emitter.update(&step, &mut self.rng, &mut events);
+        }
+        for event in &events {
+            self.emitters[event.emitter].spawn_from(event, &mut self.rng, &self.mesh);
+        }
+    }
+
+    // Where the model is now, relative to the pose its mesh was captured in
+    pub fn set_model_transform(&mut self, transform: Mat4) {
+        self.colliders.set_model_transform(transform);
+        self.mesh.set_model_transform(transform);
+    }
+
+    // Every live particle, emitter by emitter
+    pub fn particles(&self) -> impl Iterator<Item = &Particle> {
+        self.emitters.iter().flat_map(|emitter| &emitter.particles)
+    }
+
+    pub fn max_particles(&self) -> usize {
+        self.emitters.iter().map(|emitter| emitter.config.max_particles).sum()
+    }
+
+    // Draw data for every live particle
+    fn instances(&self, interpolation: f32, sprite_regions: &[Option<[f32; 4]>]) -> Vec<ParticleInstance> {
+        self.emitters.iter().zip(sprite_regions).flat_map(|(emitter, &region)| {
+            emitter.particles.iter().map(move |p| instance(&emitter.config, region, p, interpolation))
+        }).collect()
+    }
+
+    // Segments of every trail, each running from where its particle is drawn back to the tail
+    fn trail_segments(&self, interpolation: f32, sprite_regions: &[Option<[f32; 4]>]) -> Vec<TrailSegment> {
+        let mut segments = Vec::new();
+        for (emitter, &region) in self.emitters.iter().zip(sprite_regions) {
+            let trail = &emitter.config.trail;
+            if trail.segments() == 0 {
+                continue;
+            }
+            for p in &emitter.particles {
+                let head = instance(&emitter.config, region, p, interpolation);
+                let points: Vec<Vec3> = std::iter::once(Vec3::from(head.position)).chain(p.trail.iter().skip(1).map(|&point| Vec3::from(point))).collect();
+                for k in 0..points.len() - 1 {
+                    let (start, end) = (points[k], points[k + 1]);
+                    // Carried straight on past the ends
+                    let previous = if k > 0 { points[k - 1] } else { 2.0 * start - end };
+                    let next = points.get(k + 2).copied().unwrap_or(2.0 * end - start);
+                    segments.push(TrailSegment {
+                        previous: previous.into(),
+                        start: start.into(),
+                        end: end.into(),
+                        next: next.into(),
+                        width: head.size * trail.width,
+                        color: head.color,
+                        along: [k as f32 / trail.segments() as f32, (k + 1) as f32 / trail.segments() as f32],
+                        uv_rect: head.uv_rect,
+                        blend: head.blend,
+                        softness: head.softness,
+                    });
+                }
+            }
+        }
+        segments
+    }
+
+    // Trail segments drawn at most, every emitter full
+    fn max_trail_segments(&self) -> usize {
+        self.emitters.iter().map(|emitter| emitter.config.max_particles * emitter.config.trail.segments() as usize).sum()
+    }
+}
+
+// How `p` is drawn, styled by its emitter's curves and sprite
+fn instance(config: &EmitterConfig, sprite_region: Option<[f32; 4]>, p: &Particle, interpolation: f32) -> ParticleInstance {
+    let appearance = &config.appearance;
+    let sprite = &config.sprite;
+    let age = 1.0 - p.life / p.max_life;
+    let color = appearance.color.sample(age);
+    let position = Vec3::from(p.prev_position).lerp(Vec3::from(p.position), interpolation);
+    let axis = match config.orientation {
+        Orientation::ViewFacing => Vec3::ZERO,
+        Orientation::VelocityStretch { stretch } => Vec3::from(p.velocity) * stretch,
+        Orientation::FixedAxis { axis } => Vec3::from(axis).normalize(),
+        Orientation::WorldAligned { normal } => Vec3::from(normal).normalize(),
+    };
+    ParticleInstance {
+        position: position.into(),
+        size: p.size * appearance.size.sample(age),
+        color: [color.x, color.y, color.z, appearance.alpha.sample(age)],
+        rotation: p.rotation + appearance.rotation.sample(age),
+        uv_rect: sprite_region.map_or([0.0; 4], |sheet| sprite.frame_rect(sheet, sprite.frame(age, p.start_frame))),
+        blend: sprite.blend as u32 as f32,
+        softness: sprite.softness,
+        orientation: config.orientation.index() as f32,
+        axis: axis.into(),
+    }
+}
+
+// Farthest from the camera first, so blended particles composite over whatever is behind them
+fn sort_back_to_front(instances: &mut [ParticleInstance], view: Mat4) {
+    let depth = |instance: &ParticleInstance| view.transform_point3(Vec3::from(instance.position)).z;
+    instances.sort_by(|a, b| depth(a).total_cmp(&depth(b)));
 }
 
 impl ParticleSystem {
-    pub fn new(device: &wgpu::Device, surface_format: wgpu::TextureFormat, bind_group_layout: &wgpu::BindGroupLayout) -> Self {
+    // Simulates on the backend `config` asks for, falling back to the CPU when the GPU can't
+    pub fn new(context: &ParticleContext, config: &ParticleConfig, mesh: EmissionMesh, seed: u64) -> Self {
+        let ParticleContext { device, queue, surface_format, bind_group_layout } = *context;
         // Quad geometry for particles
         let particle_vertices = vec![
-            Vertex { position: [-0.5, -0.5, 0.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5, -0.5, 0.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [ 0.5,  0.5, 0.0], normal: [0.0, 0.0, 1.0] },
-            Vertex { position: [-0.5,  0.5, 0.0], normal: [0.0, 0.0, 1.0] },
+            Vertex { position: [-0.5, -0.5, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 1.0] },
+            Vertex { position: [ 0.5, -0.5, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 1.0] },
+            Vertex { position: [ 0.5,  0.5, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 0.0] },
+            Vertex { position: [-0.5,  0.5, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0] },
         ];
         let particle_indices: Vec<u16> = vec![0, 1, 2, 0, 2, 3];
 
@@ -36,13 +405,41 @@ impl ParticleSystem {
             usage: wgpu::BufferUsages::INDEX,
         });
 
-        let max_particles = 5000usize;
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Particle Instance Buffer"),
-            size: (max_particles * std::mem::size_of::<ParticleInstance>()) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        // Every emitter's texture in one atlas, so all emitters share one (sortable) draw
+        let atlas = SpriteAtlas::build(config, device.limits().max_texture_dimension_2d);
+
+        let gpu_support = match config.simulation {
+            SimulationBackend::Cpu => Err("CPU simulation requested".to_string()),
+            SimulationBackend::Auto | SimulationBackend::Gpu => GpuSimulation::check_support(device, config),
+        };
+        let backend = match gpu_support {
+            Ok(()) => {
+                println!("✨ Simulating particles on the GPU");
+                println!("🎲 Particle seed: {} (replay with --seed {} --simulation gpu)", seed, seed);
+                Backend::Gpu(Box::new(GpuSimulation::new(device, queue, config, &mesh, seed, &atlas.regions)))
+            }
+            Err(reason) => {
+                if config.simulation == SimulationBackend::Gpu {
+                    println!("⚠️ GPU particle simulation unavailable ({}), falling back to the CPU", reason);
+                }
+                println!("✨ Simulating particles on the CPU");
+                println!("🎲 Particle seed: {} (replay with --seed {} --simulation cpu)", seed, seed);
+                let simulation = ParticleSimulation::new(config, mesh, seed);
+                let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Particle Instance Buffer"),
+                    size: (simulation.max_particles().max(1) * std::mem::size_of::<ParticleInstance>()) as u64,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                let trail_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Particle Trail Buffer"),
+                    size: (simulation.max_trail_segments().max(1) * std::mem::size_of::<TrailSegment>()) as u64,
+                    usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                Backend::Cpu { simulation: Box::new(simulation), instance_buffer, trail_buffer }
+            }
+        };
 
         let uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Particle Uniform Buffer"),
@@ -57,131 +454,158 @@ impl ParticleSystem {
             label: Some("particle_bind_group"),
         });
 
+        let sprite_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("particle_sprite_bind_group_layout"),
+        });
+        let atlas_texture = Texture::from_data(device, queue, &atlas.data, "Particle Sprite Atlas");
+        let sprite_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &sprite_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&atlas_texture.view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&atlas_texture.sampler) },
+            ],
+            label: Some("particle_sprite_bind_group"),
+        });
+
         let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
             label: Some("Particle Shader"),
             source: wgpu::ShaderSource::Wgsl(include_str!("shaders/particle.wgsl").into()),
         });
 
+        // Scene depth for soft particles, what turns it back into distance from the camera, and
+        // where the camera is and which ways are right and up on screen, for turning quads and
+        // trails toward it
+        let scene_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                    count: None,
+                },
+            ],
+            label: Some("particle_scene_bind_group_layout"),
+        });
+        let scene_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Scene Uniform Buffer"),
+            size: 64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
         let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
             label: Some("Particle Pipeline Layout"),
-            bind_group_layouts: &[bind_group_layout],
+            bind_group_layouts: &[bind_group_layout, &sprite_bind_group_layout, &scene_bind_group_layout],
             immediate_size: 0,
         });
 
-        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-            label: Some("Particle Pipeline"),
-            layout: Some(&pipeline_layout),
+        let pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, surface_format, "vs_main", ParticleInstance::desc());
+        let trail_pipeline = Self::create_pipeline(device, &pipeline_layout, &shader, surface_format, "vs_trail", TrailSegment::desc());
+
+        Self {
+            backend,
+            pipeline,
+            trail_pipeline,
+            trails: config.emitters.iter().any(|emitter| emitter.trail.segments() > 0),
+            vertex_buffer,
+            index_buffer,
+            uniform_buffer,
+            bind_group,
+            sprite_bind_group,
+            sprite_regions: atlas.regions,
+            scene_bind_group_layout,
+            scene_uniform_buffer,
+            sorted: config.sorts(),
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        layout: &wgpu::PipelineLayout,
+        shader: &wgpu::ShaderModule,
+        surface_format: wgpu::TextureFormat,
+        // Particle quads or trail segments, and their instance data
+        vertex_entry: &str,
+        instances: wgpu::VertexBufferLayout<'static>,
+    ) -> wgpu::RenderPipeline {
+        // The shader premultiplies every particle by its own blend mode, additive ones with
+        // zero alpha, so emitters of any mode can be drawn (and sorted) together
+        let blend = wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING;
+
+        device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(vertex_entry),
+            layout: Some(layout),
             vertex: wgpu::VertexState {
-                module: &shader,
-                entry_point: Some("vs_main"),
-                buffers: &[Vertex::desc(), ParticleInstance::desc()],
+                module: shader,
+                entry_point: Some(vertex_entry),
+                buffers: &[Vertex::desc(), instances],
                 compilation_options: Default::default(),
             },
             fragment: Some(wgpu::FragmentState {
-                module: &shader,
+                module: shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(wgpu::ColorTargetState {
                     format: surface_format,
-                    blend: Some(wgpu::BlendState {
-                        color: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::SrcAlpha, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
-                        alpha: wgpu::BlendComponent { src_factor: wgpu::BlendFactor::One, dst_factor: wgpu::BlendFactor::One, operation: wgpu::BlendOperation::Add },
-                    }),
+                    blend: Some(blend),
                     write_mask: wgpu::ColorWrites::ALL,
                 })],
                 compilation_options: Default::default(),
             }),
             primitive: wgpu::PrimitiveState { topology: wgpu::PrimitiveTopology::TriangleList, strip_index_format: None, front_face: wgpu::FrontFace::Ccw, cull_mode: None, polygon_mode: wgpu::PolygonMode::Fill, unclipped_depth: false, conservative: false },
-            depth_stencil: Some(wgpu::DepthStencilState { format: wgpu::TextureFormat::Depth32Float, depth_write_enabled: false, depth_compare: wgpu::CompareFunction::Less, stencil: wgpu::StencilState::default(), bias: wgpu::DepthBiasState::default() }),
+            // Hidden or faded by the scene depth in the shader: not every backend can read a depth
+            // buffer that's also attached
+            depth_stencil: None,
             multisample: wgpu::MultisampleState { count: 1, mask: !0, alpha_to_coverage_enabled: false },
             multiview_mask: Default::default(),
             cache: None,
-        });
+        })
+    }
 
-        Self {
-            particles: Vec::new(),
-            max_particles,
-            pipeline,
-            vertex_buffer,
-            index_buffer,
-            instance_buffer,
-            uniform_buffer,
-            bind_group,
+    // Moves mesh emitters and colliders with the model; applies from the next update
+    pub fn set_model_transform(&mut self, transform: Mat4) {
+        match &mut self.backend {
+            Backend::Cpu { simulation, .. } => simulation.set_model_transform(transform),
+            Backend::Gpu(simulation) => simulation.set_model_transform(transform),
         }
     }
 
-    pub fn update(&mut self, dt: f32, time: f32) {
-        let mut rng = rand::thread_rng();
-
-        // Update existing with upward drift and lateral turbulence
-        self.particles.retain_mut(|p| {
-            p.life -= dt;
-            if p.life <= 0.0 { return false; }
-            // Integrate position
-            p.position[0] += p.velocity[0] * dt;
-            p.position[1] += p.velocity[1] * dt;
-            p.position[2] += p.velocity[2] * dt;
-            // Buoyancy upward
-            p.velocity[1] += 80.0 * dt;
-            // Lateral turbulence (swirl)
-            let swirl_amp = 30.0f32;
-            let swirl_freq = 3.5f32;
-            let angle = p.phase + time * swirl_freq + p.position[1] * 0.01;
-            p.velocity[0] += swirl_amp * angle.sin() * dt;
-            p.velocity[2] += swirl_amp * angle.cos() * dt;
-            // Strict X waver
-            let w = (time * p.waver_freq).sin();
-            p.velocity[0] += p.waver_amp * w * dt;
-            // Mild drag
-            p.velocity[0] *= 1.0 - 0.25 * dt;
-            p.velocity[2] *= 1.0 - 0.25 * dt;
-            // Slight growth over life
-            p.size += 2.5 * dt;
-            true
-        });
-
-        // Spawn rate (denser base; multiple per frame)
-        let spawn_rate = 900.0; // particles per second
-        let desired = (spawn_rate * dt).floor() as usize;
-        for _ in 0..desired {
-            if self.particles.len() >= self.max_particles { break; }
-            // Disk emitter behind mailbox
-            let center = Vec3::new(0.0, -50.0, -300.0);
-            let disk_radius = 80.0f32;
-            let angle = rng.gen_range(0.0..(std::f32::consts::TAU));
-            let r = rng.gen_range(0.0..disk_radius);
-            let pos = center + Vec3::new(r * angle.cos(), 0.0, r * angle.sin());
-
-            // Upward-biased velocity
-            let upward = Vec3::new(0.0, rng.gen_range(180.0..260.0), 0.0);
-            // Mild outward spread
-            let radial = (pos - center).normalize_or_zero() * rng.gen_range(15.0..40.0);
-            let vel = upward + radial;
-
-            let p = Particle {
-                position: [pos.x, pos.y, pos.z],
-                velocity: [vel.x, vel.y, vel.z],
-                life: rng.gen_range(1.2..2.2),
-                max_life: 2.2,
-                size: rng.gen_range(6.0..12.0),
-                phase: rng.gen_range(0.0..std::f32::consts::TAU),
-                waver_amp: rng.gen_range(50.0..120.0),
-                waver_freq: rng.gen_range(3.0..7.5),
-            };
-            self.particles.push(p);
+    pub fn update(&mut self, dt: f32) {
+        match &mut self.backend {
+            Backend::Cpu { simulation, .. } => simulation.update(dt),
+            Backend::Gpu(simulation) => simulation.update(dt),
         }
     }
 
-    pub fn render(&mut self,
-        queue: &wgpu::Queue,
-        encoder: &mut wgpu::CommandEncoder,
-        texture_view: &wgpu::TextureView,
-        depth_view: &wgpu::TextureView,
-        projection: Mat4,
-        view: Mat4,
-        time: f32,
-    ) {
-        if self.particles.is_empty() { return; }
-
+    pub fn render(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, encoder: &mut wgpu::CommandEncoder, frame: &ParticleFrame) {
+        let ParticleFrame { target, depth_view, projection, view, time, interpolation } = *frame;
         // Write uniforms (camera-only MVP, time in model translation.x)
         let p_mvp = projection * view * Mat4::IDENTITY;
         let uniforms = Uniforms {
@@ -190,40 +614,296 @@ impl ParticleSystem {
             base_color: [1.0, 0.5, 0.0, 1.0],
         };
         queue.write_buffer(&self.uniform_buffer, 0, bytemuck::cast_slice(&[uniforms]));
+        // Depth d is at distance m32 / (d + m22) from the camera
+        let camera = view.inverse();
+        let scene = [[projection.z_axis.z, projection.w_axis.z, 0.0, 0.0], camera.w_axis.to_array(), camera.x_axis.to_array(), camera.y_axis.to_array()];
+        queue.write_buffer(&self.scene_uniform_buffer, 0, bytemuck::cast_slice(&scene));
 
-        // Build instances buffer
-        let instances: Vec<ParticleInstance> = self.particles.iter().map(|p| {
-            let t = p.life / p.max_life;
-            let (r, g, b) = if t > 0.7 { (1.0, 0.95, 0.7) } else if t > 0.4 { (1.0, 0.6, 0.2) } else { (1.0, 0.2, 0.05) };
-            let size_curve = (t * (1.0 - t)) * 3.2;
-            let alpha = (t * 1.3).clamp(0.35, 1.0);
-            ParticleInstance { position: p.position, size: p.size * size_curve, color: [r, g, b, alpha] }
-        }).collect();
-        queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        // Instances and trail segments for this frame, and how many of each to draw (on the GPU
+        // path only the GPU knows)
+        let counts = match &mut self.backend {
+            Backend::Cpu { simulation, instance_buffer, trail_buffer } => {
+                if simulation.particles().next().is_none() { return; }
+                let mut instances = simulation.instances(interpolation, &self.sprite_regions);
+                if self.sorted {
+                    sort_back_to_front(&mut instances, view);
+                }
+                queue.write_buffer(instance_buffer, 0, bytemuck::cast_slice(&instances));
+                let segments = simulation.trail_segments(interpolation, &self.sprite_regions);
+                if !segments.is_empty() {
+                    queue.write_buffer(trail_buffer, 0, bytemuck::cast_slice(&segments));
+                }
+                Some((instances.len() as u32, segments.len() as u32))
+            }
+            Backend::Gpu(simulation) => {
+                simulation.prepare_draw(encoder, interpolation, view);
+                None
+            }
+        };
+
+        let scene_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &self.scene_bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(depth_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: self.scene_uniform_buffer.as_entire_binding() },
+            ],
+            label: Some("particle_scene_bind_group"),
+        });
 
         let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("Particle Pass"),
             color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: texture_view,
+                view: target,
                 depth_slice: None,
                 resolve_target: None,
                 ops: wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store },
             })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: depth_view,
-                depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Load, store: wgpu::StoreOp::Store }),
-                stencil_ops: None,
-            }),
+            // The shader tests against the scene depth itself, reading it from a texture
+            depth_stencil_attachment: None,
             timestamp_writes: None,
             occlusion_query_set: None,
             multiview_mask: None,
         });
 
-        pass.set_pipeline(&self.pipeline);
         pass.set_bind_group(0, &self.bind_group, &[]);
+        pass.set_bind_group(1, &self.sprite_bind_group, &[]);
+        pass.set_bind_group(2, &scene_bind_group, &[]);
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
         pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        pass.draw_indexed(0..6, 0, 0..instances.len() as u32);
+        // Trails go under the particles, and aren't sorted
+        if self.trails {
+            pass.set_pipeline(&self.trail_pipeline);
+            match (&self.backend, counts) {
+                (Backend::Cpu { trail_buffer, .. }, Some((_, count))) => {
+                    pass.set_vertex_buffer(1, trail_buffer.slice(..));
+                    pass.draw_indexed(0..6, 0, 0..count);
+                }
+                (Backend::Gpu(simulation), _) => {
+                    pass.set_vertex_buffer(1, simulation.trail_buffer.slice(..));
+                    pass.draw_indexed_indirect(&simulation.indirect_buffer, simulation.trail_draw_args_offset());
+                }
+                _ => {}
+            }
+        }
+        pass.set_pipeline(&self.pipeline);
+        match (&self.backend, counts) {
+            (Backend::Cpu { instance_buffer, .. }, Some((count, _))) => {
+                pass.set_vertex_buffer(1, instance_buffer.slice(..));
+                pass.draw_indexed(0..6, 0, 0..count);
+            }
+            (Backend::Gpu(simulation), _) => {
+                pass.set_vertex_buffer(1, simulation.instance_buffer.slice(..));
+                pass.draw_indexed_indirect(&simulation.indirect_buffer, simulation.draw_args_offset());
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{Curve, Interpolation};
+    use crate::emitter::{Burst, ForceConfig, SubEmitterConfig, TrailConfig, VelocityConfig};
+    use bytemuck::Zeroable;
+
+    fn state_bits(simulation: &ParticleSimulation) -> Vec<u32> {
+        simulation.particles()
+            .flat_map(|p| {
+                let scalars = [p.life, p.max_life, p.size, p.phase, p.waver_amp, p.waver_freq, p.rotation, p.angular_velocity];
+                p.position.into_iter().chain(p.prev_position).chain(p.velocity).chain(scalars)
+            })
+            .map(f32::to_bits)
+            .collect()
+    }
+
+    fn run(seed: u64, dts: &[f32]) -> ParticleSimulation {
+        let mut simulation = ParticleSimulation::new(&ParticleConfig::default(), EmissionMesh::default(), seed);
+        for &dt in dts {
+            simulation.update(dt);
+        }
+        simulation
+    }
+
+    #[test]
+    fn same_seed_and_dts_give_identical_state() {
+        // Uneven steps, like a real frame clock
+        let dts: Vec<f32> = (0..240).map(|i| 1.0 / 60.0 + (i % 7) as f32 * 0.001).collect();
+        let a = run(42, &dts);
+        let b = run(42, &dts);
+        assert!(a.particles().next().is_some());
+        assert_eq!(state_bits(&a), state_bits(&b));
+    }
+
+    #[test]
+    fn bursts_fire_on_schedule_and_repeat() {
+        let emitter = EmitterConfig {
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.1, count: 5, interval: Some(0.5) }],
+            lifetime: [10.0, 10.0],
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut simulation = ParticleSimulation::new(&config, EmissionMesh::default(), 0);
+        let mut counts = Vec::new();
+        for _ in 0..12 {
+            simulation.update(0.1);
+            counts.push(simulation.particles().count());
+        }
+        // Fires at 0.1, 0.6 and 1.1 seconds (the last step is left out, it's on float error's edge)
+        assert_eq!(&counts[..10], [5, 5, 5, 5, 5, 10, 10, 10, 10, 10]);
+        assert_eq!(counts[11], 15);
+    }
+
+    #[test]
+    fn schedule_moves_on_from_bursts_validation_would_reject() {
+        // At a million seconds 1e-9 is far below a float's precision
+        let emitter = EmitterConfig { rate: 0.0, bursts: vec![Burst { time: 1e6, count: 3, interval: Some(1e-9) }], ..Default::default() };
+        let mut schedule = SpawnSchedule::new(&emitter);
+        assert_eq!(schedule.due(&emitter, 0.1, 1e6), 3);
+        assert_eq!(schedule.due(&emitter, 0.1, 2e6), 0);
+        let mut schedule = SpawnSchedule::new(&emitter);
+        assert_eq!(schedule.due(&emitter, 0.1, f32::NAN), 0);
+    }
+
+    #[test]
+    fn every_particle_starts_at_the_beginning_of_its_curves() {
+        // Fading out linearly over lives anywhere from one to two seconds
+        let mut emitter = EmitterConfig { rate: 0.0, bursts: vec![Burst { time: 0.0, count: 50, interval: None }], lifetime: [1.0, 2.0], ..Default::default() };
+        emitter.appearance.alpha = Curve::new(Interpolation::Linear, vec![(0.0, 1.0), (1.0, 0.0)]);
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut simulation = ParticleSimulation::new(&config, EmissionMesh::default(), 0);
+        simulation.update(0.1);
+        let emitter = &simulation.emitters[0];
+        assert_eq!(emitter.particles.len(), 50);
+        for p in &emitter.particles {
+            let alpha = instance(&emitter.config, None, p, 1.0).color[3];
+            assert!(alpha > 0.85, "life {} of {} already at alpha {}", p.life, p.max_life, alpha);
+            // And fades all the way out as it dies, whatever its lifetime
+            assert!((alpha - p.life / p.max_life).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn different_seeds_diverge() {
+        let dts = [1.0 / 60.0; 30];
+        assert_ne!(state_bits(&run(1, &dts)), state_bits(&run(2, &dts)));
+    }
+
+    #[test]
+    fn trails_follow_the_particle_back_along_its_path() {
+        // One particle moving along +x at 60 units a second, with nothing else acting on it
+        let emitter = EmitterConfig {
+            shape: EmitterShape::Point { position: [0.0; 3] },
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 1, interval: None }],
+            lifetime: [10.0, 10.0],
+            velocity: VelocityConfig { direction: [1.0, 0.0, 0.0], speed: [60.0, 60.0], radial: [0.0, 0.0] },
+            forces: ForceConfig::none(),
+            trail: TrailConfig { points: 4, width: 0.5 },
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut simulation = ParticleSimulation::new(&config, EmissionMesh::default(), 0);
+        let starts = |simulation: &ParticleSimulation| -> Vec<f32> {
+            simulation.trail_segments(1.0, &[None]).iter().map(|segment| segment.start[0]).collect()
+        };
+        simulation.update(0.1);
+        assert!(starts(&simulation).is_empty());
+        simulation.update(0.1);
+        assert_eq!(starts(&simulation).len(), 1);
+        // Capped at one segment fewer than the points remembered, newest first
+        for _ in 0..5 {
+            simulation.update(0.1);
+        }
+        let segments = simulation.trail_segments(1.0, &[None]);
+        let head = simulation.particles().next().unwrap().position[0];
+        for (k, segment) in segments.iter().enumerate() {
+            assert!((segment.start[0] - (head - 6.0 * k as f32)).abs() < 1e-3);
+            assert!((segment.end[0] - (head - 6.0 * (k + 1) as f32)).abs() < 1e-3);
+            assert_eq!(segment.along, [k as f32 / 3.0, (k + 1) as f32 / 3.0]);
+        }
+        assert_eq!(segments.len(), 3);
+        // The ends carry straight on
+        assert!((segments[0].previous[0] - (head + 6.0)).abs() < 1e-3);
+        assert!((segments[2].next[0] - (head - 24.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sub_emitters_spawn_where_particles_die() {
+        // Three particles heading along +x at 100 units a second for a quarter of a second
+        let sparks = EmitterConfig {
+            name: "sparks".to_string(),
+            shape: EmitterShape::Point { position: [0.0; 3] },
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 3, interval: None }],
+            lifetime: [0.25, 0.25],
+            velocity: VelocityConfig { direction: [1.0, 0.0, 0.0], speed: [100.0, 100.0], radial: [0.0, 0.0] },
+            forces: ForceConfig::none(),
+            sub_emitters: vec![SubEmitterConfig { event: SubEmitterEvent::Death, emitter: "puffs".to_string(), count: 2, inherit_velocity: 0.5 }],
+            ..Default::default()
+        };
+        // Only spawned by the sparks; its own birth events don't set it off again
+        let puffs = EmitterConfig {
+            name: "puffs".to_string(),
+            rate: 0.0,
+            lifetime: [10.0, 10.0],
+            velocity: VelocityConfig { speed: [0.0, 0.0], radial: [0.0, 0.0], ..Default::default() },
+            forces: ForceConfig::none(),
+            sub_emitters: vec![SubEmitterConfig { event: SubEmitterEvent::Birth, emitter: "puffs".to_string(), count: 1, inherit_velocity: 1.0 }],
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![sparks, puffs], ..Default::default() };
+        let mut simulation = ParticleSimulation::new(&config, EmissionMesh::default(), 0);
+        for _ in 0..3 {
+            simulation.update(0.1);
+        }
+        assert_eq!(simulation.emitters[1].particles.len(), 0);
+        // Dies on the fourth step, having moved on the second and third
+        simulation.update(0.1);
+        assert!(simulation.emitters[0].particles.is_empty());
+        let puffs = &simulation.emitters[1].particles;
+        assert_eq!(puffs.len(), 6);
+        for p in puffs {
+            assert!((Vec3::from(p.position) - Vec3::new(20.0, 0.0, 0.0)).length() < 1e-3, "spawned at {:?}", p.position);
+            assert_eq!(p.velocity, [50.0, 0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn particles_spin_and_stretch_along_their_motion() {
+        let emitter = EmitterConfig {
+            rate: 0.0,
+            bursts: vec![Burst { time: 0.0, count: 4, interval: None }],
+            lifetime: [10.0, 10.0],
+            rotation: [1.0, 1.0],
+            angular_velocity: [2.0, 2.0],
+            orientation: Orientation::VelocityStretch { stretch: 0.1 },
+            ..Default::default()
+        };
+        let config = ParticleConfig { emitters: vec![emitter], ..Default::default() };
+        let mut simulation = ParticleSimulation::new(&config, EmissionMesh::default(), 0);
+        for _ in 0..6 {
+            simulation.update(0.1);
+        }
+        // Born on the first step and turned on the five after
+        let instances = simulation.instances(1.0, &[None]);
+        assert_eq!(instances.len(), 4);
+        for (instance, p) in instances.iter().zip(simulation.particles()) {
+            assert!((instance.rotation - 2.0).abs() < 1e-5, "rotation {}", instance.rotation);
+            assert_eq!(instance.orientation, 1.0);
+            assert_eq!(Vec3::from(instance.axis), Vec3::from(p.velocity) * 0.1);
+        }
+    }
+
+    #[test]
+    fn sorts_the_farthest_particles_first() {
+        let at = |z: f32| ParticleInstance { position: [0.0, 0.0, z], ..Zeroable::zeroed() };
+        let mut instances = vec![at(1.0), at(-3.0), at(2.0)];
+        // Looking down -z from z = 5
+        let view = Mat4::look_at_rh(Vec3::new(0.0, 0.0, 5.0), Vec3::ZERO, Vec3::Y);
+        sort_back_to_front(&mut instances, view);
+        let order: Vec<f32> = instances.iter().map(|instance| instance.position[2]).collect();
+        assert_eq!(order, [-3.0, 1.0, 2.0]);
     }
 }
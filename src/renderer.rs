@@ -0,0 +1,338 @@
+// Everything drawn each frame (fire background, model, particles), independent of where
+// the frame ends up: the window's swapchain or an offscreen texture
+use crate::camera::Camera;
+use crate::emitter::{EmissionMesh, ParticleConfig};
+use crate::model::{BoundingSphere, ModelData, ModelFit, ModelRenderer, UpAxis};
+use crate::particles::{ParticleContext, ParticleFrame, ParticleSystem};
+use crate::types::{Uniforms, Vertex};
+use glam::{Mat4, Vec3};
+use wgpu::util::DeviceExt;
+
+// How the loaded model is fitted to the view unless told otherwise; the camera starts 800
+// units back
+pub const DEFAULT_MODEL_FIT: ModelFit = ModelFit {
+    up_axis: UpAxis::Y,
+    unit_scale: 1.0,
+    fit_radius: Some(200.0),
+};
+
+// Model spin in radians per simulated second, about both X and Y
+const ROTATION_SPEED: f32 = 0.6;
+
+// Which passes `render` draws; disabled passes leave the frame black underneath
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Passes {
+    pub background: bool,
+    pub model: bool,
+    pub particles: bool,
+}
+
+impl Passes {
+    pub const ALL: Passes = Passes { background: true, model: true, particles: true };
+}
+
+pub struct Renderer {
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub passes: Passes,
+    pub camera: Camera,
+    model_renderer: ModelRenderer,
+    // Recenters and scales the model to the view, from its bounds
+    model_fit: Mat4,
+    // Around the fitted model, which spins about its center so this holds as it turns
+    model_sphere: BoundingSphere,
+    // Background fire quad
+    background_pipeline: wgpu::RenderPipeline,
+    background_vertex_buffer: wgpu::Buffer,
+    background_index_buffer: wgpu::Buffer,
+    background_uniform_buffer: wgpu::Buffer,
+    background_bind_group: wgpu::BindGroup,
+    rotation: (f32, f32), // (x_rotation, y_rotation)
+    previous_rotation: (f32, f32),
+    // Simulated seconds so far, and the length of the last step
+    time: f32,
+    last_dt: f32,
+    // Particle system
+    particle_system: ParticleSystem,
+}
+
+impl Renderer {
+    // `color_format` is the format of the views passed to `render`; `model_fit` places the model
+    // in the scene, `particles` describes the emitters and `particle_seed` fixes their random stream
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, color_format: wgpu::TextureFormat, model: &ModelData, model_fit: ModelFit,
+               particles: &ParticleConfig, particle_seed: u64) -> Self {
+        // Create uniform bind group layout (shared by every pipeline at group 0)
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer { ty: wgpu::BufferBindingType::Uniform, has_dynamic_offset: false, min_binding_size: None },
+                count: None,
+            }],
+            label: Some("uniform_bind_group_layout"),
+        });
+
+        // Upload the model with its materials
+        let model_renderer = ModelRenderer::new(&device, &queue, color_format, &bind_group_layout, model);
+        let model_fit = model_fit.transform(&model.bounds);
+        let sphere = model.bounds.sphere;
+        let model_sphere = BoundingSphere {
+            center: model_fit.transform_point3(sphere.center),
+            radius: model_fit.transform_vector3(Vec3::X * sphere.radius).length(),
+        };
+
+        // Load shaders
+        let fire_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Fire Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("shaders/fire.wgsl").into()),
+        });
+
+        // Create fullscreen quad vertices for background fire effect
+        let background_vertices = vec![
+            Vertex { position: [-1.0, -1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 1.0] }, // Bottom-left
+            Vertex { position: [1.0, -1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 1.0] },  // Bottom-right
+            Vertex { position: [1.0, 1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [1.0, 0.0] },   // Top-right
+            Vertex { position: [-1.0, 1.0, 0.0], normal: [0.0, 0.0, 1.0], uv: [0.0, 0.0] },  // Top-left
+        ];
+        let background_indices: Vec<u16> = vec![0, 1, 2, 0, 2, 3];
+
+        // Create background vertex buffer
+        let background_vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background Vertex Buffer"),
+            contents: bytemuck::cast_slice(&background_vertices),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        // Create background index buffer
+        let background_index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Background Index Buffer"),
+            contents: bytemuck::cast_slice(&background_indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        // Create background uniform buffer
+        let background_uniform_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Background Uniform Buffer"),
+            size: std::mem::size_of::<Uniforms>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        // Create background bind group
+        let background_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: background_uniform_buffer.as_entire_binding(),
+            }],
+            label: Some("background_bind_group"),
+        });
+
+        // Create background render pipeline with fire shader (uniforms only, no texture group)
+        let background_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Background Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            immediate_size: 0,
+        });
+        let background_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Background Fire Pipeline"),
+            layout: Some(&background_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &fire_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex::desc()],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &fire_shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: color_format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None, // Background doesn't need depth testing
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview_mask: Default::default(),
+            cache: None,
+        });
+
+        // Initialize particle system
+        // Mesh-surface emitters spawn on the model where it's drawn at rest
+        let mesh = EmissionMesh::new(model, model_fit);
+        let context = ParticleContext { device: &device, queue: &queue, surface_format: color_format, bind_group_layout: &bind_group_layout };
+        let particle_system = ParticleSystem::new(&context, particles, mesh, particle_seed);
+
+        Self {
+            device,
+            queue,
+            passes: Passes::ALL,
+            camera: Camera::default(),
+            model_renderer,
+            model_fit,
+            model_sphere,
+            background_pipeline,
+            background_vertex_buffer,
+            background_index_buffer,
+            background_uniform_buffer,
+            background_bind_group,
+            rotation: (0.0, 0.0),
+            previous_rotation: (0.0, 0.0),
+            time: 0.0,
+            last_dt: 0.0,
+            particle_system,
+        }
+    }
+
+    // Advance the simulation (particles, model spin, shader clock) by one step of `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        self.previous_rotation = self.rotation;
+        self.rotation.0 += ROTATION_SPEED * dt; // Rotate around X axis
+        self.rotation.1 += ROTATION_SPEED * dt; // Rotate around Y axis
+        // Particles collide with the model where it is at the end of the step
+        self.particle_system.set_model_transform(spin(self.rotation));
+        self.particle_system.update(dt);
+        self.time += dt;
+        self.last_dt = dt;
+    }
+
+    // Point the camera at the model from just far enough back to see all of it
+    pub fn frame_model(&mut self) {
+        self.camera.frame(self.model_sphere);
+    }
+
+    // Draw one frame into `target`, a view of `color_format` and `size`. `interpolation` places
+    // the frame between the previous step (0.0) and the latest one (1.0)
+    pub fn render(&mut self, target: &wgpu::TextureView, size: (u32, u32), interpolation: f32) {
+        let time = self.time - self.last_dt * (1.0 - interpolation);
+        // Update MVP matrix
+        let aspect = size.0 as f32 / size.1 as f32;
+        let projection = self.camera.projection(aspect);
+        let view = self.camera.view();
+
+        // Fit the model to the view first so it spins about its own center
+        let lerp = |from: f32, to: f32| from + (to - from) * interpolation;
+        let rotation = (lerp(self.previous_rotation.0, self.rotation.0), lerp(self.previous_rotation.1, self.rotation.1));
+        let model = spin(rotation) * self.model_fit;
+        self.model_renderer.update(&self.queue, projection * view, model);
+
+        // Create depth texture (needed for 3D rendering)
+        let depth_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Depth32Float,
+            // Also read by the particle shader, to soften particles where they meet the model
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            label: Some("depth_texture"),
+            view_formats: &[],
+        });
+
+        let depth_view = depth_texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self.device.create_command_encoder(&Default::default());
+
+        // First pass: Render background fire effect
+        {
+            // Encode time in the model matrix translation to pass to shader
+            let time_matrix = Mat4::from_translation(Vec3::new(time, time * 0.5, 0.0));
+
+            let background_uniforms = Uniforms {
+                mvp_matrix: Mat4::IDENTITY.to_cols_array_2d(),
+                model_matrix: time_matrix.to_cols_array_2d(),
+                base_color: [1.0, 0.5, 0.0, 1.0], // Orange color base
+            };
+            self.queue.write_buffer(&self.background_uniform_buffer, 0, bytemuck::cast_slice(&[background_uniforms]));
+
+            let mut background_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Background Fire Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            // Still cleared when disabled, the later passes load from it
+            if self.passes.background {
+                background_pass.set_pipeline(&self.background_pipeline);
+                background_pass.set_bind_group(0, &self.background_bind_group, &[]);
+                background_pass.set_vertex_buffer(0, self.background_vertex_buffer.slice(..));
+                background_pass.set_index_buffer(self.background_index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                background_pass.draw_indexed(0..6, 0, 0..1);
+            }
+        }
+
+        // Second pass: Render 3D model on top (always run, it clears the depth buffer)
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Model Render Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    depth_slice: None,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load, // Keep the background we just rendered
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+                multiview_mask: None,
+            });
+
+            if self.passes.model {
+                self.model_renderer.draw(&mut render_pass);
+            }
+        }
+
+        // Third pass: Render fire particles
+        if self.passes.particles {
+            let frame = ParticleFrame { target, depth_view: &depth_view, projection, view, time, interpolation };
+            self.particle_system.render(&self.device, &self.queue, &mut encoder, &frame);
+        }
+
+        self.queue.submit([encoder.finish()]);
+    }
+}
+
+// The model's spin at (x_rotation, y_rotation), applied after fitting it to the view
+fn spin((x, y): (f32, f32)) -> Mat4 {
+    Mat4::from_rotation_y(y) * Mat4::from_rotation_x(x)
+}
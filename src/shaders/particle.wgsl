@@ -8,45 +8,214 @@ struct Uniforms {
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+// Every emitter's sprite texture
+@group(1) @binding(0)
+var sprite_atlas: texture_2d<f32>;
+@group(1) @binding(1)
+var sprite_sampler: sampler;
+
+// The model pass's depth, for hiding particles behind the model and fading them out where they
+// meet it. Bound as a plain float texture, which loads on every backend (GL can't load from
+// depth textures)
+@group(2) @binding(0)
+var scene_depth: texture_2d<f32>;
+
+struct Scene {
+    // Projection terms (m22, m32): depth d lies m32 / (d + m22) from the camera
+    depth_terms: vec4<f32>,
+    camera_position: vec4<f32>,
+    // World directions of the screen's right and up
+    camera_right: vec4<f32>,
+    camera_up: vec4<f32>,
+}
+
+@group(2) @binding(1)
+var<uniform> scene: Scene;
+
+// BlendMode indices
+const BLEND_ADDITIVE: u32 = 0u;
+const BLEND_PREMULTIPLIED: u32 = 2u;
+
+// Orientation indices
+const ORIENT_VELOCITY_STRETCH: u32 = 1u;
+const ORIENT_FIXED_AXIS: u32 = 2u;
+const ORIENT_WORLD_ALIGNED: u32 = 3u;
+
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 }
 
 struct InstanceInput {
-    @location(2) particle_position: vec3<f32>,
-    @location(3) size: f32,
-    @location(4) color: vec4<f32>,
+    @location(3) particle_position: vec3<f32>,
+    @location(4) size: f32,
+    @location(5) color: vec4<f32>,
+    @location(6) rotation: f32,
+    @location(7) uv_rect: vec4<f32>,
+    @location(8) blend: f32,
+    @location(9) softness: f32,
+    @location(10) orientation: f32,
+    @location(11) axis: vec3<f32>,
+}
+
+// One segment of a trail (TrailSegment on the CPU)
+struct SegmentInput {
+    @location(3) previous: vec3<f32>,
+    @location(4) start: vec3<f32>,
+    @location(5) end: vec3<f32>,
+    @location(6) next: vec3<f32>,
+    @location(7) width: f32,
+    @location(8) color: vec4<f32>,
+    @location(9) along: vec2<f32>,
+    @location(10) uv_rect: vec4<f32>,
+    @location(11) blend: f32,
+    @location(12) softness: f32,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) color: vec4<f32>,
     @location(1) uv: vec2<f32>,
+    // In the atlas, within the current frame of the sprite sheet
+    @location(2) atlas_uv: vec2<f32>,
+    @location(3) @interpolate(flat) textured: u32,
+    @location(4) @interpolate(flat) blend: u32,
+    // Distance from the camera along the view direction
+    @location(5) view_distance: f32,
+    @location(6) @interpolate(flat) softness: f32,
 }
 
 @vertex
 fn vs_main(vertex: VertexInput, instance: InstanceInput) -> VertexOutput {
     var out: VertexOutput;
 
-    // Axis-aligned billboard elongated vertically for flame-like shape
-    let sx = instance.size * 1.0; // slightly wider
-    let sy = instance.size * 2.2; // more vertical elongation
-    let world_pos = vec3<f32>(
-        instance.particle_position.x + vertex.position.x * sx,
-        instance.particle_position.y + vertex.position.y * sy,
-        instance.particle_position.z
-    );
+    // The soft dot is elongated vertically for a flame-like shape, textures keep their proportions
+    let textured = instance.uv_rect.z > 0.0;
+    var sx = instance.size * 1.0; // slightly wider
+    var sy = instance.size * select(2.2, 1.0, textured); // more vertical elongation
+
+    // The quad's right and up in the world
+    var right = scene.camera_right.xyz;
+    var up = scene.camera_up.xyz;
+    let to_camera = scene.camera_position.xyz - instance.particle_position;
+    switch u32(instance.orientation) {
+        case ORIENT_VELOCITY_STRETCH, ORIENT_FIXED_AXIS: {
+            // Upright along the axis and turned about it toward the camera; square on while
+            // there's no axis to follow, or it points at the camera
+            let side = cross(instance.axis, to_camera);
+            if dot(side, side) > 0.0 {
+                right = normalize(side);
+                up = normalize(instance.axis);
+            }
+            if u32(instance.orientation) == ORIENT_VELOCITY_STRETCH {
+                sy += length(instance.axis);
+            }
+        }
+        case ORIENT_WORLD_ALIGNED: {
+            // Up is as near world up as the plane allows
+            let normal = instance.axis;
+            let reference = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(0.0, 0.0, -1.0), abs(normal.y) > 0.999);
+            up = normalize(reference - normal * dot(reference, normal));
+            right = cross(up, normal);
+        }
+        default: {}
+    }
+
+    let corner = vec2<f32>(vertex.position.x * sx, vertex.position.y * sy);
+    let c = cos(instance.rotation);
+    let s = sin(instance.rotation);
+    let offset = vec2<f32>(c * corner.x - s * corner.y, s * corner.x + c * corner.y);
+    let world_pos = instance.particle_position + right * offset.x + up * offset.y;
 
     out.clip_position = uniforms.mvp_matrix * vec4<f32>(world_pos, 1.0);
     out.color = instance.color;
     out.uv = vertex.position.xy + 0.5;
+    out.atlas_uv = instance.uv_rect.xy + vertex.uv * instance.uv_rect.zw;
+    out.textured = select(0u, 1u, textured);
+    out.blend = u32(instance.blend);
+    out.view_distance = out.clip_position.w;
+    out.softness = instance.softness;
+    return out;
+}
+
+// The quad stretched from the segment's start (x = -0.5) to its end, as wide as the segment
+// across the trail's direction and the view. Each end turns with the trail's direction at that
+// point, so it meets the neighbouring segment's end exactly
+@vertex
+fn vs_trail(vertex: VertexInput, segment: SegmentInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let at_end = vertex.position.x > 0.0;
+    let point = select(segment.start, segment.end, at_end);
+    let direction = select(segment.end - segment.previous, segment.next - segment.start, at_end);
+    let side = cross(direction, scene.camera_position.xyz - point);
+    // Collapsed where the trail points at the camera or hasn't moved
+    var offset = vec3<f32>(0.0);
+    if dot(side, side) > 0.0 {
+        offset = normalize(side) * segment.width * vertex.position.y;
+    }
+    let along = select(segment.along.x, segment.along.y, at_end);
+    let textured = segment.uv_rect.z > 0.0;
+
+    out.clip_position = uniforms.mvp_matrix * vec4<f32>(point + offset, 1.0);
+    // Fading out toward the tail
+    out.color = vec4<f32>(segment.color.rgb, segment.color.a * (1.0 - along));
+    // The soft dot's falloff across the trail only
+    out.uv = vec2<f32>(0.5, vertex.position.y + 0.5);
+    // Textures stretch along the whole trail
+    out.atlas_uv = segment.uv_rect.xy + vec2<f32>(along, vertex.uv.y) * segment.uv_rect.zw;
+    out.textured = select(0u, 1u, textured);
+    out.blend = u32(segment.blend);
+    out.view_distance = out.clip_position.w;
+    out.softness = segment.softness;
     return out;
 }
 
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    // Soft circular sprite
+    // Premultiplied, so fading scales every channel
+    return sprite_color(in) * scene_fade(in);
+}
+
+// The particle's premultiplied color before fading
+fn sprite_color(in: VertexOutput) -> vec4<f32> {
+    // Sampled up front, texture sampling needs uniform control flow
+    let texel = textureSample(sprite_atlas, sprite_sampler, in.atlas_uv);
+    var color: vec4<f32>;
+    if in.textured == 1u {
+        // Tinted texture; premultiplied textures carry their own alpha in their color already
+        color = vec4<f32>(texel.rgb * in.color.rgb, texel.a * in.color.a);
+        if in.blend == BLEND_PREMULTIPLIED {
+            return vec4<f32>(color.rgb * in.color.a, color.a);
+        }
+    } else {
+        color = soft_dot(in);
+    }
+
+    // Everything is blended premultiplied: additive particles add their light and cover nothing.
+    // Clamped first, as the target would have clamped it before blending
+    color = saturate(color);
+    let premultiplied = color.rgb * color.a;
+    if in.blend == BLEND_ADDITIVE {
+        return vec4<f32>(premultiplied, 0.0);
+    }
+    return vec4<f32>(premultiplied, color.a);
+}
+
+// 0 behind the scene, rising to 1 at `softness` in front of it (or straight away without one)
+fn scene_fade(in: VertexOutput) -> f32 {
+    let depth = textureLoad(scene_depth, vec2<i32>(in.clip_position.xy), 0).r;
+    let scene_distance = scene.depth_terms.y / (depth + scene.depth_terms.x);
+    let gap = scene_distance - in.view_distance;
+    if in.softness <= 0.0 {
+        return select(0.0, 1.0, gap > 0.0);
+    }
+    return saturate(gap / in.softness);
+}
+
+// Soft circular sprite with a flickering bright core
+fn soft_dot(in: VertexOutput) -> vec4<f32> {
     let d = distance(in.uv, vec2<f32>(0.5, 0.5));
     var alpha = 1.0 - smoothstep(0.22, 0.52, d);
     // Slightly brighter core (component-wise)
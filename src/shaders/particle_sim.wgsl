@@ -0,0 +1,1006 @@
+// GPU particle simulation. Particles live in a fixed pool split between the emitters; each
+// emitter keeps a stack of its free slots (the dead list) and the live slots are listed in
+// two alternating alive lists, compacted as particles die.
+// Per step: begin_step -> simulate (alive_in -> alive_out) -> emit (into alive_out), then
+// begin_events -> spawn_events (into alive_out) when there are sub-emitters.
+// Per frame: begin_draw -> [begin_sort -> sort...] -> write_instances (alive_in -> instances
+// and trail segments, drawn indirectly). The sort orders alive_in back to front when some
+// emitter blends by alpha. Stepping and drawing bind different subsets of the buffers below
+
+const WORKGROUP_SIZE: u32 = 64u;
+const MAX_EMITTERS: u32 = 16u;
+const MAX_COLLIDERS: u32 = 8u;
+const MAX_FIELDS: u32 = 8u;
+const MAX_SUB_EMITTERS: u32 = 16u;
+const CURVE_SAMPLES: u32 = 64u;
+
+const SHAPE_POINT: u32 = 0u;
+const SHAPE_DISK: u32 = 1u;
+const SHAPE_SPHERE: u32 = 2u;
+const SHAPE_BOX: u32 = 3u;
+const SHAPE_MESH: u32 = 4u;
+const SHAPE_MESH_VERTEX: u32 = 5u;
+
+const FIELD_GRAVITY: u32 = 0u;
+const FIELD_WIND: u32 = 1u;
+const FIELD_ATTRACTOR: u32 = 2u;
+const FIELD_VORTEX: u32 = 3u;
+const FIELD_TURBULENCE: u32 = 4u;
+const FIELD_DRAG: u32 = 5u;
+
+const EVENT_BIRTH: u32 = 0u;
+const EVENT_DEATH: u32 = 1u;
+const EVENT_COLLISION: u32 = 2u;
+
+const VOLUME_EVERYWHERE: u32 = 0u;
+const VOLUME_SPHERE: u32 = 1u;
+const VOLUME_BOX: u32 = 2u;
+
+const COLLIDER_PLANE: u32 = 0u;
+const COLLIDER_SPHERE: u32 = 1u;
+const COLLIDER_BOX: u32 = 2u;
+const COLLIDER_MESH: u32 = 3u;
+// How far off a collider particles are put back; past the end of the step for a miss
+const SKIN: f32 = 0.01;
+const MISS: f32 = 2.0;
+
+const TAU: f32 = 6.28318530718;
+
+// Pads the alive list out to the sort size; sorts after every real particle
+const SORT_SENTINEL: u32 = 0xffffffffu;
+// What spawn returns when the emitter's pool is full
+const NO_SLOT: u32 = 0xffffffffu;
+
+struct Globals {
+    dt: f32,
+    // Simulation clock after this step
+    time: f32,
+    seed: u32,
+    step: u32,
+    emitter_count: u32,
+    // Particles to emit this step, over all emitters
+    spawn_total: u32,
+    triangle_count: u32,
+    // Between the previous step (0) and the current one (1), for drawing
+    interpolation: f32,
+    // Power of two the alive list is sorted over
+    sort_size: u32,
+    // Trail segments written per particle, the most any emitter draws
+    trail_segments: u32,
+    // Room in the event list
+    event_capacity: u32,
+    // Emission vertices, and where they start in `mesh`
+    vertex_count: u32,
+    vertex_offset: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    // View matrix row giving a position's view-space depth
+    view_depth: vec4<f32>,
+}
+
+// One compare-and-swap pass of the bitonic sort
+struct SortStep {
+    block: u32,
+    distance: u32,
+}
+
+struct Emitter {
+    // Shape center (point position) and radius
+    center_radius: vec4<f32>,
+    // Disk normal or box half extents
+    axis: vec4<f32>,
+    // Velocity direction; w = growth per second
+    direction_growth: vec4<f32>,
+    // Speed min/max, radial speed min/max
+    speed_radial: vec4<f32>,
+    // Lifetime min/max, size min/max
+    lifetime_size: vec4<f32>,
+    acceleration: vec4<f32>,
+    drag: vec4<f32>,
+    // Amplitude, frequency, height scale
+    swirl: vec4<f32>,
+    // Amplitude min/max, frequency min/max
+    waver: vec4<f32>,
+    // The sprite sheet's atlas rectangle, zero-sized for the soft dot
+    sprite_rect: vec4<f32>,
+    // Sheet columns and rows, times the flipbook plays over a life, blend mode
+    sprite: vec4<f32>,
+    // Rotation min/max, angular velocity min/max
+    spin: vec4<f32>,
+    // The orientation's axis or normal; w = seconds of motion a velocity stretch covers
+    orientation_axis: vec4<f32>,
+    // First pool slot, pool size, particles due this step, first spawn thread, SHAPE_*
+    pool_base: u32,
+    pool_size: u32,
+    spawn_count: u32,
+    spawn_offset: u32,
+    shape: u32,
+    // 1 to start particles on a random frame
+    random_start_frame: u32,
+    // Fade distance in front of the scene
+    softness: f32,
+    // Positions each particle's trail remembers (0 for no trail), where the emitter's trail
+    // histories start, and the trail's width as a fraction of the particle's size
+    trail_points: u32,
+    trail_base: u32,
+    trail_width: f32,
+    // Its sub-emitters' range in sub_emitters
+    sub_base: u32,
+    sub_count: u32,
+    // Orientation index
+    orientation: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+}
+
+struct SubEmitter {
+    // The emitter spawned from, and the EVENT_* that spawns it
+    emitter: u32,
+    event: u32,
+    count: u32,
+    inherit_velocity: f32,
+}
+
+// A sub-emitter's spawn, queued for spawn_events
+struct Event {
+    position: vec3<f32>,
+    sub_emitter: u32,
+    // The velocity and seed of the particle it happened to
+    velocity: vec3<f32>,
+    seed: u32,
+}
+
+struct EventList {
+    // Can pass event_capacity; the events past it are dropped
+    count: atomic<u32>,
+    events: array<Event>,
+}
+
+struct Particle {
+    position: vec3<f32>,
+    life: f32,
+    velocity: vec3<f32>,
+    max_life: f32,
+    prev_position: vec3<f32>,
+    size: f32,
+    phase: f32,
+    waver_amp: f32,
+    waver_freq: f32,
+    emitter: u32,
+    start_frame: f32,
+    // Positions recorded in its trail history so far
+    steps: u32,
+    rotation: f32,
+    angular_velocity: f32,
+    // Drawn from its spawner's random numbers, so it doesn't depend on the slot it landed in;
+    // seeds the sub-emitter spawns it sets off
+    seed: u32,
+}
+
+struct AliveList {
+    count: atomic<u32>,
+    indices: array<u32>,
+}
+
+struct DeadList {
+    // Free slots per emitter; signed so a pop that finds it empty can go below zero and back
+    counts: array<atomic<i32>, MAX_EMITTERS>,
+    indices: array<u32>,
+}
+
+struct IndirectArgs {
+    dispatch: array<u32, 3>,
+    _padding: u32,
+    // index_count, instance_count, first_index, base_vertex, first_instance
+    draw: array<u32, 5>,
+    // The same for the trail segments
+    trail_draw: array<u32, 5>,
+    // spawn_events' workgroups
+    spawn_dispatch: array<u32, 3>,
+}
+
+// Same layout as ParticleInstance on the CPU (scalars only, so nothing gets padded)
+struct Instance {
+    position_x: f32,
+    position_y: f32,
+    position_z: f32,
+    size: f32,
+    color_r: f32,
+    color_g: f32,
+    color_b: f32,
+    color_a: f32,
+    rotation: f32,
+    uv_x: f32,
+    uv_y: f32,
+    uv_width: f32,
+    uv_height: f32,
+    blend: f32,
+    softness: f32,
+    orientation: f32,
+    axis_x: f32,
+    axis_y: f32,
+    axis_z: f32,
+}
+
+// Same layout as TrailSegment on the CPU
+struct Segment {
+    previous_x: f32,
+    previous_y: f32,
+    previous_z: f32,
+    start_x: f32,
+    start_y: f32,
+    start_z: f32,
+    end_x: f32,
+    end_y: f32,
+    end_z: f32,
+    next_x: f32,
+    next_y: f32,
+    next_z: f32,
+    width: f32,
+    color_r: f32,
+    color_g: f32,
+    color_b: f32,
+    color_a: f32,
+    along_start: f32,
+    along_end: f32,
+    uv_x: f32,
+    uv_y: f32,
+    uv_width: f32,
+    uv_height: f32,
+    blend: f32,
+    softness: f32,
+}
+
+// Matches force_field.rs on the CPU
+struct Field {
+    // Gravity's acceleration, wind velocity, attractor position or vortex center; w = strength
+    // (negative for a repulsor)
+    a: vec4<f32>,
+    // Vortex axis, or turbulence frequency and speed
+    b: vec4<f32>,
+    // Volume center; w = sphere radius
+    volume_center: vec4<f32>,
+    // Box half extents
+    volume_half_extents: vec4<f32>,
+    force: u32,
+    volume: u32,
+    falloff: f32,
+    _padding: u32,
+}
+
+struct Fields {
+    count: u32,
+    _padding0: u32,
+    _padding1: u32,
+    _padding2: u32,
+    fields: array<Field, MAX_FIELDS>,
+}
+
+struct Collider {
+    // Plane point, or sphere or box center; w = sphere radius
+    a: vec4<f32>,
+    // Plane normal or box half extents
+    b: vec4<f32>,
+    bounce: f32,
+    friction: f32,
+    kill: u32,
+    shape: u32,
+}
+
+struct Colliders {
+    // Where the model is relative to the pose its mesh was captured in, and back
+    model: mat4x4<f32>,
+    model_inverse: mat4x4<f32>,
+    count: u32,
+    bvh_nodes: u32,
+    // Where the BVH starts in `mesh`
+    bvh_offset: u32,
+    _padding: u32,
+    colliders: array<Collider, MAX_COLLIDERS>,
+}
+
+// Fraction of the step where it crosses a collider, and the surface normal facing back along it
+struct Hit {
+    t: f32,
+    normal: vec3<f32>,
+    collider: u32,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+@group(0) @binding(1) var<uniform> emitters: array<Emitter, MAX_EMITTERS>;
+@group(0) @binding(2) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(3) var<storage, read_write> alive_in: AliveList;
+@group(0) @binding(4) var<storage, read_write> alive_out: AliveList;
+@group(0) @binding(5) var<storage, read_write> dead: DeadList;
+@group(0) @binding(6) var<storage, read_write> instances: array<Instance>;
+// Per emitter, CURVE_SAMPLES pairs of (rgb color, alpha) and (size, rotation, -, -)
+@group(0) @binding(7) var<storage, read> appearance: array<vec4<f32>>;
+// The model: each emission triangle's corners, with the cumulative area up to it in the first
+// corner's w. Then from globals.vertex_offset each emission vertex's position and normal, and
+// from colliders.bvh_offset its BVH, per node (min, first triangle or second
+// child) and (max, triangle count, 0 for branches), followed by the BVH's triangles' corners
+@group(0) @binding(8) var<storage, read> mesh: array<vec4<f32>>;
+@group(0) @binding(9) var<uniform> colliders: Colliders;
+@group(0) @binding(10) var<uniform> fields: Fields;
+// Per trail particle, a ring of its last positions
+@group(0) @binding(11) var<storage, read_write> trail_history: array<vec4<f32>>;
+// trail_segments per particle, in alive list order
+@group(0) @binding(12) var<storage, read_write> segments: array<Segment>;
+// Every emitter's sub-emitters, emitter after emitter
+@group(0) @binding(13) var<uniform> sub_emitters: array<SubEmitter, MAX_SUB_EMITTERS>;
+@group(0) @binding(14) var<storage, read_write> events: EventList;
+// Only bound for begin_step / begin_draw, the dispatches that follow read it as indirect args
+@group(1) @binding(0) var<storage, read_write> indirect: IndirectArgs;
+// Only bound for sort, at a different offset each pass
+@group(1) @binding(1) var<uniform> sort_step: SortStep;
+
+// PCG hash: cheap, well-distributed, and the same on every GPU
+fn pcg(value: u32) -> u32 {
+    let state = value * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+var<private> rng_state: u32;
+
+fn random() -> f32 {
+    rng_state = pcg(rng_state);
+    return f32(rng_state >> 8u) / 16777216.0;
+}
+
+fn random_range(range: vec2<f32>) -> f32 {
+    return range.x + (range.y - range.x) * random();
+}
+
+@compute @workgroup_size(1)
+fn begin_step() {
+    let count = atomicLoad(&alive_in.count);
+    indirect.dispatch = array<u32, 3>((count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE, 1u, 1u);
+    atomicStore(&alive_out.count, 0u);
+    atomicStore(&events.count, 0u);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn simulate(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= atomicLoad(&alive_in.count) {
+        return;
+    }
+    let index = alive_in.indices[id.x];
+    var p = particles[index];
+    let emitter = emitters[p.emitter];
+    let dt = globals.dt;
+    let time = globals.time;
+
+    p.life -= dt;
+    if p.life <= 0.0 {
+        fire(emitter, EVENT_DEATH, p.position, p.velocity, p.seed);
+        release(index, p.emitter);
+        return;
+    }
+    p.prev_position = p.position;
+    p.position += p.velocity * dt;
+    // Stop at the first collider crossed, bouncing off or dying there
+    if colliders.count > 0u {
+        let hit = first_hit(p.prev_position, p.position);
+        if hit.t != MISS {
+            let collider = colliders.colliders[hit.collider];
+            if collider.kill != 0u {
+                let at = mix(p.prev_position, p.position, hit.t);
+                fire(emitter, EVENT_COLLISION, at, p.velocity, p.seed);
+                fire(emitter, EVENT_DEATH, at, p.velocity, p.seed);
+                release(index, p.emitter);
+                return;
+            }
+            let into = hit.normal * dot(p.velocity, hit.normal);
+            p.velocity = (p.velocity - into) * (1.0 - collider.friction) - into * collider.bounce;
+            p.position = mix(p.prev_position, p.position, hit.t) + hit.normal * SKIN;
+            fire(emitter, EVENT_COLLISION, p.position, p.velocity, p.seed);
+        }
+    }
+    record_trail(index, emitter, &p);
+    p.velocity += emitter.acceleration.xyz * dt;
+    // Lateral turbulence (swirl)
+    let angle = p.phase + time * emitter.swirl.y + p.position.y * emitter.swirl.z;
+    p.velocity.x += emitter.swirl.x * sin(angle) * dt;
+    p.velocity.z += emitter.swirl.x * cos(angle) * dt;
+    // Strict X waver
+    p.velocity.x += p.waver_amp * sin(time * p.waver_freq) * dt;
+    p.velocity *= vec3<f32>(1.0) - emitter.drag.xyz * dt;
+    // The scene's force fields
+    for (var i = 0u; i < fields.count; i += 1u) {
+        p.velocity = apply_field(fields.fields[i], p.position, p.velocity, time, dt);
+    }
+    p.size += emitter.direction_growth.w * dt;
+    p.rotation += p.angular_velocity * dt;
+    particles[index] = p;
+
+    let slot = atomicAdd(&alive_out.count, 1u);
+    alive_out.indices[slot] = index;
+}
+
+fn apply_field(field: Field, position: vec3<f32>, velocity: vec3<f32>, time: f32, step_dt: f32) -> vec3<f32> {
+    let weight = field_weight(field, position);
+    let dt = step_dt * weight;
+    let strength = field.a.w;
+    switch field.force {
+        case FIELD_GRAVITY: {
+            return velocity + field.a.xyz * dt;
+        }
+        case FIELD_WIND: {
+            return velocity + (field.a.xyz - velocity) * min(strength * dt, 1.0);
+        }
+        case FIELD_ATTRACTOR: {
+            return velocity + normalize_or_zero(field.a.xyz - position) * strength * dt;
+        }
+        case FIELD_VORTEX: {
+            let around = cross(normalize(field.b.xyz), position - field.a.xyz);
+            return velocity + normalize_or_zero(around) * strength * dt;
+        }
+        case FIELD_TURBULENCE: {
+            return velocity + curl_noise(position * field.b.x, time * field.b.y) * strength * dt;
+        }
+        case FIELD_DRAG: {
+            return velocity * (1.0 - strength * dt);
+        }
+        default: {
+            return velocity;
+        }
+    }
+}
+
+// How much of the field reaches `position`: none outside the volume, rising to all of it
+// `falloff` in from the edge
+fn field_weight(field: Field, position: vec3<f32>) -> f32 {
+    var depth: f32;
+    switch field.volume {
+        case VOLUME_SPHERE: {
+            depth = field.volume_center.w - distance(position, field.volume_center.xyz);
+        }
+        case VOLUME_BOX: {
+            let inside = field.volume_half_extents.xyz - abs(position - field.volume_center.xyz);
+            depth = min(inside.x, min(inside.y, inside.z));
+        }
+        default: {
+            return 1.0;
+        }
+    }
+    if depth < 0.0 {
+        return 0.0;
+    }
+    if field.falloff == 0.0 {
+        return 1.0;
+    }
+    return min(depth / field.falloff, 1.0);
+}
+
+// Curl of drifting sine waves; see force_field.rs
+fn curl_noise(p: vec3<f32>, t: f32) -> vec3<f32> {
+    let dzdy = 1.3 * cos(1.3 * p.y - 1.1 * t);
+    let dydz = cos(p.z + 1.3 * t);
+    let dxdz = 1.7 * cos(1.7 * p.z - 0.6 * t);
+    let dzdx = cos(p.x + 0.8 * t);
+    let dydx = 1.9 * cos(1.9 * p.x + 0.4 * t);
+    let dxdy = cos(p.y + t);
+    return vec3<f32>(dzdy - dydz, dxdz - dzdx, dydx - dxdy) * 0.5;
+}
+
+fn normalize_or_zero(v: vec3<f32>) -> vec3<f32> {
+    let length_squared = dot(v, v);
+    if length_squared == 0.0 {
+        return vec3<f32>(0.0);
+    }
+    return v * inverseSqrt(length_squared);
+}
+
+// Adds where the particle is now to its trail
+fn record_trail(index: u32, emitter: Emitter, p: ptr<function, Particle>) {
+    if emitter.trail_points > 0u {
+        trail_history[trail_slot(emitter, index, (*p).steps)] = vec4<f32>((*p).position, 0.0);
+    }
+    (*p).steps += 1u;
+}
+
+// Where the particle in `index` keeps the position it recorded on `step`
+fn trail_slot(emitter: Emitter, index: u32, step: u32) -> u32 {
+    return emitter.trail_base + (index - emitter.pool_base) * emitter.trail_points + step % emitter.trail_points;
+}
+
+// Queues the sub-emitter spawns `event` sets off for one of `emitter`'s particles
+fn fire(emitter: Emitter, event: u32, position: vec3<f32>, velocity: vec3<f32>, seed: u32) {
+    for (var i = emitter.sub_base; i < emitter.sub_base + emitter.sub_count; i += 1u) {
+        if sub_emitters[i].event == event {
+            let slot = atomicAdd(&events.count, 1u);
+            if slot < globals.event_capacity {
+                events.events[slot] = Event(position, i, velocity, seed);
+            }
+        }
+    }
+}
+
+// Returns a dead particle's slot to its emitter
+fn release(index: u32, e: u32) {
+    let slot = atomicAdd(&dead.counts[e], 1);
+    dead.indices[emitters[e].pool_base + u32(slot)] = index;
+}
+
+fn first_hit(start: vec3<f32>, to: vec3<f32>) -> Hit {
+    var nearest = Hit(MISS, vec3<f32>(0.0), 0u);
+    for (var i = 0u; i < colliders.count; i += 1u) {
+        let collider = colliders.colliders[i];
+        var hit: Hit;
+        switch collider.shape {
+            case COLLIDER_PLANE: {
+                hit = hit_plane(start, to, collider.a.xyz, normalize(collider.b.xyz));
+            }
+            case COLLIDER_SPHERE: {
+                hit = hit_sphere(start, to, collider.a.xyz, collider.a.w);
+            }
+            case COLLIDER_BOX: {
+                hit = hit_box(start, to, collider.a.xyz - collider.b.xyz, collider.a.xyz + collider.b.xyz);
+            }
+            default: {
+                let local_start = (colliders.model_inverse * vec4<f32>(start, 1.0)).xyz;
+                let local_to = (colliders.model_inverse * vec4<f32>(to, 1.0)).xyz;
+                hit = hit_mesh(local_start, local_to);
+                if hit.t != MISS {
+                    hit.normal = normalize((colliders.model * vec4<f32>(hit.normal, 0.0)).xyz);
+                }
+            }
+        }
+        if hit.t < nearest.t {
+            nearest = Hit(hit.t, hit.normal, i);
+        }
+    }
+    return nearest;
+}
+
+fn hit_plane(start: vec3<f32>, to: vec3<f32>, point: vec3<f32>, normal: vec3<f32>) -> Hit {
+    let before = dot(start - point, normal);
+    let after = dot(to - point, normal);
+    if before >= 0.0 && after < 0.0 {
+        return Hit(before / (before - after), normal, 0u);
+    }
+    return Hit(MISS, vec3<f32>(0.0), 0u);
+}
+
+// From outside only
+fn hit_sphere(start: vec3<f32>, to: vec3<f32>, center: vec3<f32>, radius: f32) -> Hit {
+    let offset = start - center;
+    let step = to - start;
+    let c = dot(offset, offset) - radius * radius;
+    let b = dot(offset, step);
+    let a = dot(step, step);
+    let discriminant = b * b - a * c;
+    if c < 0.0 || b >= 0.0 || discriminant < 0.0 {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    let t = (-b - sqrt(discriminant)) / a;
+    if t > 1.0 {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    return Hit(t, (start + step * t - center) / radius, 0u);
+}
+
+// From outside only
+fn hit_box(start: vec3<f32>, to: vec3<f32>, low: vec3<f32>, high: vec3<f32>) -> Hit {
+    if all(start >= low) && all(start <= high) {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    let step = to - start;
+    let entry = slabs(start, step, low, high, 1.0);
+    if entry.x == MISS {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    var normal = vec3<f32>(0.0);
+    let axis = u32(entry.y);
+    normal[axis] = -sign(step[axis]);
+    return Hit(entry.x, normal, 0u);
+}
+
+// Where the ray from `start` along `step` enters the box (as a fraction of `step`, MISS if it
+// misses or not before `max_t`) and the axis it enters through
+fn slabs(start: vec3<f32>, step: vec3<f32>, low: vec3<f32>, high: vec3<f32>, max_t: f32) -> vec2<f32> {
+    var enter = 0.0;
+    var exit = max_t;
+    var axis = 0.0;
+    for (var i = 0u; i < 3u; i += 1u) {
+        if step[i] == 0.0 {
+            if start[i] < low[i] || start[i] > high[i] {
+                return vec2<f32>(MISS, 0.0);
+            }
+            continue;
+        }
+        let a = (low[i] - start[i]) / step[i];
+        let b = (high[i] - start[i]) / step[i];
+        if min(a, b) > enter {
+            enter = min(a, b);
+            axis = f32(i);
+        }
+        exit = min(exit, max(a, b));
+        if enter > exit {
+            return vec2<f32>(MISS, 0.0);
+        }
+    }
+    return vec2<f32>(enter, axis);
+}
+
+// Möller-Trumbore, from either side
+fn hit_triangle(start: vec3<f32>, step: vec3<f32>, a: vec3<f32>, b: vec3<f32>, c: vec3<f32>) -> Hit {
+    let ab = b - a;
+    let ac = c - a;
+    let p = cross(step, ac);
+    let determinant = dot(ab, p);
+    if abs(determinant) < 1e-12 {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    let inverse = 1.0 / determinant;
+    let offset = start - a;
+    let u = dot(offset, p) * inverse;
+    let q = cross(offset, ab);
+    let v = dot(step, q) * inverse;
+    let t = dot(ac, q) * inverse;
+    // Not the surface the particle is resting on
+    if u < 0.0 || u > 1.0 || v < 0.0 || u + v > 1.0 || t <= 1e-4 || t > 1.0 {
+        return Hit(MISS, vec3<f32>(0.0), 0u);
+    }
+    let normal = normalize(cross(ab, ac));
+    return Hit(t, select(normal, -normal, dot(normal, step) > 0.0), 0u);
+}
+
+// The nearest of the model's triangles the step crosses, in the model's own space
+fn hit_mesh(start: vec3<f32>, to: vec3<f32>) -> Hit {
+    let step = to - start;
+    var nearest = Hit(MISS, vec3<f32>(0.0), 0u);
+    var stack: array<u32, 64>;
+    var pending = select(0u, 1u, colliders.bvh_nodes > 0u);
+    stack[0] = 0u;
+    while pending > 0u {
+        pending -= 1u;
+        let node = stack[pending];
+        let low = mesh[colliders.bvh_offset + node * 2u];
+        let high = mesh[colliders.bvh_offset + node * 2u + 1u];
+        if slabs(start, step, low.xyz, high.xyz, min(nearest.t, 1.0)).x == MISS {
+            continue;
+        }
+        let index = bitcast<u32>(low.w);
+        let count = bitcast<u32>(high.w);
+        if count == 0u {
+            stack[pending] = index;
+            stack[pending + 1u] = node + 1u;
+            pending += 2u;
+            continue;
+        }
+        for (var i = index; i < index + count; i += 1u) {
+            let corners = colliders.bvh_offset + colliders.bvh_nodes * 2u + i * 3u;
+            let hit = hit_triangle(start, step, mesh[corners].xyz, mesh[corners + 1u].xyz, mesh[corners + 2u].xyz);
+            if hit.t < nearest.t {
+                nearest = hit;
+            }
+        }
+    }
+    return nearest;
+}
+
+// Spawn position and the outward direction there
+fn sample_shape(emitter: Emitter) -> array<vec3<f32>, 2> {
+    let center = emitter.center_radius.xyz;
+    let radius = emitter.center_radius.w;
+    switch emitter.shape {
+        case SHAPE_DISK: {
+            let normal = normalize(emitter.axis.xyz);
+            var reference = vec3<f32>(0.0, 0.0, 1.0);
+            if abs(normal.z) >= 0.999 {
+                reference = vec3<f32>(1.0, 0.0, 0.0);
+            }
+            let tangent = normalize(cross(normal, reference));
+            let bitangent = cross(tangent, normal);
+            let angle = random() * TAU;
+            let r = random() * radius;
+            let position = center + tangent * (r * cos(angle)) + bitangent * (r * sin(angle));
+            return array<vec3<f32>, 2>(position, safe_normalize(position - center));
+        }
+        case SHAPE_SPHERE: {
+            let direction = random_direction();
+            return array<vec3<f32>, 2>(center + direction * radius * pow(random(), 1.0 / 3.0), direction);
+        }
+        case SHAPE_BOX: {
+            let offset = (vec3<f32>(random(), random(), random()) * 2.0 - 1.0) * emitter.axis.xyz;
+            return array<vec3<f32>, 2>(center + offset, safe_normalize(offset));
+        }
+        case SHAPE_MESH: {
+            if globals.triangle_count == 0u {
+                return array<vec3<f32>, 2>(vec3<f32>(0.0), vec3<f32>(0.0));
+            }
+            // Pick a triangle by area: first one whose cumulative area passes the target
+            let target_area = random() * mesh[(globals.triangle_count - 1u) * 3u].w;
+            var low = 0u;
+            var high = globals.triangle_count - 1u;
+            while low < high {
+                let middle = (low + high) / 2u;
+                if mesh[middle * 3u].w <= target_area {
+                    low = middle + 1u;
+                } else {
+                    high = middle;
+                }
+            }
+            let u = sqrt(random());
+            let v = random();
+            let a = mesh[low * 3u].xyz;
+            let b = mesh[low * 3u + 1u].xyz;
+            let c = mesh[low * 3u + 2u].xyz;
+            let position = a * (1.0 - u) + b * (u * (1.0 - v)) + c * (u * v);
+            return place_on_model(position, cross(b - a, c - a));
+        }
+        case SHAPE_MESH_VERTEX: {
+            if globals.vertex_count == 0u {
+                return array<vec3<f32>, 2>(vec3<f32>(0.0), vec3<f32>(0.0));
+            }
+            let vertex = globals.vertex_offset + min(u32(random() * f32(globals.vertex_count)), globals.vertex_count - 1u) * 2u;
+            return place_on_model(mesh[vertex].xyz, mesh[vertex + 1u].xyz);
+        }
+        default: {
+            return array<vec3<f32>, 2>(center, vec3<f32>(0.0));
+        }
+    }
+}
+
+// A point and normal of the mesh, from the pose it was captured in to where the model is now
+fn place_on_model(position: vec3<f32>, normal: vec3<f32>) -> array<vec3<f32>, 2> {
+    let placed = (colliders.model * vec4<f32>(position, 1.0)).xyz;
+    return array<vec3<f32>, 2>(placed, safe_normalize((colliders.model * vec4<f32>(normal, 0.0)).xyz));
+}
+
+// Uniformly over the unit sphere
+fn random_direction() -> vec3<f32> {
+    let z = random() * 2.0 - 1.0;
+    let angle = random() * TAU;
+    let ring = sqrt(1.0 - z * z);
+    return vec3<f32>(ring * cos(angle), z, ring * sin(angle));
+}
+
+fn safe_normalize(v: vec3<f32>) -> vec3<f32> {
+    let length_squared = dot(v, v);
+    if length_squared > 0.0 {
+        return v * inverseSqrt(length_squared);
+    }
+    return vec3<f32>(0.0);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn emit(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= globals.spawn_total {
+        return;
+    }
+    // Which emitter this thread spawns for
+    var e = 0u;
+    loop {
+        if e + 1u >= globals.emitter_count || id.x < emitters[e].spawn_offset + emitters[e].spawn_count {
+            break;
+        }
+        e += 1u;
+    }
+
+    rng_state = pcg(globals.seed ^ pcg(globals.step ^ pcg(id.x)));
+    let at = sample_shape(emitters[e]);
+    let index = spawn(e, at[0], at[1], vec3<f32>(0.0));
+    if index != NO_SLOT {
+        let p = particles[index];
+        fire(emitters[e], EVENT_BIRTH, p.position, p.velocity, p.seed);
+    }
+}
+
+// Puts a new particle of emitter `e`'s at `position`, with `outward` setting the way its radial
+// speed takes it and `inherited` added to its velocity. Returns its slot, or NO_SLOT when the
+// emitter is at its cap. Which slot it gets depends on thread timing, so nothing it draws may
+// depend on the slot; only a full pool lets timing pick which spawns fail
+fn spawn(e: u32, position: vec3<f32>, outward: vec3<f32>, inherited: vec3<f32>) -> u32 {
+    let emitter = emitters[e];
+    // Take a free slot, or give up if the emitter is at its cap
+    let free = atomicSub(&dead.counts[e], 1) - 1;
+    if free < 0 {
+        atomicAdd(&dead.counts[e], 1);
+        return NO_SLOT;
+    }
+    let index = dead.indices[emitter.pool_base + u32(free)];
+
+    let speed = random_range(emitter.speed_radial.xy);
+    let radial = outward * random_range(emitter.speed_radial.zw);
+
+    var p: Particle;
+    p.position = position;
+    p.prev_position = position;
+    p.velocity = emitter.direction_growth.xyz * speed + radial + inherited;
+    p.life = random_range(emitter.lifetime_size.xy);
+    // Its own lifetime, so every particle runs the whole length of the appearance curves
+    p.max_life = max(p.life, 1e-6);
+    p.size = random_range(emitter.lifetime_size.zw);
+    p.phase = random() * TAU;
+    p.waver_amp = random_range(emitter.waver.xy);
+    p.waver_freq = random_range(emitter.waver.zw);
+    p.emitter = e;
+    p.start_frame = 0.0;
+    if emitter.random_start_frame != 0u {
+        p.start_frame = floor(random() * emitter.sprite.x * emitter.sprite.y);
+    }
+    p.rotation = random_range(emitter.spin.xy);
+    p.angular_velocity = random_range(emitter.spin.zw);
+    rng_state = pcg(rng_state);
+    p.seed = rng_state;
+    p.steps = 0u;
+    record_trail(index, emitter, &p);
+    particles[index] = p;
+
+    let slot = atomicAdd(&alive_out.count, 1u);
+    alive_out.indices[slot] = index;
+    return index;
+}
+
+@compute @workgroup_size(1)
+fn begin_events() {
+    let count = min(atomicLoad(&events.count), globals.event_capacity);
+    indirect.spawn_dispatch = array<u32, 3>((count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE, 1u, 1u);
+}
+
+// One thread per queued event, spawning its sub-emitter's particles out of where it happened.
+// These don't fire birth events, so sub-emitters can't set each other off without end
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn spawn_events(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= min(atomicLoad(&events.count), globals.event_capacity) {
+        return;
+    }
+    let event = events.events[id.x];
+    let sub = sub_emitters[event.sub_emitter];
+    // Seeded by the particle and sub-emitter that queued it, not its place in the queue,
+    // which depends on thread timing
+    rng_state = pcg(globals.seed ^ pcg(globals.step ^ pcg(event.seed ^ pcg(event.sub_emitter))));
+    for (var n = 0u; n < sub.count; n += 1u) {
+        if spawn(sub.emitter, event.position, random_direction(), event.velocity * sub.inherit_velocity) == NO_SLOT {
+            return;
+        }
+    }
+}
+
+@compute @workgroup_size(1)
+fn begin_draw() {
+    let count = atomicLoad(&alive_in.count);
+    indirect.dispatch = array<u32, 3>((count + WORKGROUP_SIZE - 1u) / WORKGROUP_SIZE, 1u, 1u);
+    indirect.draw = array<u32, 5>(6u, count, 0u, 0u, 0u);
+    indirect.trail_draw = array<u32, 5>(6u, count * globals.trail_segments, 0u, 0u, 0u);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn begin_sort(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= atomicLoad(&alive_in.count) && id.x < globals.sort_size {
+        alive_in.indices[id.x] = SORT_SENTINEL;
+    }
+}
+
+// View-space depth where the particle is drawn; more negative is farther away
+fn sort_depth(index: u32) -> f32 {
+    if index == SORT_SENTINEL {
+        return 3.4e38;
+    }
+    let p = particles[index];
+    let position = mix(p.prev_position, p.position, globals.interpolation);
+    return dot(globals.view_depth.xyz, position) + globals.view_depth.w;
+}
+
+// Bitonic sort pass: each entry compares with the one `distance` away, and blocks alternate
+// direction until the final block spans the whole list. Ascending depth is back to front
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn sort(@builtin(global_invocation_id) id: vec3<u32>) {
+    let partner = id.x ^ sort_step.distance;
+    if partner <= id.x || partner >= globals.sort_size {
+        return;
+    }
+    let a = alive_in.indices[id.x];
+    let b = alive_in.indices[partner];
+    let ascending = (id.x & sort_step.block) == 0u;
+    if (sort_depth(a) > sort_depth(b)) == ascending {
+        alive_in.indices[id.x] = b;
+        alive_in.indices[partner] = a;
+    }
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE)
+fn write_instances(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= atomicLoad(&alive_in.count) {
+        return;
+    }
+    let p = particles[alive_in.indices[id.x]];
+    let emitter = emitters[p.emitter];
+    let age = clamp(1.0 - p.life / p.max_life, 0.0, 1.0);
+
+    // Blend the two nearest curve samples
+    let x = age * f32(CURVE_SAMPLES - 1u);
+    let i0 = u32(floor(x));
+    let i1 = min(i0 + 1u, CURVE_SAMPLES - 1u);
+    let base = p.emitter * CURVE_SAMPLES;
+    let color = mix(appearance[(base + i0) * 2u], appearance[(base + i1) * 2u], fract(x));
+    let shape = mix(appearance[(base + i0) * 2u + 1u], appearance[(base + i1) * 2u + 1u], fract(x));
+
+    let position = mix(p.prev_position, p.position, globals.interpolation);
+    var instance: Instance;
+    instance.position_x = position.x;
+    instance.position_y = position.y;
+    instance.position_z = position.z;
+    instance.size = p.size * shape.x;
+    instance.color_r = color.r;
+    instance.color_g = color.g;
+    instance.color_b = color.b;
+    instance.color_a = color.a;
+    instance.rotation = p.rotation + shape.y;
+
+    // Flipbook frame, left to right then top to bottom in the sheet
+    let sheet = emitter.sprite.xy;
+    let frame_count = sheet.x * sheet.y;
+    let frame = (p.start_frame + floor(age * emitter.sprite.z * frame_count)) % frame_count;
+    let frame_size = emitter.sprite_rect.zw / sheet;
+    let frame_origin = emitter.sprite_rect.xy + vec2<f32>(frame % sheet.x, floor(frame / sheet.x)) * frame_size;
+    instance.uv_x = frame_origin.x;
+    instance.uv_y = frame_origin.y;
+    instance.uv_width = frame_size.x;
+    instance.uv_height = frame_size.y;
+    instance.blend = emitter.sprite.w;
+    instance.softness = emitter.softness;
+    instance.orientation = f32(emitter.orientation);
+    // Stretched over the motion in the next `w` seconds, or turned by a fixed direction
+    var axis = emitter.orientation_axis.xyz;
+    if emitter.orientation_axis.w > 0.0 {
+        axis = p.velocity * emitter.orientation_axis.w;
+    }
+    instance.axis_x = axis.x;
+    instance.axis_y = axis.y;
+    instance.axis_z = axis.z;
+    instances[id.x] = instance;
+
+    if globals.trail_segments > 0u {
+        write_trail(id.x, alive_in.indices[id.x], p, emitter, instance);
+    }
+}
+
+// The particle's trail segments from where it's drawn (`head`) back to the tail, then unused
+// zero-width ones to fill its share of the segment buffer
+fn write_trail(slot: u32, index: u32, p: Particle, emitter: Emitter, head: Instance) {
+    var points = 0u;
+    if emitter.trail_points > 1u {
+        points = min(p.steps, emitter.trail_points);
+    }
+    let head_position = vec3<f32>(head.position_x, head.position_y, head.position_z);
+    for (var k = 0u; k < globals.trail_segments; k += 1u) {
+        var segment = Segment();
+        if k + 1u < points {
+            let start = trail_point(index, p, emitter, head_position, k);
+            let end = trail_point(index, p, emitter, head_position, k + 1u);
+            // Carried straight on past the ends
+            var previous = 2.0 * start - end;
+            if k > 0u {
+                previous = trail_point(index, p, emitter, head_position, k - 1u);
+            }
+            var next = 2.0 * end - start;
+            if k + 2u < points {
+                next = trail_point(index, p, emitter, head_position, k + 2u);
+            }
+            let segment_count = f32(emitter.trail_points - 1u);
+            segment = Segment(
+                previous.x, previous.y, previous.z, start.x, start.y, start.z,
+                end.x, end.y, end.z, next.x, next.y, next.z,
+                head.size * emitter.trail_width,
+                head.color_r, head.color_g, head.color_b, head.color_a,
+                f32(k) / segment_count, f32(k + 1u) / segment_count,
+                head.uv_x, head.uv_y, head.uv_width, head.uv_height, head.blend, head.softness,
+            );
+        }
+        segments[slot * globals.trail_segments + k] = segment;
+    }
+}
+
+// The trail's `k`th point back: where the particle is drawn, then the positions it recorded
+fn trail_point(index: u32, p: Particle, emitter: Emitter, head: vec3<f32>, k: u32) -> vec3<f32> {
+    if k == 0u {
+        return head;
+    }
+    return trail_history[trail_slot(emitter, index, p.steps - 1u - k)].xyz;
+}
@@ -2,29 +2,47 @@
 struct VertexInput {
     @location(0) position: vec3<f32>,
     @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) world_normal: vec3<f32>,
     @location(1) world_position: vec3<f32>,
+    @location(2) uv: vec2<f32>,
 }
 
+// Per instance; colors come from the material below
 struct Uniforms {
     mvp_matrix: mat4x4<f32>,
     model_matrix: mat4x4<f32>,
-    base_color: vec4<f32>,
 }
 
 @group(0) @binding(0)
 var<uniform> uniforms: Uniforms;
 
+// Per-material factors, matching MaterialUniforms in material.rs
+struct Material {
+    base_color: vec4<f32>,
+    emissive: vec4<f32>,
+    params: vec4<f32>, // metallic, roughness, alpha_cutoff (negative = off), unused
+}
+
+@group(1) @binding(0)
+var<uniform> material: Material;
+// Base-color texture (1x1 white when the material has none)
+@group(1) @binding(1)
+var t_base_color: texture_2d<f32>;
+@group(1) @binding(2)
+var s_base_color: sampler;
+
 @vertex
 fn vs_main(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
     out.clip_position = uniforms.mvp_matrix * vec4<f32>(model.position, 1.0);
     out.world_position = (uniforms.model_matrix * vec4<f32>(model.position, 1.0)).xyz;
     out.world_normal = normalize((uniforms.model_matrix * vec4<f32>(model.normal, 0.0)).xyz);
+    out.uv = model.uv;
     return out;
 }
 
@@ -33,11 +51,20 @@ fn vs_main(model: VertexInput) -> VertexOutput {
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
     let light_dir = normalize(vec3<f32>(1.0, 1.0, 1.0));
     let light_color = vec3<f32>(1.0, 1.0, 1.0);
-    
+
     // Basic Lambert lighting
     let normal = normalize(in.world_normal);
     let light_intensity = max(dot(normal, light_dir), 0.1); // 0.1 ambient
-    
-    let final_color = uniforms.base_color.rgb * light_color * light_intensity;
-    return vec4<f32>(final_color, uniforms.base_color.a);
-}
\ No newline at end of file
+
+    // glTF multiplies the base-color factor with the texture sample
+    let albedo = material.base_color * textureSample(t_base_color, s_base_color, in.uv);
+    // Alpha-mask materials cut out below the cutoff
+    if (material.params.z >= 0.0 && albedo.a < material.params.z) {
+        discard;
+    }
+
+    // Metals have no diffuse term, keep a little so they don't go black without reflections
+    let diffuse = albedo.rgb * mix(1.0, 0.3, material.params.x);
+    let final_color = diffuse * light_color * light_intensity + material.emissive.rgb;
+    return vec4<f32>(final_color, albedo.a);
+}
@@ -0,0 +1,145 @@
+// Golden-image tests: render fixed-time frames offscreen and compare them with the
+// references in tests/snapshots. Set UPDATE_SNAPSHOTS=1 to re-record the references
+// after an intended visual change; failures write a diff image to target/snapshot-diffs
+use crate::emitter::{EmitterShape, ParticleConfig, SimulationBackend};
+use crate::headless::{self, OffscreenTarget};
+use crate::model::ModelData;
+use crate::model_loader::{LoadOptions, ModelLoader};
+use crate::renderer::{Passes, Renderer, DEFAULT_MODEL_FIT};
+use image::{Rgba, RgbaImage};
+use std::path::PathBuf;
+
+const WIDTH: u32 = 160;
+const HEIGHT: u32 = 120;
+const SEED: u64 = 1234;
+const DT: f32 = 1.0 / 60.0;
+
+// A pixel differs once its CIE76 color distance passes this (~2.3 is just noticeable),
+// and a frame fails once more than this fraction of its pixels differ
+const MAX_DELTA_E: f32 = 4.0;
+const MAX_DIFFERENT_PIXELS: f32 = 0.005;
+
+fn mailbox() -> ModelData {
+    ModelLoader::load(crate::cli::DEFAULT_MODEL, &LoadOptions::default()).expect("mailbox asset loads")
+}
+
+// Step the scene `steps` times at a fixed dt, then render a single frame at the last step.
+// Particles run on the CPU, whose seeded stream is the same on every machine
+fn render_frame(model: &ModelData, particles: &ParticleConfig, passes: Passes, steps: u32) -> RgbaImage {
+    let (device, queue) = pollster::block_on(headless::request_device());
+    let particles = ParticleConfig { simulation: SimulationBackend::Cpu, ..particles.clone() };
+    let mut renderer = Renderer::new(device, queue, headless::COLOR_FORMAT, model, DEFAULT_MODEL_FIT, &particles, SEED);
+    renderer.passes = passes;
+    let target = OffscreenTarget::new(&renderer.device, WIDTH, HEIGHT);
+
+    for _ in 0..steps {
+        renderer.update(DT);
+    }
+    renderer.render(&target.view, (WIDTH, HEIGHT), 1.0);
+    target.read(&renderer.device, &renderer.queue)
+}
+
+fn assert_snapshot(name: &str, actual: &RgbaImage) {
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/snapshots").join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        std::fs::create_dir_all(reference_path.parent().unwrap()).unwrap();
+        actual.save(&reference_path).unwrap();
+        return;
+    }
+    let expected = match image::open(&reference_path) {
+        Ok(image) => image.to_rgba8(),
+        Err(e) => panic!("no reference image {} ({}), run with UPDATE_SNAPSHOTS=1 to record it", reference_path.display(), e),
+    };
+    assert_eq!(expected.dimensions(), actual.dimensions(), "snapshot {} changed size", name);
+
+    // Diff image: the reference in gray with every differing pixel in red
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    let mut different = 0;
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let delta_e = delta_e(*expected_pixel, *actual.get_pixel(x, y));
+        if delta_e > MAX_DELTA_E {
+            different += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            let gray = (luminance(*expected_pixel) * 0.5 * 255.0) as u8;
+            diff.put_pixel(x, y, Rgba([gray, gray, gray, 255]));
+        }
+    }
+
+    let fraction = different as f32 / (actual.width() * actual.height()) as f32;
+    if fraction > MAX_DIFFERENT_PIXELS {
+        let diff_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/snapshot-diffs");
+        std::fs::create_dir_all(&diff_dir).unwrap();
+        diff.save(diff_dir.join(format!("{}.diff.png", name))).unwrap();
+        actual.save(diff_dir.join(format!("{}.actual.png", name))).unwrap();
+        panic!("snapshot {} differs in {:.2}% of pixels (allowed {:.2}%), see {}",
+               name, fraction * 100.0, MAX_DIFFERENT_PIXELS * 100.0, diff_dir.display());
+    }
+}
+
+fn srgb_to_linear(channel: u8) -> f32 {
+    let c = channel as f32 / 255.0;
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+fn luminance(pixel: Rgba<u8>) -> f32 {
+    0.2126 * srgb_to_linear(pixel[0]) + 0.7152 * srgb_to_linear(pixel[1]) + 0.0722 * srgb_to_linear(pixel[2])
+}
+
+// sRGB -> CIE L*a*b* (D65)
+fn to_lab(pixel: Rgba<u8>) -> [f32; 3] {
+    let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(srgb_to_linear);
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.9505;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.089;
+    let f = |t: f32| if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 };
+    [116.0 * f(y) - 16.0, 500.0 * (f(x) - f(y)), 200.0 * (f(y) - f(z))]
+}
+
+fn delta_e(a: Rgba<u8>, b: Rgba<u8>) -> f32 {
+    let (a, b) = (to_lab(a), to_lab(b));
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[test]
+fn background_pass() {
+    let passes = Passes { background: true, model: false, particles: false };
+    assert_snapshot("background", &render_frame(&mailbox(), &ParticleConfig::default(), passes, 30));
+}
+
+#[test]
+fn model_pass() {
+    let passes = Passes { background: false, model: true, particles: false };
+    assert_snapshot("model", &render_frame(&mailbox(), &ParticleConfig::default(), passes, 30));
+}
+
+#[test]
+fn particle_pass() {
+    let passes = Passes { background: false, model: false, particles: true };
+    assert_snapshot("particles", &render_frame(&mailbox(), &ParticleConfig::default(), passes, 60));
+}
+
+#[test]
+fn textured_particles() {
+    // Flipbook smoke blended by alpha over the additive fire and sparks, from a fixture rather
+    // than campfire.toml so the sample can grow without moving the reference
+    let scene = ParticleConfig::load("tests/fixtures/textured_particles.toml").expect("textured particles fixture loads");
+    let passes = Passes { background: true, model: false, particles: true };
+    assert_snapshot("textured_particles", &render_frame(&mailbox(), &scene, passes, 90));
+}
+
+#[test]
+fn soft_particles() {
+    // The fire moved forward into the mailbox, fading where it crosses the model
+    let mut fire = ParticleConfig::default();
+    if let EmitterShape::Disk { center, .. } = &mut fire.emitters[0].shape {
+        *center = [0.0, 0.0, 150.0];
+    }
+    let passes = Passes { background: false, model: true, particles: true };
+    assert_snapshot("soft_particles", &render_frame(&mailbox(), &fire, passes, 60));
+}
+
+#[test]
+fn all_passes() {
+    assert_snapshot("full", &render_frame(&mailbox(), &ParticleConfig::default(), Passes::ALL, 60));
+}
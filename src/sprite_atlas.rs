@@ -0,0 +1,127 @@
+// Every emitter's sprite texture packed into one image, so particles from all emitters can be
+// sorted together and drawn in a single draw call
+use crate::emitter::ParticleConfig;
+use crate::texture::{SamplerSettings, TextureData};
+use image::RgbaImage;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// Pixels around each sprite repeating its edge, so filtering never picks up a neighbour
+const GUTTER: u32 = 1;
+
+pub struct SpriteAtlas {
+    pub data: TextureData,
+    // Per emitter, its sheet's UV rectangle (x, y, width, height); None draws the soft dot
+    pub regions: Vec<Option<[f32; 4]>>,
+}
+
+impl SpriteAtlas {
+    // Loads each texture once; one that fails to load (or doesn't fit in `max_size`) leaves its
+    // emitters with the soft dot
+    pub fn build(config: &ParticleConfig, max_size: u32) -> Self {
+        let mut images = Vec::new();
+        let mut image_indices: HashMap<PathBuf, Option<usize>> = HashMap::new();
+        let emitter_images: Vec<Option<usize>> = config.emitters.iter().map(|emitter| {
+            let path = emitter.sprite.texture.as_ref()?;
+            *image_indices.entry(path.clone()).or_insert_with(|| match image::open(path) {
+                Ok(image) => {
+                    let image = image.to_rgba8();
+                    println!("🖼️ Using particle texture: {} ({}x{})", path.display(), image.width(), image.height());
+                    images.push(image);
+                    Some(images.len() - 1)
+                }
+                Err(e) => {
+                    println!("Warning: could not load particle texture '{}': {}", path.display(), e);
+                    None
+                }
+            })
+        }).collect();
+
+        let (data, image_regions) = pack(&images, max_size);
+        let regions = emitter_images.iter().map(|image| image.and_then(|i| image_regions[i])).collect();
+        Self { data, regions }
+    }
+}
+
+// Stacks the images top to bottom; images past `max_size` are left out (None)
+fn pack(images: &[RgbaImage], max_size: u32) -> (TextureData, Vec<Option<[f32; 4]>>) {
+    let width = images.iter().map(|image| image.width() + 2 * GUTTER).max().unwrap_or(1);
+    let mut placed = Vec::with_capacity(images.len());
+    let mut height = 0;
+    for image in images {
+        let cell_height = image.height() + 2 * GUTTER;
+        if width > max_size || height + cell_height > max_size {
+            println!("Warning: particle texture ({}x{}) doesn't fit in the sprite atlas", image.width(), image.height());
+            placed.push(None);
+            continue;
+        }
+        placed.push(Some(height));
+        height += cell_height;
+    }
+    let height = height.max(1);
+
+    let mut atlas = RgbaImage::from_pixel(width, height, image::Rgba([255, 255, 255, 255]));
+    let mut regions = Vec::with_capacity(images.len());
+    for (image, top) in images.iter().zip(placed) {
+        let Some(top) = top else {
+            regions.push(None);
+            continue;
+        };
+        // The image plus its gutter, clamping to the image's own edge
+        for y in 0..image.height() + 2 * GUTTER {
+            for x in 0..image.width() + 2 * GUTTER {
+                let source_x = x.saturating_sub(GUTTER).min(image.width() - 1);
+                let source_y = y.saturating_sub(GUTTER).min(image.height() - 1);
+                atlas.put_pixel(x, top + y, *image.get_pixel(source_x, source_y));
+            }
+        }
+        regions.push(Some([
+            GUTTER as f32 / width as f32,
+            (top + GUTTER) as f32 / height as f32,
+            image.width() as f32 / width as f32,
+            image.height() as f32 / height as f32,
+        ]));
+    }
+
+    let data = TextureData {
+        width,
+        height,
+        pixels: atlas.into_raw(),
+        sampler: SamplerSettings {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..Default::default()
+        },
+    };
+    (data, regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn packs_images_apart_with_their_edges_repeated() {
+        let red = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(2, 3, Rgba([0, 0, 255, 255]));
+        let (data, regions) = pack(&[red, blue], 64);
+        assert_eq!((data.width, data.height), (6, 9));
+        assert_eq!(regions[0], Some([1.0 / 6.0, 1.0 / 9.0, 4.0 / 6.0, 2.0 / 9.0]));
+        assert_eq!(regions[1], Some([1.0 / 6.0, 5.0 / 9.0, 2.0 / 6.0, 3.0 / 9.0]));
+
+        let atlas = RgbaImage::from_raw(data.width, data.height, data.pixels).unwrap();
+        // Gutter rows above each image are its own color
+        assert_eq!(atlas.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(atlas.get_pixel(3, 4), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn leaves_out_what_does_not_fit() {
+        let small = RgbaImage::new(2, 2);
+        let tall = RgbaImage::new(2, 30);
+        let (_, regions) = pack(&[small, tall], 16);
+        assert!(regions[0].is_some());
+        assert!(regions[1].is_none());
+    }
+}
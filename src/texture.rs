@@ -0,0 +1,100 @@
+// Texture helpers: CPU-side decoded images and their GPU upload
+
+// Sampler state copied from the asset (glTF samplers map onto these directly)
+#[derive(Copy, Clone, Debug)]
+pub struct SamplerSettings {
+    pub address_mode_u: wgpu::AddressMode,
+    pub address_mode_v: wgpu::AddressMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub min_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::MipmapFilterMode,
+}
+
+impl Default for SamplerSettings {
+    fn default() -> Self {
+        // glTF default: repeat + linear
+        Self {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::MipmapFilterMode::Linear,
+        }
+    }
+}
+
+// Decoded image, always expanded to 8-bit RGBA
+#[derive(Clone, Debug)]
+pub struct TextureData {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+    pub sampler: SamplerSettings,
+}
+
+impl TextureData {
+    // 1x1 white, so untextured materials can share the textured shader path
+    pub fn white() -> Self {
+        Self {
+            width: 1,
+            height: 1,
+            pixels: vec![255, 255, 255, 255],
+            sampler: SamplerSettings::default(),
+        }
+    }
+}
+
+pub struct Texture {
+    pub view: wgpu::TextureView,
+    pub sampler: wgpu::Sampler,
+}
+
+impl Texture {
+    pub fn from_data(device: &wgpu::Device, queue: &wgpu::Queue, data: &TextureData, label: &str) -> Self {
+        let size = wgpu::Extent3d {
+            width: data.width,
+            height: data.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            // Base color is authored in sRGB
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            wgpu::TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+                aspect: wgpu::TextureAspect::All,
+            },
+            &data.pixels,
+            wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * data.width),
+                rows_per_image: Some(data.height),
+            },
+            size,
+        );
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: data.sampler.address_mode_u,
+            address_mode_v: data.sampler.address_mode_v,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            mag_filter: data.sampler.mag_filter,
+            min_filter: data.sampler.min_filter,
+            mipmap_filter: data.sampler.mipmap_filter,
+            ..Default::default()
+        });
+
+        Self { view, sampler }
+    }
+}
@@ -0,0 +1,135 @@
+// Fixed-step simulation clock: real frame time is accumulated and spent in whole steps,
+// so the simulation runs the same at any frame rate. What's left over becomes the
+// interpolation factor between the last two simulated states
+pub struct FixedTimestep {
+    // Simulated seconds per step
+    pub step: f32,
+    // Multiplies real time before it's accumulated (0.5 = half speed)
+    pub time_scale: f32,
+    pub paused: bool,
+    // Longest frame we catch up on; slower frames make the simulation lag instead of stall
+    pub max_frame_time: f32,
+    accumulator: f32,
+    // Single steps requested while paused
+    pending_steps: u32,
+}
+
+// The simulation always advances in steps of this many seconds
+pub const SIMULATION_STEP: f32 = 1.0 / 60.0;
+
+pub const MIN_TIME_SCALE: f32 = 0.125;
+pub const MAX_TIME_SCALE: f32 = 8.0;
+
+impl FixedTimestep {
+    pub fn new(step: f32) -> Self {
+        Self {
+            step,
+            time_scale: 1.0,
+            paused: false,
+            max_frame_time: 0.25,
+            accumulator: 0.0,
+            pending_steps: 0,
+        }
+    }
+
+    // Feed one frame's real time and get back how many steps to simulate
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+        self.accumulator += frame_time.min(self.max_frame_time) * self.time_scale;
+        let steps = (self.accumulator / self.step).floor();
+        self.accumulator -= steps * self.step;
+        steps as u32
+    }
+
+    // How far between the previous and the current step the frame falls, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        if self.paused { 1.0 } else { self.accumulator / self.step }
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        self.pending_steps = 0;
+        // Paused frames draw the current step (alpha 1), so resume a full step in: the first
+        // frame back runs that step and interpolates on from where the paused view was
+        self.accumulator = self.step;
+    }
+
+    // Advance exactly one step on the next frame; only meaningful while paused
+    pub fn step_once(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    pub fn scale_time(&mut self, factor: f32) {
+        self.time_scale = (self.time_scale * factor).clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_do_not_depend_on_frame_rate() {
+        let run = |frame_time: f32, frames: u32| {
+            let mut timestep = FixedTimestep::new(SIMULATION_STEP);
+            (0..frames).map(|_| timestep.advance(frame_time)).sum::<u32>()
+        };
+        // One simulated second at 30, 60 and 144 Hz
+        assert_eq!(run(1.0 / 30.0, 30), 60);
+        assert_eq!(run(1.0 / 60.0, 60), 60);
+        assert!((59..=60).contains(&run(1.0 / 144.0, 144)));
+    }
+
+    #[test]
+    fn leftover_time_becomes_interpolation() {
+        let mut timestep = FixedTimestep::new(0.1);
+        assert_eq!(timestep.advance(0.25), 2);
+        assert!((timestep.alpha() - 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn paused_clock_only_runs_requested_steps() {
+        let mut timestep = FixedTimestep::new(SIMULATION_STEP);
+        timestep.toggle_pause();
+        assert_eq!(timestep.advance(1.0), 0);
+        timestep.step_once();
+        timestep.step_once();
+        assert_eq!(timestep.advance(1.0), 2);
+        assert_eq!(timestep.advance(1.0), 0);
+        assert_eq!(timestep.alpha(), 1.0);
+    }
+
+    #[test]
+    fn resuming_carries_on_from_the_paused_frame() {
+        let mut timestep = FixedTimestep::new(0.1);
+        // Where a frame draws, in steps: between the previous and the current step
+        let mut steps = 0;
+        let mut frame = |timestep: &mut FixedTimestep, frame_time: f32| {
+            steps += timestep.advance(frame_time);
+            steps as f32 - 1.0 + timestep.alpha()
+        };
+        assert!((frame(&mut timestep, 0.25) - 1.5).abs() < 1e-4);
+        timestep.toggle_pause();
+        let paused = frame(&mut timestep, 0.05);
+        assert_eq!(paused, 2.0);
+        timestep.toggle_pause();
+        // Moves on by the frame's time, not back to the previous step
+        let resumed = frame(&mut timestep, 0.05);
+        assert!((resumed - 2.5).abs() < 1e-4, "paused at {}, resumed at {}", paused, resumed);
+    }
+
+    #[test]
+    fn time_scale_changes_step_count_and_is_clamped() {
+        let mut timestep = FixedTimestep::new(0.1);
+        timestep.scale_time(2.0);
+        assert_eq!(timestep.advance(0.2), 4);
+        for _ in 0..10 {
+            timestep.scale_time(2.0);
+        }
+        assert_eq!(timestep.time_scale, MAX_TIME_SCALE);
+    }
+}
@@ -1,14 +1,16 @@
 use bytemuck::{Pod, Zeroable};
+use std::collections::VecDeque;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub normal: [f32; 3],
+    pub uv: [f32; 2],
 }
 
 impl Vertex {
-    pub const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    pub const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
     
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
@@ -34,13 +36,31 @@ pub struct ParticleInstance {
     pub position: [f32; 3],
     pub size: f32,
     pub color: [f32; 4],
+    // Sprite rotation in radians
+    pub rotation: f32,
+    // Sprite atlas region of the current frame; zero-sized for the soft dot
+    pub uv_rect: [f32; 4],
+    // BlendMode index
+    pub blend: f32,
+    // Fade distance in front of the scene, 0 for none
+    pub softness: f32,
+    // Orientation index, and the direction it turns the quad by: the motion to stretch along,
+    // the fixed axis or the world normal
+    pub orientation: f32,
+    pub axis: [f32; 3],
 }
 
 impl ParticleInstance {
-    pub const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
-        2 => Float32x3, // position
-        3 => Float32,   // size
-        4 => Float32x4, // color
+    pub const ATTRIBS: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+        3 => Float32x3,  // position
+        4 => Float32,    // size
+        5 => Float32x4,  // color
+        6 => Float32,    // rotation
+        7 => Float32x4,  // uv_rect
+        8 => Float32,    // blend
+        9 => Float32,    // softness
+        10 => Float32,   // orientation
+        11 => Float32x3, // axis
     ];
 
     pub fn desc() -> wgpu::VertexBufferLayout<'static> {
@@ -52,10 +72,55 @@ impl ParticleInstance {
     }
 }
 
+// GPU-side instance data for one segment of a particle's trail, a quad facing the camera
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct TrailSegment {
+    // The points before `start` and after `end`, which turn the segment's ends to meet its
+    // neighbours'
+    pub previous: [f32; 3],
+    pub start: [f32; 3],
+    pub end: [f32; 3],
+    pub next: [f32; 3],
+    // 0 for an unused segment
+    pub width: f32,
+    pub color: [f32; 4],
+    // How far along the whole trail `start` and `end` are, from 0 at the particle to 1 at the tail
+    pub along: [f32; 2],
+    pub uv_rect: [f32; 4],
+    pub blend: f32,
+    pub softness: f32,
+}
+
+impl TrailSegment {
+    pub const ATTRIBS: [wgpu::VertexAttribute; 10] = wgpu::vertex_attr_array![
+        3 => Float32x3,  // previous
+        4 => Float32x3,  // start
+        5 => Float32x3,  // end
+        6 => Float32x3,  // next
+        7 => Float32,    // width
+        8 => Float32x4,  // color
+        9 => Float32x2,  // along
+        10 => Float32x4, // uv_rect
+        11 => Float32,   // blend
+        12 => Float32,   // softness
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TrailSegment>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
 // CPU-side particle state
-#[derive(Copy, Clone, Debug)]
+#[derive(Clone, Debug)]
 pub struct Particle {
     pub position: [f32; 3],
+    // Position before the last step, for drawing between steps
+    pub prev_position: [f32; 3],
     pub velocity: [f32; 3],
     pub life: f32,
     pub max_life: f32,
@@ -63,4 +128,11 @@ pub struct Particle {
     pub phase: f32,
     pub waver_amp: f32,
     pub waver_freq: f32,
+    // Flipbook frame the particle was born on
+    pub start_frame: f32,
+    // Radians, and radians per second
+    pub rotation: f32,
+    pub angular_velocity: f32,
+    // Positions at the end of recent steps, newest first, for emitters with trails
+    pub trail: VecDeque<[f32; 3]>,
 }
\ No newline at end of file